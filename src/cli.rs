@@ -0,0 +1,289 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::{
+    fs,
+    io::{self, IsTerminal, Write},
+    path::PathBuf,
+    thread,
+};
+
+use clap::{Parser, Subcommand};
+use log::LevelFilter;
+use rusqlite::Connection;
+
+use crate::{
+    config, device,
+    fs_interface::{find_document, init_db, load_file_tree, scan_file_tree, sync_remote_to_local},
+    inbox::InboxWatcher,
+    integrity::{self, CheckSource},
+    intern_error::{Error, ErrorContext, ResultExt},
+    sync_plan::plan_sync,
+    transfer::{run_blocking, TransferKind},
+    typed_text::{document_text, to_markdown},
+};
+
+// Command line options, anything left unset falls back to noteworthy.ini
+#[derive(Parser, Debug)]
+#[command(version, about = "A terminal interface for a certain e-ink tablet")]
+pub struct Args {
+    /// Lowest level written to the log (off, error, warn, info, debug, trace)
+    #[arg(long, value_name = "LEVEL")]
+    pub log_level: Option<LevelFilter>,
+
+    /// File to write the log to instead of the one in the state directory
+    #[arg(long, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+// Things that can be done without opening the interface. They work on the local
+// mirror, so sync first for current results.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Write the typed text of a notebook as Markdown
+    ExtractText {
+        /// UUID or visible name of the notebook
+        document: String,
+
+        /// File to write to instead of stdout
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Pull the tablet's documents into the local mirror
+    Sync {
+        /// List what would change and ask before syncing
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Look for broken documents and files that belong to nothing
+    Check {
+        /// Check the tablet instead of the local mirror
+        #[arg(long)]
+        device: bool,
+
+        /// Make the safe fixes and push them to the tablet
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Upload documents dropped into the inboxes set up in noteworthy.ini as they arrive
+    Watch {
+        /// Look through the inboxes once and exit
+        #[arg(long)]
+        once: bool,
+    },
+}
+
+// Mirror contents loaded into a fresh db, as the interface would see them
+fn load_library() -> Result<Connection, Error> {
+    let db = Connection::open_in_memory()?;
+    let tree = scan_file_tree()?;
+
+    init_db(&db)?;
+    load_file_tree(&db, &tree.rows)?;
+
+    Ok(db)
+}
+
+pub fn run(command: Command) -> Result<(), Error> {
+    match command {
+        Command::ExtractText { document, output } => {
+            let db = load_library()?;
+            let (uuid, name) = find_document(&db, &document)?;
+
+            let pages = document_text(&uuid).context(ErrorContext::document(&uuid))?;
+
+            if pages.is_empty() {
+                return Err(Error::CommandError(format!("No typed text in {}", name)));
+            }
+
+            let markdown = to_markdown(&name, &pages);
+
+            match output {
+                Some(path) => fs::write(&path, markdown).context(ErrorContext::file(&path))?,
+                None => print!("{}", markdown),
+            }
+        }
+        Command::Sync { dry_run } => {
+            if !dry_run || preview_sync()? {
+                sync()?;
+            }
+        }
+        Command::Check { device, fix } => check(
+            if device {
+                CheckSource::Device
+            } else {
+                CheckSource::Mirror
+            },
+            fix,
+        )?,
+        Command::Watch { once } => watch(once)?,
+    }
+
+    Ok(())
+}
+
+fn sync() -> Result<(), Error> {
+    sync_remote_to_local(|update| eprint!("\r{:>3}% {:<12}", update.percent, update.rate))?;
+    eprintln!("\nSync complete");
+
+    Ok(())
+}
+
+// Print what a sync would change, giving back whether to go ahead with it. Only asks
+// when there's someone at the terminal to answer.
+fn preview_sync() -> Result<bool, Error> {
+    let mut plan = plan_sync()?;
+    plan.resolve_names(&load_library()?)?;
+
+    if plan.is_empty() {
+        println!("Nothing to sync, the mirror is up to date");
+        return Ok(false);
+    }
+
+    println!("Changes the sync brings from the tablet into the mirror:\n");
+
+    for change in &plan.changes {
+        println!("{}", change.get_text());
+    }
+    println!("\n{}", plan.summary());
+
+    if !io::stdin().is_terminal() {
+        return Ok(false);
+    }
+
+    print!("Sync now? [y/N] ");
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    Ok(answer.trim().eq_ignore_ascii_case("y"))
+}
+
+fn check(source: CheckSource, fix: bool) -> Result<(), Error> {
+    let problems = integrity::check(source)?;
+
+    if problems.is_empty() {
+        println!("No problems found in the {}", source.get_text());
+        return Ok(());
+    }
+
+    for problem in &problems {
+        let remedy = match &problem.fix {
+            Some(fix) => format!(" [fix: {}]", fix.get_text()),
+            None => String::new(),
+        };
+
+        println!(
+            "{:<6} {}\n       {}{}",
+            problem.severity_text(),
+            problem.path,
+            problem.description,
+            remedy
+        );
+    }
+
+    let fixable = problems
+        .iter()
+        .filter(|problem| problem.fix.is_some())
+        .count();
+
+    println!(
+        "\n{} problem(s) in the {}, {} with a safe fix",
+        problems.len(),
+        source.get_text(),
+        fixable
+    );
+
+    if fixable == 0 {
+        return Ok(());
+    }
+
+    if !fix {
+        println!("Run again with --fix to make them");
+        return Ok(());
+    }
+
+    for transfer in integrity::apply_fixes(&problems)? {
+        run_blocking(&transfer)?;
+    }
+
+    device::restart_ui()?;
+
+    println!("Fixed {} problem(s)", fixable);
+
+    Ok(())
+}
+
+// The inbox watching the interface does, without the interface. The mirror is read
+// again before every look so uploads from elsewhere are taken into account.
+fn watch(once: bool) -> Result<(), Error> {
+    if config::SETTINGS.inboxes.is_empty() {
+        return Err(Error::CommandError(String::from(
+            "No inboxes set up, add an [inbox] section to noteworthy.ini",
+        )));
+    }
+
+    for inbox in &config::SETTINGS.inboxes {
+        eprintln!("Watching {} -> {}", inbox.dir.display(), inbox.collection);
+    }
+
+    let mut watcher = InboxWatcher::default();
+
+    loop {
+        let db = load_library()?;
+        let (uploads, errors) = watcher.poll(&db);
+
+        for why in errors {
+            eprintln!("{}", why);
+        }
+
+        let mut uploaded = 0;
+
+        for upload in uploads {
+            let name = upload.get_name();
+
+            match run_blocking(&upload) {
+                Ok(_) => {
+                    log::info!("Uploaded {} from inbox", name);
+                    eprintln!("Uploaded {}", name);
+                    uploaded += 1;
+                }
+                Err(why) => {
+                    log::error!("Upload of {} failed: {}", name, why);
+                    eprintln!("Upload of {} failed\n{}", name, why);
+
+                    // Try again on the next look
+                    if let TransferKind::Upload { source, .. } = &upload {
+                        watcher.forget(source);
+                    }
+                }
+            }
+        }
+
+        // New documents only show up on the tablet once its interface restarts
+        if uploaded > 0 {
+            if let Err(why) = device::restart_ui() {
+                eprintln!("{}", why);
+            }
+        }
+
+        if once {
+            return Ok(());
+        }
+
+        thread::sleep(config::SETTINGS.inbox_interval);
+    }
+}
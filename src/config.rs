@@ -10,8 +10,14 @@ of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Publ
 You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
 */
 
-use std::path::Path;
+use std::{
+    env,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
+use configparser::ini::Ini;
+use log::LevelFilter;
 // #[macro_use]
 use lazy_static::lazy_static;
 use tui::style::Color;
@@ -26,6 +32,13 @@ pub struct Theme {
     pub highlight: Color,
 }
 
+// Local directory watched for new documents and the tablet collection they're filed into,
+// given as a UUID or a path of collection names like "Work/Inbox"
+pub struct Inbox {
+    pub dir: PathBuf,
+    pub collection: String,
+}
+
 pub struct Settings {
     pub default_local_dir: String,
     pub default_remote_dir: String,
@@ -33,6 +46,186 @@ pub struct Settings {
     pub save_data_loc: Box<Path>,
     pub backup_loc: String,
     pub remote_backup_loc: String,
+    pub ssh_name: String,
+    pub web_interface: String,
+    pub templates_path: String,
+    pub screens_path: String,
+    // Width and height of the panel in pixels
+    pub screen_resolution: (u32, u32),
+    pub state_dir: PathBuf,
+    pub log_level: LevelFilter,
+    pub log_file: PathBuf,
+    pub inboxes: Vec<Inbox>,
+    pub inbox_interval: Duration,
+}
+
+impl Settings {
+    // Start from the defaults and override whatever the ini file sets
+    fn load() -> Settings {
+        let mut settings = Settings {
+            default_local_dir: home_dir().to_string_lossy().to_string(),
+            default_remote_dir: String::from("root"),
+            show_hidden_files: false,
+            save_data_loc: state_dir().join("save.json").into(),
+            backup_loc: String::from("/home/schelcc/projects/noteworthy/raw-files"),
+            remote_backup_loc: String::from("/home/root/.local/share/remarkable/xochitl/"),
+            ssh_name: String::from("remarkable-wired"),
+            web_interface: String::from("http://10.11.99.1"),
+            templates_path: String::from("/usr/share/remarkable/templates/"),
+            screens_path: String::from("/usr/share/remarkable/"),
+            screen_resolution: (1404, 1872),
+            state_dir: state_dir(),
+            log_level: LevelFilter::Info,
+            log_file: state_dir().join("noteworthy.log"),
+            inboxes: Vec::new(),
+            inbox_interval: Duration::from_secs(10),
+        };
+
+        let mut ini = Ini::new();
+
+        let Some(path) = config_file() else {
+            return settings;
+        };
+
+        if ini.load(path).is_err() {
+            return settings;
+        }
+
+        let get = |key: &str| {
+            ini.get("settings", key)
+                .map(|val| val.trim_matches('"').to_string())
+        };
+
+        if let Some(val) = get("ssh_name") {
+            settings.ssh_name = val;
+        }
+        if let Some(val) = get("sync_path") {
+            settings.backup_loc = val;
+        }
+        if let Some(val) = get("remote_path") {
+            settings.remote_backup_loc = val;
+        }
+        if let Some(val) = get("web_interface") {
+            settings.web_interface = val;
+        }
+        if let Some(val) = get("templates_path") {
+            settings.templates_path = val;
+        }
+        if let Some(val) = get("screens_path") {
+            settings.screens_path = val;
+        }
+        // Given as "1404x1872"
+        if let Some((width, height)) = get("screen_resolution").and_then(|val| {
+            let (width, height) = val.split_once('x')?;
+            Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+        }) {
+            settings.screen_resolution = (width, height);
+        }
+        if let Some(val) = get("local_dir") {
+            settings.default_local_dir = expand_home(&val).to_string_lossy().to_string();
+        }
+        if let Ok(Some(val)) = ini.getboolcoerce("settings", "show_hidden_files") {
+            settings.show_hidden_files = val;
+        }
+        if let Some(val) = get("state_dir") {
+            settings.log_file = Path::new(&val).join("noteworthy.log");
+            settings.save_data_loc = Path::new(&val).join("save.json").into();
+            settings.state_dir = val.into();
+        }
+        if let Some(val) = get("log_level").and_then(|val| val.parse().ok()) {
+            settings.log_level = val;
+        }
+        if let Some(val) = get("save_data") {
+            settings.save_data_loc = Path::new(&val).into();
+        }
+        if let Some(val) = get("log_file") {
+            settings.log_file = val.into();
+        }
+        if let Some(val) = get("inbox_interval").and_then(|val| val.parse().ok()) {
+            settings.inbox_interval = Duration::from_secs(val);
+        }
+
+        // One "label = ~/Scans -> Work/Inbox" line per inbox. Keys are lowercased by the
+        // parser, so the directory and collection go in the value.
+        if let Some(section) = ini.get_map_ref().get("inbox") {
+            let mut lines: Vec<(&String, &String)> = section
+                .iter()
+                .filter_map(|(label, val)| Some((label, val.as_ref()?)))
+                .collect();
+            lines.sort();
+
+            for (_, val) in lines {
+                if let Some((dir, collection)) = val.trim_matches('"').split_once("->") {
+                    settings.inboxes.push(Inbox {
+                        dir: expand_home(dir.trim()),
+                        collection: collection.trim().to_string(),
+                    });
+                }
+            }
+        }
+
+        settings
+    }
+}
+
+// noteworthy.ini in the working directory wins over the one in the user's config dir
+fn config_file() -> Option<PathBuf> {
+    let local = PathBuf::from("noteworthy.ini");
+
+    if local.exists() {
+        return Some(local);
+    }
+
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+
+    Some(config_dir.join("noteworthy").join("noteworthy.ini"))
+}
+
+// $HOME, or the directory noteworthy was started from if there isn't one
+fn home_dir() -> PathBuf {
+    env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::current_dir().ok())
+        .unwrap_or_else(|| PathBuf::from("/"))
+}
+
+// Directory the local pane opens in. A configured one that doesn't exist falls back to
+// the home directory rather than stopping the app from starting.
+pub fn local_start_dir() -> PathBuf {
+    let configured = PathBuf::from(&SETTINGS.default_local_dir);
+
+    if configured.is_dir() {
+        return configured;
+    }
+
+    log::warn!(
+        "Local directory {} doesn't exist, opening the home directory instead",
+        configured.display()
+    );
+
+    [home_dir(), env::current_dir().unwrap_or_default()]
+        .into_iter()
+        .find(|dir| dir.is_dir())
+        .unwrap_or_else(|| PathBuf::from("/"))
+}
+
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), env::var_os("HOME")) {
+        (Some(rest), Some(home)) => Path::new(&home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+// Where logs, backups and other files noteworthy creates for itself live
+fn state_dir() -> PathBuf {
+    env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("state")))
+        .unwrap_or_else(env::temp_dir)
+        .join("noteworthy")
 }
 
 lazy_static! {
@@ -55,12 +248,5 @@ lazy_static! {
 
     //     output
     // };
-    pub static ref SETTINGS: Settings = Settings {
-        default_local_dir: String::from("/home/schelcc/"),
-        default_remote_dir: String::from("root"),
-        show_hidden_files: false,
-        save_data_loc: Path::new("/home/schelcc/projects/noteworthy/save.json").into(),
-        backup_loc: String::from("/home/schelcc/projects/noteworthy/raw-files"),
-        remote_backup_loc: String::from("/home/root/.local/share/remarkable/xochitl/"),
-    };
+    pub static ref SETTINGS: Settings = Settings::load();
 }
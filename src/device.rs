@@ -0,0 +1,292 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Everything that talks to the tablet goes through the system ssh client so that
+// host aliases, keys and agents from ~/.ssh/config just work.
+
+use std::{
+    fs::{self, File},
+    io::{Read, Write},
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::{
+    config,
+    intern_error::{Error, ErrorContext, ResultExt},
+};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// Quote a string for use as a single argument in a remote shell command
+pub fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+// Path of a file in the document store on the tablet
+pub fn remote_path(file_name: &str) -> String {
+    format!(
+        "{}/{}",
+        config::SETTINGS.remote_backup_loc.trim_end_matches('/'),
+        file_name
+    )
+}
+
+fn ssh(cmd: &str) -> Command {
+    log::debug!("ssh {} : {}", config::SETTINGS.ssh_name, cmd);
+
+    let mut command = Command::new("ssh");
+    command.arg(&config::SETTINGS.ssh_name).arg(cmd);
+    command
+}
+
+// ssh exits with 255 when it couldn't reach the tablet at all, anything else comes
+// from the command that was run
+fn ssh_failure(code: Option<i32>, stderr: &[u8]) -> Error {
+    let stderr = String::from_utf8_lossy(stderr).trim().to_string();
+
+    match code {
+        Some(255) | None => Error::ConnectionError(stderr),
+        Some(_) => Error::SSHError(stderr),
+    }
+}
+
+// Run a command on the tablet, returning its stdout
+pub fn run(cmd: &str) -> Result<String, Error> {
+    let context = || ErrorContext::RemoteCommand(String::from(cmd));
+
+    let output = ssh(cmd).stdin(Stdio::null()).output().context(context())?;
+
+    if !output.status.success() {
+        let why = ssh_failure(output.status.code(), &output.stderr).with_context(context());
+        log::warn!("{}", why);
+        return Err(why);
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+// Size in bytes of a file on the tablet
+pub fn file_size(remote: &str) -> Result<u64, Error> {
+    let output = run(&format!("stat -c %s {}", quote(remote)))?;
+
+    output
+        .trim()
+        .parse::<u64>()
+        .map_err(|_| Error::SSHError(format!("Unexpected size for {} : {}", remote, output)))
+}
+
+// Text of several files in the document store in one round trip, keyed by their path
+// in the store. `files` are shell words relative to the store, globs included.
+pub fn read_store_files(files: &str) -> Result<Vec<(String, String)>, Error> {
+    // A marker line before each file, metadata doesn't always end in a newline
+    let output = run(&format!(
+        "cd {} && for f in {}; do [ -f \"$f\" ] && echo \"==> $f\" && cat \"$f\" && echo; done; true",
+        quote(&config::SETTINGS.remote_backup_loc),
+        files
+    ))?;
+
+    let mut read: Vec<(String, String)> = Vec::new();
+
+    for line in output.lines() {
+        match (line.strip_prefix("==> "), read.last_mut()) {
+            (Some(file), _) => read.push((String::from(file), String::new())),
+            (None, Some((_, text))) => {
+                text.push_str(line);
+                text.push('\n');
+            }
+            (None, None) => (),
+        }
+    }
+
+    Ok(read)
+}
+
+// Every file in the document store and the directories directly under it
+pub fn list_store() -> Result<Vec<String>, Error> {
+    let output = run(&format!(
+        "cd {} && find . -mindepth 1 -maxdepth 2 -type f",
+        quote(&config::SETTINGS.remote_backup_loc)
+    ))?;
+
+    Ok(output
+        .lines()
+        .map(|line| String::from(line.trim_start_matches("./")))
+        .filter(|line| !line.is_empty())
+        .collect())
+}
+
+// Restart the tablet UI so it picks up files changed behind its back
+pub fn restart_ui() -> Result<(), Error> {
+    run("systemctl restart xochitl").map(|_| ())
+}
+
+// Copy `reader` into `writer` in chunks, handing the size of each chunk to `on_chunk`.
+// An error from `on_chunk` aborts the copy.
+fn pipe(
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+    on_chunk: &mut dyn FnMut(usize) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let mut buf = vec![0; CHUNK_SIZE];
+
+    loop {
+        let read = reader.read(&mut buf)?;
+
+        if read == 0 {
+            break;
+        }
+
+        writer.write_all(&buf[..read])?;
+        on_chunk(read)?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+// Stream a local file to a path on the tablet
+pub fn upload(
+    local: &Path,
+    remote: &str,
+    on_chunk: &mut dyn FnMut(usize) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let mut source = File::open(local).context(ErrorContext::file(local))?;
+
+    log::info!("Uploading {} to {}", local.display(), remote);
+
+    upload_from(&mut source, remote, on_chunk)
+}
+
+// Stream anything readable to a path on the tablet
+pub fn upload_from(
+    source: &mut impl Read,
+    remote: &str,
+    on_chunk: &mut dyn FnMut(usize) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let cmd = format!("cat > {}", quote(remote));
+    let context = || ErrorContext::RemoteCommand(cmd.clone());
+
+    let mut child = ssh(&cmd)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context(context())?;
+
+    let copied = match child.stdin.take() {
+        Some(mut stdin) => pipe(source, &mut stdin, on_chunk),
+        None => Err(Error::SSHError(String::from("Couldn't open ssh stdin"))),
+    };
+
+    if let Err(why) = copied {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(why);
+    }
+
+    let output = child.wait_with_output().context(context())?;
+
+    if !output.status.success() {
+        return Err(ssh_failure(output.status.code(), &output.stderr).with_context(context()));
+    }
+
+    Ok(())
+}
+
+// Stream a file from the tablet into a local file, removing it again if the copy fails
+pub fn download(
+    remote: &str,
+    local: &Path,
+    on_chunk: &mut dyn FnMut(usize) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let cmd = format!("cat {}", quote(remote));
+
+    log::info!("Downloading {} to {}", remote, local.display());
+
+    stream_command(ssh(&cmd), local, on_chunk, ssh_failure)
+        .context(ErrorContext::RemoteCommand(cmd))
+}
+
+// Fetch a notebook rendered to PDF from the tablet's web interface
+pub fn render_pdf(
+    uuid: &str,
+    local: &Path,
+    on_chunk: &mut dyn FnMut(usize) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let url = format!(
+        "{}/download/{}/placeholder",
+        config::SETTINGS.web_interface.trim_end_matches('/'),
+        uuid
+    );
+
+    log::info!("Rendering {} from {} to {}", uuid, url, local.display());
+
+    let mut child = Command::new("curl");
+    child
+        .arg("--silent")
+        .arg("--show-error")
+        .arg("--fail")
+        .arg(&url);
+
+    // Couldn't resolve, couldn't connect and timed out all mean the tablet isn't reachable
+    stream_command(child, local, on_chunk, |code, stderr| {
+        let stderr = String::from_utf8_lossy(stderr).trim().to_string();
+
+        match code {
+            Some(6 | 7 | 28) => Error::ConnectionError(stderr),
+            _ => Error::SSHError(format!("{} : {}", url, stderr)),
+        }
+    })
+}
+
+// Write the stdout of a command into a local file, `failure` turns a non-zero exit into an error
+fn stream_command(
+    mut command: Command,
+    local: &Path,
+    on_chunk: &mut dyn FnMut(usize) -> Result<(), Error>,
+    failure: impl Fn(Option<i32>, &[u8]) -> Error,
+) -> Result<(), Error> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let mut dest = File::create(local).context(ErrorContext::file(local))?;
+
+    let copied = match child.stdout.take() {
+        Some(mut stdout) => pipe(&mut stdout, &mut dest, on_chunk),
+        None => Err(Error::SSHError(String::from(
+            "Couldn't open command stdout",
+        ))),
+    };
+
+    if copied.is_err() {
+        let _ = child.kill();
+    }
+
+    let output = child.wait_with_output()?;
+
+    let result = match copied {
+        Err(why) => Err(why),
+        Ok(_) if !output.status.success() => Err(failure(output.status.code(), &output.stderr)),
+        Ok(_) => Ok(()),
+    };
+
+    if result.is_err() {
+        let _ = fs::remove_file(local);
+    }
+
+    result
+}
@@ -0,0 +1,420 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Turn Markdown and plain text into EPUB for the tablet, which can't open either.
+// Markdown is split into chapters at its headings, images next to the file are
+// packed into the book and anything else that can't be shown offline is left as its
+// alt text. Raw HTML is shown as text so the chapters stay valid XHTML.
+
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs,
+    io::{Cursor, Write},
+    path::{Path, PathBuf},
+};
+
+use pulldown_cmark::{html, CowStr, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use uuid::Uuid;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::intern_error::{Error, ErrorContext, ResultExt};
+
+pub struct Book {
+    pub title: String,
+    pub data: Vec<u8>,
+}
+
+struct Chapter {
+    title: String,
+    body: String,
+}
+
+// Image packed into the book, `name` is its path inside OEBPS
+struct Image {
+    source: PathBuf,
+    name: String,
+    media_type: &'static str,
+}
+
+// Local files that get converted instead of being refused
+pub fn is_convertible(source: &Path) -> bool {
+    source
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| matches!(ext.as_str(), "md" | "markdown" | "txt"))
+}
+
+pub fn convert(source: &Path) -> Result<Book, Error> {
+    let context = || ErrorContext::file(source);
+
+    let text = String::from_utf8_lossy(&fs::read(source).context(context())?).to_string();
+    let stem = source
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let is_text = source
+        .extension()
+        .is_some_and(|ext| ext.to_string_lossy().eq_ignore_ascii_case("txt"));
+
+    let (title, chapters, images) = if is_text {
+        let chapter = Chapter {
+            title: stem.clone(),
+            body: text_to_html(&text),
+        };
+
+        (stem, vec![chapter], Vec::new())
+    } else {
+        let dir = source.parent().unwrap_or(Path::new("."));
+        markdown_chapters(&text, &stem, dir)
+    };
+
+    let data = package(&title, &chapters, &images).context(context())?;
+
+    Ok(Book { title, data })
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Paragraphs at blank lines, line breaks kept within them
+fn text_to_html(text: &str) -> String {
+    let mut body = String::new();
+
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        let lines: Vec<String> = paragraph.lines().map(escape).collect();
+        let _ = writeln!(body, "<p>{}</p>", lines.join("<br />"));
+    }
+
+    body
+}
+
+fn media_type(path: &Path) -> Option<&'static str> {
+    match path.extension()?.to_string_lossy().to_lowercase().as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "svg" => Some("image/svg+xml"),
+        _ => None,
+    }
+}
+
+// Local image a Markdown link points at, relative to the file
+fn local_image(dir: &Path, url: &str) -> Option<PathBuf> {
+    if url.contains("://") || url.starts_with("data:") {
+        return None;
+    }
+
+    let url = url.split(['#', '?']).next().unwrap_or(url);
+
+    [url.to_string(), url.replace("%20", " ")]
+        .iter()
+        .map(|candidate| dir.join(candidate))
+        .find(|path| path.is_file() && media_type(path).is_some())
+}
+
+// Headings of the level chapters start at. A lone top heading is taken as the title,
+// the level under it splits the chapters instead.
+fn chapter_level(levels: &[HeadingLevel]) -> Option<HeadingLevel> {
+    let top = *levels.iter().min()?;
+    let count = levels.iter().filter(|level| **level == top).count();
+
+    match levels.iter().filter(|level| **level > top).min() {
+        Some(next) if count == 1 => Some(*next),
+        _ => Some(top),
+    }
+}
+
+fn markdown_chapters(text: &str, stem: &str, dir: &Path) -> (String, Vec<Chapter>, Vec<Image>) {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+
+    let events: Vec<Event> = Parser::new_ext(text, options).collect();
+
+    // Plain text of each heading, in order
+    let mut headings: Vec<(HeadingLevel, String)> = Vec::new();
+    let mut current: Option<(HeadingLevel, String)> = None;
+
+    for event in &events {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => current = Some((*level, String::new())),
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, title)) = current.as_mut() {
+                    title.push_str(text);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => headings.extend(current.take()),
+            _ => (),
+        }
+    }
+
+    let title = headings
+        .first()
+        .map(|(_, title)| title.trim().to_string())
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| String::from(stem));
+
+    let levels: Vec<HeadingLevel> = headings.iter().map(|(level, _)| *level).collect();
+    let split = chapter_level(&levels);
+
+    let mut chapters: Vec<(String, Vec<Event>)> = vec![(title.clone(), Vec::new())];
+    let mut heading_titles = headings.into_iter().map(|(_, title)| title);
+    let mut images: Vec<Image> = Vec::new();
+    let mut packed: HashMap<PathBuf, String> = HashMap::new();
+    let mut dropped_image = false;
+
+    for event in events {
+        let event = match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                let heading_title = heading_titles.next().unwrap_or_default();
+
+                if Some(level) == split {
+                    chapters.push((heading_title.trim().to_string(), Vec::new()));
+                }
+
+                event
+            }
+            Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => match local_image(dir, &dest_url) {
+                Some(path) => {
+                    let name = packed.entry(path.clone()).or_insert_with(|| {
+                        let ext = path
+                            .extension()
+                            .map(|ext| ext.to_string_lossy().to_lowercase())
+                            .unwrap_or_default();
+                        let name = format!("images/image{}.{}", images.len() + 1, ext);
+
+                        images.push(Image {
+                            media_type: media_type(&path).unwrap_or("image/png"),
+                            source: path.clone(),
+                            name: name.clone(),
+                        });
+
+                        name
+                    });
+
+                    Event::Start(Tag::Image {
+                        link_type,
+                        dest_url: CowStr::from(name.clone()),
+                        title,
+                        id,
+                    })
+                }
+                None => {
+                    log::info!("Leaving out image {}, it isn't a local file", dest_url);
+                    dropped_image = true;
+                    continue;
+                }
+            },
+            Event::End(TagEnd::Image) if dropped_image => {
+                dropped_image = false;
+                continue;
+            }
+            Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+            event => event,
+        };
+
+        if let Some((_, chapter)) = chapters.last_mut() {
+            chapter.push(event);
+        }
+    }
+
+    let chapters = chapters
+        .into_iter()
+        .filter(|(_, events)| !events.is_empty())
+        .map(|(title, events)| {
+            let mut body = String::new();
+            html::push_html(&mut body, events.into_iter());
+
+            Chapter { title, body }
+        })
+        .collect();
+
+    (title, chapters, images)
+}
+
+fn chapter_file(idx: usize) -> String {
+    format!("chapter{}.xhtml", idx + 1)
+}
+
+const STYLE: &str = "body { font-family: serif; line-height: 1.4; }
+pre { white-space: pre-wrap; font-size: 0.85em; border-left: 3px solid #999; padding-left: 0.6em; }
+code { font-family: monospace; }
+img { max-width: 100%; }
+table { border-collapse: collapse; }
+td, th { border: 1px solid #999; padding: 0.2em 0.4em; }
+blockquote { margin-left: 1em; padding-left: 0.6em; border-left: 3px solid #ccc; }
+";
+
+fn xhtml(title: &str, body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<!DOCTYPE html>
+<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">
+<head>
+<meta charset=\"UTF-8\" />
+<title>{}</title>
+<link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\" />
+</head>
+<body>
+{}</body>
+</html>
+",
+        escape(title),
+        body
+    )
+}
+
+// Zip everything up as EPUB 3, with an NCX table of contents for older readers
+fn package(title: &str, chapters: &[Chapter], images: &[Image]) -> Result<Vec<u8>, Error> {
+    let id = format!("urn:uuid:{}", Uuid::new_v4());
+    let modified = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ");
+
+    let mut manifest = String::new();
+    let mut spine = String::new();
+    let mut nav = String::new();
+    let mut ncx = String::new();
+
+    for (idx, chapter) in chapters.iter().enumerate() {
+        let file = chapter_file(idx);
+        let label = escape(&chapter.title);
+
+        let _ = writeln!(
+            manifest,
+            "<item id=\"chapter{}\" href=\"{}\" media-type=\"application/xhtml+xml\" />",
+            idx + 1,
+            file
+        );
+        let _ = writeln!(spine, "<itemref idref=\"chapter{}\" />", idx + 1);
+        let _ = writeln!(nav, "<li><a href=\"{}\">{}</a></li>", file, label);
+        let _ = writeln!(
+            ncx,
+            "<navPoint id=\"nav{0}\" playOrder=\"{0}\"><navLabel><text>{1}</text></navLabel><content src=\"{2}\" /></navPoint>",
+            idx + 1,
+            label,
+            file
+        );
+    }
+
+    for (idx, image) in images.iter().enumerate() {
+        let _ = writeln!(
+            manifest,
+            "<item id=\"image{}\" href=\"{}\" media-type=\"{}\" />",
+            idx + 1,
+            image.name,
+            image.media_type
+        );
+    }
+
+    let opf = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\">
+<metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">
+<dc:identifier id=\"book-id\">{}</dc:identifier>
+<dc:title>{}</dc:title>
+<dc:language>en</dc:language>
+<meta property=\"dcterms:modified\">{}</meta>
+</metadata>
+<manifest>
+<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\" />
+<item id=\"ncx\" href=\"toc.ncx\" media-type=\"application/x-dtbncx+xml\" />
+<item id=\"style\" href=\"style.css\" media-type=\"text/css\" />
+{}</manifest>
+<spine toc=\"ncx\">
+{}</spine>
+</package>
+",
+        id,
+        escape(title),
+        modified,
+        manifest,
+        spine
+    );
+
+    let toc = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<ncx xmlns=\"http://www.daisy.org/z3986/2005/ncx/\" version=\"2005-1\">
+<head><meta name=\"dtb:uid\" content=\"{}\" /></head>
+<docTitle><text>{}</text></docTitle>
+<navMap>
+{}</navMap>
+</ncx>
+",
+        id,
+        escape(title),
+        ncx
+    );
+
+    let nav = xhtml(
+        title,
+        &format!(
+            "<nav epub:type=\"toc\">\n<h1>Contents</h1>\n<ol>\n{}</ol>\n</nav>\n",
+            nav
+        ),
+    );
+
+    let container = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">
+<rootfiles>
+<rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\" />
+</rootfiles>
+</container>
+";
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    // Readers look for the uncompressed mimetype right at the start of the archive
+    zip.start_file(
+        "mimetype",
+        SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+    )?;
+    zip.write_all(b"application/epub+zip")?;
+
+    let mut files: Vec<(String, Vec<u8>)> = vec![
+        (String::from("META-INF/container.xml"), container.into()),
+        (String::from("OEBPS/content.opf"), opf.into_bytes()),
+        (String::from("OEBPS/toc.ncx"), toc.into_bytes()),
+        (String::from("OEBPS/nav.xhtml"), nav.into_bytes()),
+        (String::from("OEBPS/style.css"), STYLE.into()),
+    ];
+
+    for (idx, chapter) in chapters.iter().enumerate() {
+        files.push((
+            format!("OEBPS/{}", chapter_file(idx)),
+            xhtml(&chapter.title, &chapter.body).into_bytes(),
+        ));
+    }
+
+    for image in images {
+        let data = fs::read(&image.source).context(ErrorContext::file(&image.source))?;
+        files.push((format!("OEBPS/{}", image.name), data));
+    }
+
+    for (name, data) in files {
+        zip.start_file(name, deflated)?;
+        zip.write_all(&data)?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
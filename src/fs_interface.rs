@@ -10,12 +10,22 @@ of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Publ
 You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
 */
 
-use ::glob::{self, glob};
-use glob::GlobError;
-use rusqlite::{self, named_params, types::FromSql, Connection, Result};
-use std::{fs, path::PathBuf, sync::Arc};
-
-use crate::{config, intern_error};
+use ::glob::glob;
+use rusqlite::{self, named_params, types::FromSql, Connection, OptionalExtension, Result};
+use serde_json::json;
+use std::{
+    fs,
+    io::{BufReader, Read},
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    config,
+    intern_error::{Error, ErrorContext, ResultExt},
+};
 
 // use crate::intern_error;
 
@@ -88,13 +98,18 @@ impl From<String> for MetadataType {
 }
 
 #[derive(Debug, Default)]
-struct Metadata {
+pub struct Metadata {
     uuid: String,
     name: String,
     last_modified: String,
+    last_opened: String,
     parent: String,
     pinned: bool,
     object_type: MetadataType,
+    file_type: String,
+    size: u64,
+    // (tag, page id) pairs, the page id is empty for tags on the whole document
+    tags: Vec<(String, String)>,
 }
 
 impl MetadataType {
@@ -106,7 +121,7 @@ impl MetadataType {
         }
     }
 
-    fn as_str(&self) -> &str {
+    pub fn as_str(&self) -> &str {
         match &self {
             Self::DocumentType => "DocumentType",
             Self::CollectionType => "CollectionType",
@@ -118,116 +133,585 @@ impl MetadataType {
 }
 
 impl Metadata {
-    fn from_file(path: Result<PathBuf, GlobError>) -> Metadata {
-        let file_name = String::from(
-            path.as_ref()
-                .unwrap()
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .split('.')
-                .next()
-                .unwrap(),
-        );
-        //fix
-        let file = fs::read_to_string(path.unwrap()).expect("foo");
-
-        let mut row = Metadata {
-            uuid: file_name,
-            ..Default::default()
+    fn from_file(path: &Path) -> Result<Metadata, Error> {
+        let uuid = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let file = fs::read_to_string(path).context(ErrorContext::file(path))?;
+
+        let value: serde_json::Value =
+            serde_json::from_str(&file).context(ErrorContext::file(path))?;
+
+        // Timestamps are strings on current firmware but numbers have been seen in the wild
+        let timestamp = |key: &str| match &value[key] {
+            serde_json::Value::String(val) => val.clone(),
+            serde_json::Value::Number(val) => val.to_string(),
+            _ => String::new(),
         };
 
-        for line in file.lines() {
-            let trim_line = line.trim();
-            let kv_pair = trim_line.replace('\"', "");
+        let object_type = MetadataType::from_str(value["type"].as_str().unwrap_or_default());
 
-            let (key, value) = (kv_pair.split(':').next(), kv_pair.split(':').nth(1));
+        let (file_type, size, tags) = match object_type {
+            MetadataType::DocumentType => {
+                let content = read_content(&uuid).unwrap_or_default();
 
-            match key {
-                None => (),
-                Some(res) => match res {
-                    // TODO: Clean up .unwrap() usage
-                    "lastModified" => {
-                        row.last_modified = value.unwrap().trim().to_string().replace(',', "")
-                    }
-                    "parent" => {
-                        if value.as_ref().unwrap().to_string().trim().replace(',', "") == "" {
-                            row.parent = String::from("root");
-                        } else {
-                            row.parent = value.unwrap().to_string().trim().replace(',', "");
-                        }
-                    }
-                    "pinned" => row.pinned = value.unwrap().parse::<bool>().unwrap_or(false),
-                    "type" => {
-                        row.object_type = MetadataType::from_str(
-                            value.unwrap().trim().replace(['\"', ','], "").as_ref(),
-                        )
-                    }
-                    "visibleName" => {
-                        row.name = value.unwrap().to_string().trim().replace(',', "");
-                    }
-                    _ => (),
-                },
+                (
+                    content_file_type(&content),
+                    path.parent()
+                        .map(|dir| document_size(dir, &uuid))
+                        .unwrap_or(0),
+                    content_tags(&content),
+                )
             }
+            _ => (String::new(), 0, Vec::new()),
+        };
+
+        let parent = match value["parent"].as_str() {
+            None | Some("") => String::from("root"),
+            Some(val) => String::from(val),
+        };
+
+        Ok(Metadata {
+            uuid,
+            name: String::from(value["visibleName"].as_str().unwrap_or_default()),
+            last_modified: timestamp("lastModified"),
+            last_opened: timestamp("lastOpened"),
+            parent,
+            pinned: value["pinned"].as_bool().unwrap_or(false),
+            object_type,
+            file_type,
+            size,
+            tags,
+        })
+    }
+}
+
+// Result of reading the local mirror, files that couldn't be read are reported
+// alongside the ones that could rather than failing the whole scan
+#[derive(Default)]
+pub struct ScannedTree {
+    pub rows: Vec<Metadata>,
+    pub errors: Vec<Error>,
+}
+
+// Milliseconds since the epoch, the format the tablet uses for timestamps
+pub fn now_millis() -> String {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default()
+        .to_string()
+}
+
+// Metadata for a freshly created object, `parent` uses the db's "root" convention
+pub fn new_metadata(name: &str, parent: &str, object_type: MetadataType) -> serde_json::Value {
+    json!({
+        "deleted": false,
+        "lastModified": now_millis(),
+        "lastOpened": "0",
+        "lastOpenedPage": 0,
+        "metadatamodified": true,
+        "modified": true,
+        "parent": if parent == "root" { "" } else { parent },
+        "pinned": false,
+        "synced": false,
+        "type": object_type.as_str(),
+        "version": 1,
+        "visibleName": name,
+    })
+}
+
+fn read_content(uuid: &str) -> Option<serde_json::Value> {
+    let path = Path::new(&config::SETTINGS.backup_loc).join(format!("{}.content", uuid));
+
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+}
+
+fn content_file_type(content: &serde_json::Value) -> String {
+    content["fileType"]
+        .as_str()
+        .filter(|file_type| !file_type.is_empty())
+        .map(String::from)
+        .unwrap_or_else(|| String::from("notebook"))
+}
+
+// Document tags live in "tags", page tags in "pageTags" with the id of their page
+fn content_tags(content: &serde_json::Value) -> Vec<(String, String)> {
+    let mut tags = Vec::new();
+
+    for (key, page_key) in [("tags", None), ("pageTags", Some("pageId"))] {
+        for tag in content[key].as_array().into_iter().flatten() {
+            let Some(name) = tag["name"].as_str() else {
+                continue;
+            };
+
+            let page = page_key
+                .and_then(|page_key| tag[page_key].as_str())
+                .unwrap_or_default();
+
+            let tag = (String::from(name), String::from(page));
+
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+    }
+
+    tags
+}
+
+// Ids of a document's pages in reading order. Older firmware keeps a plain list in
+// "pages", 3.x keeps "cPages" entries ordered by their "idx" and marks removed ones.
+pub fn document_pages(uuid: &str) -> Vec<String> {
+    let content = read_content(uuid).unwrap_or_default();
+
+    if let Some(pages) = content["cPages"]["pages"].as_array() {
+        let mut pages: Vec<(&str, &str)> = pages
+            .iter()
+            .filter(|page| page["deleted"]["value"].as_i64().unwrap_or(0) == 0)
+            .filter_map(|page| {
+                Some((
+                    page["idx"]["value"].as_str().unwrap_or_default(),
+                    page["id"].as_str()?,
+                ))
+            })
+            .collect();
+
+        pages.sort_by(|a, b| a.0.cmp(b.0));
+
+        return pages.into_iter().map(|(_, id)| String::from(id)).collect();
+    }
+
+    content["pages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|page| page.as_str().map(String::from))
+        .collect()
+}
+
+// The payload type recorded in a document's .content file in the local mirror
+// ("pdf", "epub" or "notebook")
+pub fn document_file_type(uuid: &str) -> String {
+    content_file_type(&read_content(uuid).unwrap_or_default())
+}
+
+// Bytes taken up by a document in `dir`: its payload plus everything in its page directory
+fn document_size(dir: &Path, uuid: &str) -> u64 {
+    fn dir_size(path: &Path) -> u64 {
+        fs::read_dir(path)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| match entry.file_type() {
+                        Ok(file_type) if file_type.is_dir() => dir_size(&entry.path()),
+                        _ => entry.metadata().map(|m| m.len()).unwrap_or(0),
+                    })
+                    .sum()
+            })
+            .unwrap_or(0)
+    }
+
+    let payload: u64 = ["pdf", "epub"]
+        .iter()
+        .filter_map(|ext| fs::metadata(dir.join(format!("{}.{}", uuid, ext))).ok())
+        .map(|m| m.len())
+        .sum();
+
+    payload + dir_size(&dir.join(uuid))
+}
+
+// Change a document's metadata in the local mirror the way the tablet expects an edit
+// to look, so the change survives being pushed back to the device
+pub fn edit_metadata(uuid: &str, edit: impl FnOnce(&mut serde_json::Value)) -> Result<(), Error> {
+    let path = Path::new(&config::SETTINGS.backup_loc).join(format!("{}.metadata", uuid));
+    let context = || ErrorContext::file(&path);
+
+    let mut value: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&path).context(context())?).context(context())?;
+
+    edit(&mut value);
+
+    value["version"] = json!(value["version"].as_u64().unwrap_or(0) + 1);
+    value["metadatamodified"] = json!(true);
+
+    fs::write(&path, serde_json::to_string_pretty(&value)?).context(context())
+}
+
+// Same as edit_metadata for the .content file. The metadata version is bumped as well
+// so the tablet notices the change.
+pub fn edit_content(uuid: &str, edit: impl FnOnce(&mut serde_json::Value)) -> Result<(), Error> {
+    let path = Path::new(&config::SETTINGS.backup_loc).join(format!("{}.content", uuid));
+    let context = || ErrorContext::file(&path);
+
+    let mut value: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&path).context(context())?).context(context())?;
+
+    edit(&mut value);
+
+    fs::write(&path, serde_json::to_string_pretty(&value)?).context(context())?;
+
+    edit_metadata(uuid, |_| ())
+}
+
+// Add or remove a tag on a whole document, page tags are left alone
+pub fn set_tag(db: &Connection, uuid: &str, tag: &str, present: bool) -> Result<(), Error> {
+    edit_content(uuid, |content| {
+        let mut tags = content["tags"].as_array().cloned().unwrap_or_default();
+
+        tags.retain(|existing| existing["name"].as_str() != Some(tag));
+
+        if present {
+            tags.push(json!({
+                "name": tag,
+                "timestamp": now_millis().parse::<u64>().unwrap_or_default(),
+            }));
         }
 
-        row
+        content["tags"] = json!(tags);
+    })
+    .context(ErrorContext::document(uuid))?;
+
+    db.execute(
+        "DELETE FROM tags WHERE uuid=:uuid AND tag=:tag AND page=''",
+        named_params! {":uuid" : uuid, ":tag" : tag},
+    )?;
+
+    if present {
+        db.execute(
+            "INSERT INTO tags (uuid, tag, page) VALUES (:uuid, :tag, '')",
+            named_params! {":uuid" : uuid, ":tag" : tag},
+        )?;
+    }
+
+    Ok(())
+}
+
+// Names are only accepted if they pick out a single document
+pub fn find_document(db: &Connection, document: &str) -> Result<(String, String), Error> {
+    let by_uuid = db
+        .query_row(
+            "SELECT uuid, name FROM objects WHERE uuid = ? AND object_type = 'DocumentType'",
+            [document],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+
+    if let Some(found) = by_uuid {
+        return Ok(found);
+    }
+
+    let mut stmt = db.prepare(
+        "SELECT uuid, name FROM objects WHERE name = ? AND object_type = 'DocumentType'",
+    )?;
+    let matches = stmt
+        .query_map([document], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<(String, String)>, _>>()?;
+
+    match matches.len() {
+        1 => Ok(matches.into_iter().next().unwrap_or_default()),
+        0 => Err(Error::CommandError(format!(
+            "No document called {:?} in the mirror",
+            document
+        ))),
+        count => Err(Error::CommandError(format!(
+            "{} documents are called {:?}, give the UUID instead",
+            count, document
+        ))),
     }
 }
 
-pub fn resolve_file_tree(db: Arc<Connection>) -> Result<(), crate::intern_error::Error> {
+// Uuid of a collection given by its uuid or by a path of names from My files, like
+// "Work/Inbox". An empty path or "root" is My files itself.
+pub fn find_collection(db: &Connection, collection: &str) -> Result<String, Error> {
+    if collection == "root" {
+        return Ok(String::from("root"));
+    }
+
+    let by_uuid = db
+        .query_row(
+            "SELECT uuid FROM objects WHERE uuid = ? AND object_type = 'CollectionType'",
+            [collection],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    if let Some(found) = by_uuid {
+        return Ok(found);
+    }
+
+    let mut stmt = db.prepare(
+        "SELECT uuid FROM objects
+        WHERE name = ? AND parent = ? AND object_type = 'CollectionType'",
+    )?;
+
+    let mut parent = String::from("root");
+
+    for name in collection.split('/').filter(|name| !name.is_empty()) {
+        let matches = stmt
+            .query_map([name, parent.as_str()], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+
+        parent = match matches.len() {
+            1 => matches.into_iter().next().unwrap_or_default(),
+            0 => {
+                return Err(Error::NotFoundError(format!(
+                    "No collection called {:?}",
+                    collection
+                )))
+            }
+            count => {
+                return Err(Error::NotFoundError(format!(
+                    "{} collections match {:?}, give the UUID instead",
+                    count, collection
+                )))
+            }
+        };
+    }
+
+    Ok(parent)
+}
+
+// Parent id of the tablet's trash
+pub const TRASH: &str = "trash";
+
+// Move an object to another collection, or to the trash
+pub fn set_parent(db: &Connection, uuid: &str, parent: &str) -> Result<(), Error> {
+    edit_metadata(uuid, |value| {
+        value["parent"] = json!(if parent == "root" { "" } else { parent })
+    })
+    .context(ErrorContext::document(uuid))?;
+
+    db.execute(
+        "UPDATE objects SET parent=:parent WHERE uuid=:uuid",
+        named_params! {":parent" : parent, ":uuid" : uuid},
+    )?;
+
+    Ok(())
+}
+
+pub fn set_pinned(db: &Connection, uuid: &str, pinned: bool) -> Result<(), Error> {
+    edit_metadata(uuid, |value| value["pinned"] = json!(pinned))
+        .context(ErrorContext::document(uuid))?;
+
+    db.execute(
+        "UPDATE objects SET pinned=:pinned WHERE uuid=:uuid",
+        named_params! {":pinned" : pinned, ":uuid" : uuid},
+    )?;
+
+    Ok(())
+}
+
+// Progress of a running sync as reported by rsync
+pub struct SyncProgress {
+    pub percent: u8,
+    pub rate: String,
+}
+
+pub fn init_db(db: &Connection) -> Result<(), Error> {
     let _ = db.execute("DROP TABLE objects", ());
+    let _ = db.execute("DROP TABLE tags", ());
 
     db.execute(
         "CREATE TABLE objects (
         uuid TEXT,
         name TEXT,
         last_modified TEXT,
+        last_opened TEXT,
         parent TEXT,
         pinned NUMBER,
-        object_type TEXT )",
+        object_type TEXT,
+        file_type TEXT,
+        size NUMBER )",
         (),
     )?;
 
-    let mut stmt = db.prepare(
-        "INSERT INTO objects VALUES (:uuid, :name, :last_modified, :parent, :pinned, :object_type)",
+    db.execute(
+        "CREATE TABLE tags (
+        uuid TEXT,
+        tag TEXT,
+        page TEXT )",
+        (),
     )?;
 
-    match glob("./raw-files/*.metadata") {
-        Err(why) => println!("[ERR] Failed to read glob pattern ({:?})", why),
-        Ok(paths) => {
-            paths
-                .map(Metadata::from_file)
-                .map(|f| {
-                    stmt.execute(named_params! {
-                        ":uuid" : f.uuid,
-                        ":name" : f.name,
-                        ":last_modified" : f.last_modified,
-                        ":parent" : f.parent,
-                        ":pinned" : f.pinned,
-                        ":object_type" : f.object_type.as_str(),
-                    })
-                })
-                .for_each(drop);
+    Ok(())
+}
+
+// Read every metadata file in the local mirror. Doesn't touch the db so it can
+// run off the UI thread.
+pub fn scan_file_tree() -> Result<ScannedTree, Error> {
+    let pattern = Path::new(&config::SETTINGS.backup_loc).join("*.metadata");
+
+    let mut tree = ScannedTree::default();
+
+    for path in glob(&pattern.to_string_lossy())? {
+        let row = match path {
+            Ok(path) => Metadata::from_file(&path),
+            Err(why) => {
+                let context = ErrorContext::file(why.path());
+                Err(Error::from(why.into_error()).with_context(context))
+            }
+        };
+
+        match row {
+            Ok(row) => tree.rows.push(row),
+            Err(why) => {
+                log::warn!("Skipping unreadable metadata: {}", why);
+                tree.errors.push(why)
+            }
         }
-    };
+    }
+
+    Ok(tree)
+}
+
+// Replace the contents of the objects table with a previously scanned tree
+pub fn load_file_tree(db: &Connection, rows: &[Metadata]) -> Result<(), Error> {
+    let tx = db.unchecked_transaction()?;
+
+    tx.execute("DELETE FROM objects", ())?;
+    tx.execute("DELETE FROM tags", ())?;
+
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO objects
+            (uuid, name, last_modified, last_opened, parent, pinned, object_type, file_type, size)
+            VALUES (:uuid, :name, :last_modified, :last_opened, :parent, :pinned, :object_type, :file_type, :size)",
+        )?;
+
+        for f in rows {
+            stmt.execute(named_params! {
+                ":uuid" : f.uuid,
+                ":name" : f.name,
+                ":last_modified" : f.last_modified,
+                ":last_opened" : f.last_opened,
+                ":parent" : f.parent,
+                ":pinned" : f.pinned,
+                ":object_type" : f.object_type.as_str(),
+                ":file_type" : f.file_type,
+                ":size" : f.size,
+            })?;
+        }
+
+        let mut stmt =
+            tx.prepare("INSERT INTO tags (uuid, tag, page) VALUES (:uuid, :tag, :page)")?;
+
+        for f in rows {
+            for (tag, page) in &f.tags {
+                stmt.execute(named_params! {":uuid" : f.uuid, ":tag" : tag, ":page" : page})?;
+            }
+        }
+    }
+
+    tx.commit()?;
 
     Ok(())
 }
 
-pub fn sync_remote_to_local() -> Result<(), crate::intern_error::Error> {
-    // Shell out and use rsync
-    let cmd = format!(
-        "rsync -a remarkable-wired:{} {}",
+// rsync pulling the tablet's document store into the mirror. `flags` only change what
+// rsync prints, so a sync and its preview always transfer the same files.
+fn rsync_command(flags: &str) -> String {
+    format!(
+        "rsync -a --no-inc-recursive {} {}:{} {}",
+        flags,
+        config::SETTINGS.ssh_name,
         config::SETTINGS.remote_backup_loc,
         config::SETTINGS.backup_loc
-    );
+    )
+}
+
+// Exit codes rsync uses for socket, protocol and timeout failures, 255 comes from ssh
+fn rsync_result(code: Option<i32>, stderr: &[u8], cmd: &str) -> Result<(), Error> {
+    let stderr = String::from_utf8_lossy(stderr).trim().to_string();
+    let context = ErrorContext::RemoteCommand(String::from(cmd));
+
+    log::debug!("rsync exited with {:?}", code);
+
+    match code {
+        Some(0) => Ok(()),
+        Some(10 | 12 | 30 | 35 | 255) => Err(Error::ConnectionError(stderr).with_context(context)),
+        _ => Err(Error::SyncError(stderr).with_context(context)),
+    }
+}
+
+pub fn sync_remote_to_local(mut progress: impl FnMut(SyncProgress)) -> Result<(), Error> {
+    // Shell out and use rsync
+    let cmd = rsync_command("--info=progress2");
+
+    let context = || ErrorContext::RemoteCommand(cmd.clone());
+
+    log::info!("Syncing: {}", cmd);
 
-    std::process::Command::new("sh")
+    let mut child = Command::new("sh")
         .arg("-c")
-        .arg(cmd)
-        .output()?;
+        .arg(&cmd)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context(context())?;
+
+    // Drained alongside stdout, a full stderr pipe would otherwise stall rsync
+    let stderr = child.stderr.take().map(|mut stderr| {
+        thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = stderr.read_to_end(&mut buf);
+            buf
+        })
+    });
 
-    Ok(())
+    // rsync rewrites its progress line with carriage returns, so split on both
+    if let Some(stdout) = child.stdout.take() {
+        let mut line = Vec::new();
+
+        for byte in BufReader::new(stdout).bytes() {
+            match byte.context(context())? {
+                b'\r' | b'\n' => {
+                    if let Some(update) = parse_rsync_progress(&String::from_utf8_lossy(&line)) {
+                        progress(update);
+                    }
+                    line.clear();
+                }
+                b => line.push(b),
+            }
+        }
+    }
+
+    let status = child.wait().context(context())?;
+    let stderr = stderr
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default();
+
+    rsync_result(status.code(), &stderr, &cmd)
+}
+
+// What a sync would do without doing it, one "flags|size|mtime|path" line per file
+pub fn dry_run_sync() -> Result<String, Error> {
+    let cmd = rsync_command("--dry-run --out-format='%i|%l|%M|%n'");
+
+    log::info!("Previewing sync: {}", cmd);
+
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(&cmd)
+        .stdin(Stdio::null())
+        .output()
+        .context(ErrorContext::RemoteCommand(cmd.clone()))?;
+
+    rsync_result(output.status.code(), &output.stderr, &cmd)?;
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+// Lines look like "  1,234,567  45%    1.23MB/s    0:00:10 (xfr#1, to-chk=5/7)"
+fn parse_rsync_progress(line: &str) -> Option<SyncProgress> {
+    let mut fields = line.split_whitespace().skip(1);
+
+    let percent = fields.next()?.strip_suffix('%')?.parse::<u8>().ok()?;
+    let rate = fields.next().unwrap_or_default().to_string();
+
+    Some(SyncProgress { percent, rate })
 }
@@ -0,0 +1,141 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Collect highlights made on the tablet into Markdown. They are read from the local
+// mirror: `<uuid>.highlights/<page id>.json` from older firmware and glyph items in
+// the v6 `<uuid>/<page id>.rm` files from 3.x.
+
+use std::{fmt::Write, fs, path::Path};
+
+use crate::{
+    config,
+    fs_interface::document_pages,
+    intern_error::{Error, ErrorContext, ResultExt},
+    rm,
+};
+
+pub struct Highlight {
+    // 1 based, in reading order
+    pub page: usize,
+    pub start: Option<u32>,
+    pub color: u32,
+    pub text: String,
+}
+
+// Labels for the highlighter colours, numbered as the tablet's pen colours
+fn color_label(color: u32) -> &'static str {
+    match color {
+        0 => "black",
+        1 | 8 => "grey",
+        2 => "white",
+        3 | 9 | 13 => "yellow",
+        4 | 10 => "green",
+        5 => "pink",
+        6 => "blue",
+        7 => "red",
+        11 => "cyan",
+        12 => "magenta",
+        _ => "highlight",
+    }
+}
+
+pub fn document_highlights(uuid: &str) -> Result<Vec<Highlight>, Error> {
+    let mirror = Path::new(&config::SETTINGS.backup_loc);
+    let mut highlights = Vec::new();
+
+    for (idx, page_id) in document_pages(uuid).iter().enumerate() {
+        let page = idx + 1;
+
+        let json = mirror
+            .join(format!("{}.highlights", uuid))
+            .join(format!("{}.json", page_id));
+
+        if json.exists() {
+            let context = || ErrorContext::file(&json);
+            let value: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(&json).context(context())?)
+                    .context(context())?;
+
+            // A list of lists, one inner list per highlighting stroke
+            for item in value["highlights"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|group| group.as_array())
+                .flatten()
+            {
+                if let Some(text) = item["text"].as_str() {
+                    highlights.push(Highlight {
+                        page,
+                        start: item["start"].as_u64().and_then(|s| u32::try_from(s).ok()),
+                        color: item["color"]
+                            .as_u64()
+                            .and_then(|c| u32::try_from(c).ok())
+                            .unwrap_or(3),
+                        text: String::from(text),
+                    });
+                }
+            }
+        }
+
+        let lines = mirror.join(uuid).join(format!("{}.rm", page_id));
+
+        if let Ok(data) = fs::read(&lines) {
+            // Older .rm versions predate glyph items
+            if !rm::is_v6(&data) {
+                continue;
+            }
+
+            for glyph in rm::blocks(&data)
+                .context(ErrorContext::file(&lines))?
+                .iter()
+                .filter_map(|block| block.glyph())
+            {
+                highlights.push(Highlight {
+                    page,
+                    start: glyph.start,
+                    color: glyph.color,
+                    text: glyph.text,
+                });
+            }
+        }
+    }
+
+    // Firmware that writes both formats stores every highlight twice
+    highlights.sort_by_key(|h| (h.page, h.start, h.text.clone()));
+    highlights.dedup_by(|a, b| a.page == b.page && a.text.trim() == b.text.trim());
+
+    Ok(highlights)
+}
+
+// One section per document, highlights grouped under their page
+pub fn to_markdown(title: &str, highlights: &[Highlight]) -> String {
+    let mut markdown = format!("# {}\n", title);
+    let mut page = 0;
+
+    for highlight in highlights {
+        if highlight.page != page {
+            page = highlight.page;
+            let _ = write!(markdown, "\n## Page {}\n", page);
+        }
+
+        markdown.push('\n');
+
+        for line in highlight.text.trim().lines() {
+            let _ = writeln!(markdown, "> {}", line);
+        }
+
+        let _ = writeln!(markdown, ">\n> — *{}*", color_label(highlight.color));
+    }
+
+    markdown
+}
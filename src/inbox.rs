@@ -0,0 +1,258 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Watched inbox directories. Documents dropped into a directory listed in the [inbox]
+// section of noteworthy.ini are uploaded to the collection it's mapped to and filed into
+// a sent/ subdirectory. Everything sent is recorded by content in the state dir, so a
+// file that turns up again is filed away without a second upload.
+
+use std::{
+    collections::HashSet,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::{self, Inbox},
+    fs_interface::{find_collection, now_millis},
+    intern_error::{Error, ErrorContext, ResultExt},
+    local,
+    transfer::{upload_file_type, TransferKind},
+};
+
+pub const SENT_DIR: &str = "sent";
+
+// Files changed more recently than this may still be being written by a scanner or browser
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct SentRecord {
+    // SHA-256 of the file's contents
+    digest: String,
+    name: String,
+    uuid: String,
+    // Milliseconds since the epoch
+    sent: String,
+}
+
+// Keeps track of what has been handed to the transfer queue between looks at the inboxes
+#[derive(Default)]
+pub struct InboxWatcher {
+    queued: HashSet<PathBuf>,
+    last_scan: Option<Instant>,
+    // Problems already reported, so a missing directory isn't raised on every pass
+    reported: HashSet<String>,
+}
+
+fn record_path() -> PathBuf {
+    config::SETTINGS.state_dir.join("inbox.json")
+}
+
+fn read_records() -> Vec<SentRecord> {
+    let path = record_path();
+
+    match fs::read_to_string(&path) {
+        Err(_) => Vec::new(),
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|why| {
+            log::warn!(
+                "Ignoring unreadable inbox record {}: {}",
+                path.display(),
+                why
+            );
+            Vec::new()
+        }),
+    }
+}
+
+fn write_records(records: &[SentRecord]) -> Result<(), Error> {
+    let path = record_path();
+
+    fs::create_dir_all(&config::SETTINGS.state_dir)
+        .context(ErrorContext::file(&config::SETTINGS.state_dir))?;
+    fs::write(&path, serde_json::to_string_pretty(records)?).context(ErrorContext::file(&path))
+}
+
+fn digest(path: &Path) -> Result<String, Error> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+
+    loop {
+        let read = file.read(&mut buf)?;
+
+        if read == 0 {
+            break;
+        }
+
+        hasher.update(&buf[..read]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+// Move a file into the sent/ directory next to it
+fn file_away(source: &Path) -> Result<PathBuf, Error> {
+    let sent = source
+        .parent()
+        .map(|dir| dir.join(SENT_DIR))
+        .unwrap_or_else(|| PathBuf::from(SENT_DIR));
+
+    fs::create_dir_all(&sent).context(ErrorContext::file(&sent))?;
+
+    let dest = local::free_path_in(&sent, source);
+    local::move_path(source, &dest)?;
+
+    Ok(dest)
+}
+
+// Record an uploaded inbox file and file it away. Called once the upload has gone
+// through, so a failed one is picked up again on the next look.
+pub fn mark_sent(source: &Path, uuid: &str) -> Result<(), Error> {
+    let mut records = read_records();
+
+    records.push(SentRecord {
+        digest: digest(source).context(ErrorContext::file(source))?,
+        name: source
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        uuid: String::from(uuid),
+        sent: now_millis(),
+    });
+
+    write_records(&records)?;
+
+    let dest = file_away(source)?;
+
+    log::info!("Filed {} into {}", source.display(), dest.display());
+
+    Ok(())
+}
+
+// Documents waiting in an inbox that haven't been queued yet. Ones already sent are
+// filed away on the spot.
+fn arrivals(
+    inbox: &Inbox,
+    queued: &HashSet<PathBuf>,
+    sent: &HashSet<String>,
+) -> Result<Vec<PathBuf>, Error> {
+    let context = || ErrorContext::file(&inbox.dir);
+
+    let mut found = Vec::new();
+
+    for entry in fs::read_dir(&inbox.dir).context(context())? {
+        let path = entry.context(context())?.path();
+
+        let hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+
+        if hidden || !path.is_file() || upload_file_type(&path).is_none() || queued.contains(&path)
+        {
+            continue;
+        }
+
+        let settled = fs::metadata(&path)
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age >= SETTLE_TIME);
+
+        if !settled {
+            continue;
+        }
+
+        if sent.contains(&digest(&path).context(ErrorContext::file(&path))?) {
+            log::info!("{} was uploaded before, not sending again", path.display());
+            file_away(&path)?;
+            continue;
+        }
+
+        found.push(path);
+    }
+
+    found.sort();
+
+    Ok(found)
+}
+
+impl InboxWatcher {
+    // Whether it's time for another look
+    pub fn due(&self) -> bool {
+        !config::SETTINGS.inboxes.is_empty()
+            && self
+                .last_scan
+                .is_none_or(|last| last.elapsed() >= config::SETTINGS.inbox_interval)
+    }
+
+    // Uploads for everything new in the inboxes, along with any problems not reported
+    // before. `db` is used to find the collections the inboxes file into.
+    pub fn poll(&mut self, db: &Connection) -> (Vec<TransferKind>, Vec<Error>) {
+        self.last_scan = Some(Instant::now());
+
+        let sent: HashSet<String> = read_records()
+            .into_iter()
+            .map(|record| record.digest)
+            .collect();
+
+        let mut uploads = Vec::new();
+        let mut errors = Vec::new();
+
+        for inbox in &config::SETTINGS.inboxes {
+            let found = find_collection(db, &inbox.collection)
+                .and_then(|parent| Ok((parent, arrivals(inbox, &self.queued, &sent)?)));
+
+            match found {
+                Ok((parent, paths)) => {
+                    for source in paths {
+                        log::info!("Inbox upload of {} queued", source.display());
+
+                        self.queued.insert(source.clone());
+
+                        uploads.push(TransferKind::Upload {
+                            source,
+                            parent: parent.clone(),
+                            remove_source: false,
+                            from_inbox: true,
+                        });
+                    }
+                }
+                Err(why) => {
+                    let why = why.with_context(ErrorContext::file(&inbox.dir));
+
+                    log::warn!("Inbox skipped: {}", why);
+
+                    if self.reported.insert(why.to_string()) {
+                        errors.push(why);
+                    }
+                }
+            }
+        }
+
+        (uploads, errors)
+    }
+
+    // Let a file whose upload failed be picked up again
+    pub fn forget(&mut self, source: &Path) {
+        self.queued.remove(source);
+    }
+}
@@ -0,0 +1,817 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Consistency checks for a document store, either the local mirror or the tablet's.
+// The tablet shrugs off most broken states by hiding whatever is affected, so these
+// look for the ones that leave documents invisible or files nobody owns. Fixes are
+// made in the mirror and pushed, so a check of the tablet wants a sync before fixing.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    config, device,
+    fs_interface::{edit_metadata, new_metadata, MetadataType, TRASH},
+    intern_error::{Error, ErrorContext, ResultExt, Severity},
+    local,
+    transfer::TransferKind,
+};
+
+// Collection orphans are moved into, made in My files when first needed
+pub const RECOVERED: &str = "Recovered";
+
+// Directories next to a document holding one file per page, named after the page id
+const PAGE_DIRS: [&str; 3] = ["thumbnails", "highlights", "textconversion"];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CheckSource {
+    Mirror,
+    Device,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Fix {
+    // Move the object into the Recovered collection
+    Recover,
+    // Remove files that belong to nothing, given relative to the store
+    Remove(Vec<String>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Problem {
+    pub severity: Severity,
+    pub uuid: String,
+    // Where the object sits in the library, as far as that can be worked out
+    pub path: String,
+    pub description: String,
+    pub fix: Option<Fix>,
+}
+
+// Files of a store along with the text of its .metadata and .content files
+#[derive(Default)]
+struct Store {
+    files: BTreeSet<String>,
+    texts: HashMap<String, String>,
+}
+
+struct Object {
+    name: String,
+    parent: String,
+    is_collection: bool,
+    deleted: bool,
+}
+
+impl CheckSource {
+    pub fn get_text(&self) -> &str {
+        match self {
+            Self::Mirror => "mirror",
+            Self::Device => "tablet",
+        }
+    }
+}
+
+impl Fix {
+    pub fn get_text(&self) -> String {
+        match self {
+            Self::Recover => format!("move to {}", RECOVERED),
+            Self::Remove(files) => format!("remove {} file(s)", files.len()),
+        }
+    }
+}
+
+impl Problem {
+    pub fn severity_text(&self) -> &str {
+        match self.severity {
+            Severity::High | Severity::Fatal => "high",
+            Severity::Mid | Severity::Transient => "medium",
+            Severity::Low => "low",
+        }
+    }
+}
+
+fn is_text(file: &str) -> bool {
+    !file.contains('/') && (file.ends_with(".metadata") || file.ends_with(".content"))
+}
+
+// Object a path in the store belongs to, "<uuid>.metadata" and "<uuid>/<page>.rm"
+// both give "<uuid>"
+fn object_of(file: &str) -> &str {
+    file.split(['.', '/']).next().unwrap_or(file)
+}
+
+fn read_mirror() -> Result<Store, Error> {
+    let root = Path::new(&config::SETTINGS.backup_loc);
+    let context = || ErrorContext::file(root);
+
+    let mut store = Store::default();
+
+    for entry in fs::read_dir(root).context(context())? {
+        let entry = entry.context(context())?;
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        if entry.path().is_dir() {
+            for inner in fs::read_dir(entry.path()).context(ErrorContext::file(&entry.path()))? {
+                let inner = inner.context(ErrorContext::file(&entry.path()))?;
+
+                if inner.path().is_file() {
+                    store
+                        .files
+                        .insert(format!("{}/{}", name, inner.file_name().to_string_lossy()));
+                }
+            }
+        } else {
+            if is_text(&name) {
+                let text =
+                    fs::read_to_string(entry.path()).context(ErrorContext::file(&entry.path()))?;
+                store.texts.insert(name.clone(), text);
+            }
+
+            store.files.insert(name);
+        }
+    }
+
+    Ok(store)
+}
+
+fn read_device() -> Result<Store, Error> {
+    let files: BTreeSet<String> = device::list_store()?.into_iter().collect();
+    let texts = device::read_store_files("*.metadata *.content")?
+        .into_iter()
+        .collect();
+
+    Ok(Store { files, texts })
+}
+
+// Pages a document lists in its .content, None if it doesn't say
+fn content_pages(content: &Value) -> Option<HashSet<String>> {
+    let ids: HashSet<String> = match content["cPages"]["pages"].as_array() {
+        Some(pages) => pages
+            .iter()
+            .filter(|page| page["deleted"]["value"].as_i64().unwrap_or(0) == 0)
+            .filter_map(|page| page["id"].as_str().map(String::from))
+            .collect(),
+        None => content["pages"]
+            .as_array()?
+            .iter()
+            .filter_map(|page| page.as_str().map(String::from))
+            .collect(),
+    };
+
+    (!ids.is_empty()).then_some(ids)
+}
+
+// Page a per-page file belongs to, for "<uuid>/<page>.rm", "<uuid>/<page>-metadata.json"
+// and "<uuid>.thumbnails/<page>.png" and the like
+fn page_of<'a>(uuid: &str, file: &'a str) -> Option<&'a str> {
+    let (dir, name) = file.split_once('/')?;
+
+    let is_page_dir = dir == uuid
+        || PAGE_DIRS
+            .iter()
+            .any(|kind| dir == format!("{}.{}", uuid, kind));
+
+    if !is_page_dir {
+        return None;
+    }
+
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+
+    Some(stem.strip_suffix("-metadata").unwrap_or(stem))
+}
+
+// Display path of an object, e.g. "My files/Work/paper"
+fn library_path(uuid: &str, objects: &BTreeMap<String, Object>) -> String {
+    let mut parts = Vec::new();
+    let mut seen = HashSet::from([uuid]);
+    let mut current = uuid;
+
+    loop {
+        let Some(object) = objects.get(current).filter(|object| !object.deleted) else {
+            parts.push(format!("<missing {}>", current));
+            break;
+        };
+
+        parts.push(object.name.clone());
+
+        match object.parent.as_str() {
+            "" | "root" => {
+                parts.push(String::from("My files"));
+                break;
+            }
+            TRASH => {
+                parts.push(String::from("Trash"));
+                break;
+            }
+            parent if !seen.insert(parent) => {
+                parts.push(String::from("<loop>"));
+                break;
+            }
+            parent => current = parent,
+        }
+    }
+
+    parts.reverse();
+    parts.join("/")
+}
+
+// Collections met following the parents of one until it comes back round to itself,
+// None if it never does
+fn loop_members<'a>(uuid: &'a str, objects: &'a BTreeMap<String, Object>) -> Option<Vec<&'a str>> {
+    let mut members = vec![uuid];
+    let mut current = uuid;
+
+    while let Some(object) = objects.get(current) {
+        if object.parent == uuid {
+            return Some(members);
+        }
+
+        if members.contains(&object.parent.as_str()) {
+            return None;
+        }
+
+        current = &object.parent;
+        members.push(current);
+    }
+
+    None
+}
+
+fn check_store(store: &Store) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut objects: BTreeMap<String, Object> = BTreeMap::new();
+
+    // Everything with readable metadata, the rest is reported as it's found
+    for (file, text) in store
+        .texts
+        .iter()
+        .filter(|(file, _)| file.ends_with(".metadata"))
+    {
+        let uuid = object_of(file);
+
+        match serde_json::from_str::<Value>(text) {
+            Ok(value) => {
+                objects.insert(
+                    String::from(uuid),
+                    Object {
+                        name: String::from(value["visibleName"].as_str().unwrap_or_default()),
+                        parent: String::from(value["parent"].as_str().unwrap_or_default()),
+                        is_collection: value["type"] == "CollectionType",
+                        deleted: value["deleted"].as_bool().unwrap_or(false),
+                    },
+                );
+            }
+            Err(why) => problems.push(Problem {
+                severity: Severity::High,
+                uuid: String::from(uuid),
+                path: format!("<unreadable {}>", uuid),
+                description: format!("Metadata can't be read, the tablet won't show it: {}", why),
+                fix: None,
+            }),
+        }
+    }
+
+    let mut by_object: BTreeMap<&str, Vec<&String>> = BTreeMap::new();
+
+    for file in &store.files {
+        by_object.entry(object_of(file)).or_default().push(file);
+    }
+
+    // Files nothing owns, left behind by a crash or a half finished copy
+    for (uuid, files) in &by_object {
+        let has_metadata = store.files.contains(&format!("{}.metadata", uuid));
+
+        if has_metadata || Uuid::parse_str(uuid).is_err() {
+            continue;
+        }
+
+        problems.push(Problem {
+            severity: Severity::Mid,
+            uuid: String::from(*uuid),
+            path: format!("<no metadata {}>", uuid),
+            description: format!("{} file(s) without metadata", files.len()),
+            fix: Some(Fix::Remove(
+                files.iter().map(|file| (*file).clone()).collect(),
+            )),
+        });
+    }
+
+    for (uuid, object) in objects.iter().filter(|(_, object)| !object.deleted) {
+        let path = || library_path(uuid, &objects);
+
+        // A parent the tablet can't find hides the object and everything in it
+        match object.parent.as_str() {
+            "" | "root" | TRASH => (),
+            parent => {
+                let valid = objects
+                    .get(parent)
+                    .is_some_and(|parent| parent.is_collection && !parent.deleted);
+
+                if !valid {
+                    problems.push(Problem {
+                        severity: Severity::High,
+                        uuid: uuid.clone(),
+                        path: path(),
+                        description: format!(
+                            "Parent {} doesn't exist, it's invisible on the tablet",
+                            parent
+                        ),
+                        fix: Some(Fix::Recover),
+                    });
+                } else if loop_members(uuid, &objects)
+                    // Moving one member out of a loop is enough
+                    .is_some_and(|members| members.iter().min() == Some(&uuid.as_str()))
+                {
+                    problems.push(Problem {
+                        severity: Severity::High,
+                        uuid: uuid.clone(),
+                        path: path(),
+                        description: String::from(
+                            "Collections contain each other in a loop, they're invisible on the tablet",
+                        ),
+                        fix: Some(Fix::Recover),
+                    });
+                }
+            }
+        }
+
+        if object.is_collection {
+            continue;
+        }
+
+        let content = match store.texts.get(&format!("{}.content", uuid)) {
+            None => {
+                problems.push(Problem {
+                    severity: Severity::High,
+                    uuid: uuid.clone(),
+                    path: path(),
+                    description: String::from("No .content file, the tablet can't open it"),
+                    fix: None,
+                });
+                continue;
+            }
+            Some(text) => match serde_json::from_str::<Value>(text) {
+                Ok(content) => content,
+                Err(why) => {
+                    problems.push(Problem {
+                        severity: Severity::High,
+                        uuid: uuid.clone(),
+                        path: path(),
+                        description: format!(".content can't be read: {}", why),
+                        fix: None,
+                    });
+                    continue;
+                }
+            },
+        };
+
+        let file_type = content["fileType"].as_str().unwrap_or_default();
+
+        if matches!(file_type, "pdf" | "epub")
+            && !store.files.contains(&format!("{}.{}", uuid, file_type))
+        {
+            problems.push(Problem {
+                severity: Severity::High,
+                uuid: uuid.clone(),
+                path: path(),
+                description: format!("The {} file is missing", file_type.to_uppercase()),
+                fix: None,
+            });
+        }
+
+        let Some(pages) = content_pages(&content) else {
+            continue;
+        };
+
+        let files = by_object.get(uuid.as_str()).cloned().unwrap_or_default();
+
+        let stray: Vec<String> = files
+            .iter()
+            .filter(|file| page_of(uuid, file).is_some_and(|page| !pages.contains(page)))
+            .map(|file| (*file).clone())
+            .collect();
+
+        if !stray.is_empty() {
+            problems.push(Problem {
+                severity: Severity::Low,
+                uuid: uuid.clone(),
+                path: path(),
+                description: format!(
+                    "{} file(s) belong to pages the document no longer has",
+                    stray.len()
+                ),
+                fix: Some(Fix::Remove(stray)),
+            });
+        }
+
+        let no_strokes = pages
+            .iter()
+            .filter(|page| !store.files.contains(&format!("{}/{}.rm", uuid, page)))
+            .count();
+
+        if matches!(file_type, "" | "notebook") && no_strokes > 0 {
+            problems.push(Problem {
+                severity: Severity::Low,
+                uuid: uuid.clone(),
+                path: path(),
+                description: format!(
+                    "{} page(s) in .content have no .rm file, fine if they're blank",
+                    no_strokes
+                ),
+                fix: None,
+            });
+        }
+    }
+
+    // Names the tablet shows twice in the same place
+    let mut siblings: BTreeMap<(&str, &str), Vec<&String>> = BTreeMap::new();
+
+    for (uuid, object) in objects.iter().filter(|(_, object)| !object.deleted) {
+        let parent = if object.parent.is_empty() {
+            "root"
+        } else {
+            object.parent.as_str()
+        };
+        siblings
+            .entry((parent, object.name.as_str()))
+            .or_default()
+            .push(uuid);
+    }
+
+    for uuids in siblings.values().filter(|uuids| uuids.len() > 1) {
+        for uuid in uuids {
+            problems.push(Problem {
+                severity: Severity::Low,
+                uuid: (*uuid).clone(),
+                path: library_path(uuid, &objects),
+                description: format!("{} items here share this name", uuids.len()),
+                fix: None,
+            });
+        }
+    }
+
+    problems.sort_by(|a, b| {
+        b.severity
+            .cmp(&a.severity)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+
+    problems
+}
+
+pub fn check(source: CheckSource) -> Result<Vec<Problem>, Error> {
+    let store = match source {
+        CheckSource::Mirror => read_mirror()?,
+        CheckSource::Device => read_device()?,
+    };
+
+    log::info!(
+        "Checking {} files in the {}",
+        store.files.len(),
+        source.get_text()
+    );
+
+    Ok(check_store(&store))
+}
+
+// Uuid of the Recovered collection in the mirror, making it if there isn't one yet.
+// Newly made ones come with the files to push.
+fn recovered_collection() -> Result<(String, Vec<String>), Error> {
+    let store = read_mirror()?;
+
+    let existing = store.texts.iter().find_map(|(file, text)| {
+        let value: Value = serde_json::from_str(text).ok()?;
+
+        let matches = file.ends_with(".metadata")
+            && value["type"] == "CollectionType"
+            && value["visibleName"] == RECOVERED
+            && value["parent"].as_str().unwrap_or_default().is_empty()
+            && !value["deleted"].as_bool().unwrap_or(false);
+
+        matches.then(|| String::from(object_of(file)))
+    });
+
+    if let Some(uuid) = existing {
+        return Ok((uuid, Vec::new()));
+    }
+
+    let uuid = Uuid::new_v4().to_string();
+    let mirror = Path::new(&config::SETTINGS.backup_loc);
+
+    let metadata = mirror.join(format!("{}.metadata", uuid));
+    let content = mirror.join(format!("{}.content", uuid));
+
+    fs::write(
+        &metadata,
+        serde_json::to_string_pretty(&new_metadata(
+            RECOVERED,
+            "root",
+            MetadataType::CollectionType,
+        ))?,
+    )
+    .context(ErrorContext::file(&metadata))?;
+    fs::write(&content, json!({}).to_string()).context(ErrorContext::file(&content))?;
+
+    log::info!("Made collection {} for recovered documents", uuid);
+
+    Ok((
+        uuid.clone(),
+        vec![format!("{}.metadata", uuid), format!("{}.content", uuid)],
+    ))
+}
+
+// Files a fix works on, which have to be in the mirror before it can be made
+fn fix_files(problem: &Problem) -> Vec<String> {
+    match &problem.fix {
+        Some(Fix::Recover) => vec![format!("{}.metadata", problem.uuid)],
+        Some(Fix::Remove(files)) => files.clone(),
+        None => Vec::new(),
+    }
+}
+
+// Make the fixes to the mirror, giving back the transfers that carry them over to the
+// tablet. Removed files go to the local trash rather than being deleted outright, so
+// nothing is touched unless every file involved is in the mirror.
+pub fn apply_fixes(problems: &[Problem]) -> Result<Vec<TransferKind>, Error> {
+    let mirror = Path::new(&config::SETTINGS.backup_loc);
+    let mut transfers = Vec::new();
+    let mut recovered: Option<String> = None;
+
+    if let Some(problem) = problems.iter().find(|problem| {
+        fix_files(problem)
+            .iter()
+            .any(|file| !mirror.join(file).exists())
+    }) {
+        return Err(Error::NotFoundError(format!(
+            "{} isn't in the mirror yet, sync before fixing it",
+            problem.path
+        )));
+    }
+
+    for problem in problems {
+        let Some(fix) = &problem.fix else {
+            continue;
+        };
+
+        let (files, removed) = match fix {
+            Fix::Recover => {
+                let parent = match &recovered {
+                    Some(uuid) => uuid.clone(),
+                    None => {
+                        let (uuid, files) = recovered_collection()?;
+
+                        if !files.is_empty() {
+                            transfers.push(TransferKind::Push {
+                                uuid: uuid.clone(),
+                                name: String::from(RECOVERED),
+                                files,
+                                removed: Vec::new(),
+                            });
+                        }
+
+                        recovered = Some(uuid.clone());
+                        uuid
+                    }
+                };
+
+                edit_metadata(&problem.uuid, |value| value["parent"] = json!(parent))
+                    .context(ErrorContext::document(&problem.uuid))?;
+
+                (vec![format!("{}.metadata", problem.uuid)], Vec::new())
+            }
+            Fix::Remove(files) => {
+                for file in files {
+                    local::trash(&mirror.join(file))?;
+                }
+
+                // Directories the files were in go too once they're empty
+                for dir in files.iter().filter_map(|file| Path::new(file).parent()) {
+                    if !dir.as_os_str().is_empty() {
+                        let _ = fs::remove_dir(mirror.join(dir));
+                    }
+                }
+
+                (Vec::new(), files.clone())
+            }
+        };
+
+        log::info!("Fixed {}: {}", problem.path, fix.get_text());
+
+        transfers.push(TransferKind::Push {
+            uuid: problem.uuid.clone(),
+            name: problem
+                .path
+                .rsplit('/')
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            files,
+            removed,
+        });
+    }
+
+    Ok(transfers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: &str = "00000000-0000-4000-8000-00000000000a";
+    const B: &str = "00000000-0000-4000-8000-00000000000b";
+    const DOC: &str = "00000000-0000-4000-8000-0000000000d0";
+
+    fn add_collection(store: &mut Store, uuid: &str, name: &str, parent: &str) {
+        let metadata = json!({
+            "visibleName": name,
+            "parent": parent,
+            "type": "CollectionType",
+        });
+
+        store.files.insert(format!("{}.metadata", uuid));
+        store
+            .texts
+            .insert(format!("{}.metadata", uuid), metadata.to_string());
+    }
+
+    // A notebook with a .rm file for each of its pages
+    fn add_notebook(store: &mut Store, uuid: &str, name: &str, parent: &str, pages: &[&str]) {
+        let metadata = json!({
+            "visibleName": name,
+            "parent": parent,
+            "type": "DocumentType",
+        });
+        let content = json!({
+            "fileType": "notebook",
+            "cPages": {
+                "pages": pages.iter().map(|page| json!({ "id": page })).collect::<Vec<_>>(),
+            },
+        });
+
+        for file in ["metadata", "content"] {
+            store.files.insert(format!("{}.{}", uuid, file));
+        }
+
+        store
+            .texts
+            .insert(format!("{}.metadata", uuid), metadata.to_string());
+        store
+            .texts
+            .insert(format!("{}.content", uuid), content.to_string());
+
+        for page in pages {
+            store.files.insert(format!("{}/{}.rm", uuid, page));
+        }
+    }
+
+    #[test]
+    fn healthy_store() {
+        let mut store = Store::default();
+        add_collection(&mut store, A, "Work", "");
+        add_notebook(&mut store, DOC, "notes", A, &["p1", "p2"]);
+        store.files.insert(format!("{}.thumbnails/p1.png", DOC));
+
+        assert!(check_store(&store).is_empty());
+    }
+
+    #[test]
+    fn files_without_metadata() {
+        let mut store = Store::default();
+        store.files.insert(format!("{}.content", B));
+        store.files.insert(format!("{}/p1.rm", B));
+        // Not named after an object, left alone
+        store.files.insert(String::from(".tree"));
+
+        let problems = check_store(&store);
+
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].uuid, B);
+        assert_eq!(
+            problems[0].fix,
+            Some(Fix::Remove(vec![
+                format!("{}.content", B),
+                format!("{}/p1.rm", B)
+            ]))
+        );
+    }
+
+    #[test]
+    fn missing_parent() {
+        let mut store = Store::default();
+        add_notebook(&mut store, DOC, "notes", A, &["p1"]);
+
+        let problems = check_store(&store);
+
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].severity, Severity::High);
+        assert_eq!(problems[0].fix, Some(Fix::Recover));
+        assert_eq!(problems[0].path, format!("<missing {}>/notes", A));
+    }
+
+    #[test]
+    fn collection_in_trash_is_a_valid_parent() {
+        let mut store = Store::default();
+        add_collection(&mut store, A, "Old", TRASH);
+        add_notebook(&mut store, DOC, "notes", A, &["p1"]);
+
+        assert!(check_store(&store).is_empty());
+    }
+
+    #[test]
+    fn loop_reported_once() {
+        let mut store = Store::default();
+        add_collection(&mut store, A, "one", B);
+        add_collection(&mut store, B, "two", A);
+        add_notebook(&mut store, DOC, "notes", A, &["p1"]);
+
+        let problems = check_store(&store);
+
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].uuid, A);
+        assert_eq!(problems[0].fix, Some(Fix::Recover));
+    }
+
+    #[test]
+    fn stray_page_files() {
+        let mut store = Store::default();
+        add_notebook(&mut store, DOC, "notes", "", &["p1"]);
+
+        let stray = [
+            format!("{}/gone.rm", DOC),
+            format!("{}/gone-metadata.json", DOC),
+            format!("{}.thumbnails/gone.png", DOC),
+        ];
+
+        for file in &stray {
+            store.files.insert(file.clone());
+        }
+
+        let problems = check_store(&store);
+
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].severity, Severity::Low);
+
+        let Some(Fix::Remove(mut files)) = problems[0].fix.clone() else {
+            panic!("expected a remove fix, got {:?}", problems[0].fix);
+        };
+
+        files.sort();
+        let mut expected = stray.to_vec();
+        expected.sort();
+
+        assert_eq!(files, expected);
+    }
+
+    #[test]
+    fn unreadable_metadata_and_missing_pdf() {
+        let mut store = Store::default();
+        store.files.insert(format!("{}.metadata", A));
+        store
+            .texts
+            .insert(format!("{}.metadata", A), String::from("{ not json"));
+
+        add_notebook(&mut store, DOC, "paper", "", &[]);
+        store.texts.insert(
+            format!("{}.content", DOC),
+            json!({ "fileType": "pdf" }).to_string(),
+        );
+
+        let problems = check_store(&store);
+        let descriptions: Vec<&str> = problems
+            .iter()
+            .map(|problem| problem.description.as_str())
+            .collect();
+
+        assert_eq!(problems.len(), 2, "{:?}", descriptions);
+        assert!(problems.iter().all(|problem| problem.fix.is_none()));
+        assert!(descriptions[0].starts_with("Metadata can't be read"));
+        assert_eq!(descriptions[1], "The PDF file is missing");
+    }
+
+    #[test]
+    fn duplicate_names_in_one_place() {
+        let mut store = Store::default();
+        add_notebook(&mut store, A, "notes", "", &["p1"]);
+        add_notebook(&mut store, B, "notes", "root", &["p1"]);
+        add_notebook(&mut store, DOC, "notes", TRASH, &["p1"]);
+
+        let problems = check_store(&store);
+
+        assert_eq!(problems.len(), 2);
+        assert!(problems.iter().all(|problem| problem.uuid != DOC));
+    }
+}
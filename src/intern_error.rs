@@ -10,16 +10,45 @@ of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Publ
 You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
 */
 
+use std::{fmt, path::Path};
+
 use glob::PatternError;
 
+use crate::notification::NotificationType;
+
+// How bad an error is, which decides what the main loop does with it
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Severity {
+    // Expected in normal use, worth a passing mention
+    Low,
+    // Something went wrong but the app is still in a usable state
+    Mid,
+    // Likely to succeed if tried again, e.g. a dropped connection
+    Transient,
+    // The app may be out of step with the device and the user should know before carrying on
+    High,
+    // The app can't carry on
+    Fatal,
+}
+
+// What an error was about, so it can be traced back to a file or command
+#[derive(Debug)]
+pub enum ErrorContext {
+    File(String),
+    Document(String),
+    RemoteCommand(String),
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("[!ERR!] ! Placeholder error !")]
     PlaceholderError,
     #[error("[ERR] Crossterm : {0}")]
     CrosstermError(String),
+    #[error("[ERR] IO : {0}")]
+    IOError(#[from] std::io::Error),
     #[error("[ERR] SQLite : {0}")]
-    SQLiteError(String),
+    SQLiteError(#[from] rusqlite::Error),
     #[error("[ERR] Internal : Failed to retrieve index {0} from struct")]
     OutOfBoundsError(usize),
     #[error("[ERR] Internal : Cannot walk back any more")]
@@ -30,50 +59,152 @@ pub enum Error {
     ConfigparserError(String),
     #[error("[ERR] SSH2 : {0}")]
     SSHError(String),
+    #[error("[ERR] SSH : Connection to device failed : {0}")]
+    ConnectionError(String),
     #[error("[ERR] Internal : DB Connection not initialized, cannot populate DBBlock")]
     NoDBConnectionError,
     #[error("[ERR] Internal : Couldn't access item at index {0}")]
     VecAccessError(usize),
     #[error("[ERR] Internal : Couldn't remove selected item at index {0}")]
     VecRemoveError(usize),
-    #[error("[ERR] Internal : Glob error")]
-    GlobErr,
-    #[error("[ERR] Internal : Save data read failure")]
-    JSONParseErr,
+    #[error("[ERR] Internal : Glob error : {0}")]
+    GlobErr(#[from] PatternError),
+    #[error("[ERR] JSON : {0}")]
+    JSONParseErr(#[from] serde_json::Error),
+    #[error("[ERR] Sync : {0}")]
+    SyncError(String),
+    #[error("[ERR] Transfer : Cancelled")]
+    TransferCancelled,
+    #[error("[ERR] Transfer : Unsupported file type {0}")]
+    UnsupportedFileError(String),
+    #[error("[ERR] Image : {0}")]
+    ImageError(String),
+    #[error("[ERR] Notebook : Couldn't read .rm file : {0}")]
+    RmParseError(String),
+    #[error("[ERR] Command : {0}")]
+    CommandError(String),
+    #[error("[ERR] EPUB : {0}")]
+    EpubError(String),
+    #[error("[ERR] Pages : {0}")]
+    PageEditError(String),
+    #[error("[ERR] Library : {0}")]
+    NotFoundError(String),
+    #[error("[ERR] Templates : {0}")]
+    TemplateError(String),
+    #[error("{source}\n({context})")]
+    Context {
+        context: ErrorContext,
+        #[source]
+        source: Box<Error>,
+    },
 }
 
-impl From<std::io::Error> for Error {
-    fn from(value: std::io::Error) -> Error {
-        Error::CrosstermError(value.to_string())
+impl From<ssh2::Error> for Error {
+    fn from(value: ssh2::Error) -> Self {
+        Error::SSHError(value.to_string())
     }
 }
 
-impl From<rusqlite::Error> for Error {
-    fn from(value: rusqlite::Error) -> Error {
-        Error::SQLiteError(value.to_string())
+impl From<zip::result::ZipError> for Error {
+    fn from(value: zip::result::ZipError) -> Self {
+        Error::EpubError(value.to_string())
     }
 }
 
-impl From<ssh2::Error> for Error {
-    fn from(value: ssh2::Error) -> Self {
-        Error::SSHError(value.to_string())
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "file {}", path),
+            Self::Document(uuid) => write!(f, "document {}", uuid),
+            Self::RemoteCommand(cmd) => write!(f, "remote command `{}`", cmd),
+        }
     }
 }
 
-impl From<PatternError> for Error {
-    fn from(_value: PatternError) -> Self {
-        Self::GlobErr
+impl ErrorContext {
+    pub fn file(path: &Path) -> Self {
+        Self::File(path.to_string_lossy().to_string())
+    }
+
+    pub fn document(uuid: &str) -> Self {
+        Self::Document(String::from(uuid))
     }
 }
 
-impl From<serde_json::Error> for Error {
-    fn from(value: serde_json::Error) -> Self {
-        Self::JSONParseErr
+impl Error {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::Context { source, .. } => source.severity(),
+            Self::CrosstermError(_) | Self::NoDBConnectionError => Severity::Fatal,
+            Self::ConnectionError(_) => Severity::Transient,
+            Self::SQLiteError(_) => Severity::High,
+            Self::IOError(why) => match why.kind() {
+                std::io::ErrorKind::BrokenPipe
+                | std::io::ErrorKind::ConnectionAborted
+                | std::io::ErrorKind::ConnectionReset
+                | std::io::ErrorKind::Interrupted
+                | std::io::ErrorKind::TimedOut
+                | std::io::ErrorKind::UnexpectedEof => Severity::Transient,
+                _ => Severity::Mid,
+            },
+            Self::OutOfBoundsError(_)
+            | Self::WalkBackError
+            | Self::VecAccessError(_)
+            | Self::VecRemoveError(_)
+            | Self::TransferCancelled
+            | Self::UnsupportedFileError(_)
+            | Self::PageEditError(_)
+            | Self::NotFoundError(_)
+            | Self::TemplateError(_) => Severity::Low,
+            Self::PlaceholderError
+            | Self::HexToRGBError(_)
+            | Self::ConfigparserError(_)
+            | Self::SSHError(_)
+            | Self::SyncError(_)
+            | Self::GlobErr(_)
+            | Self::JSONParseErr(_)
+            | Self::RmParseError(_)
+            | Self::CommandError(_)
+            | Self::EpubError(_)
+            | Self::ImageError(_) => Severity::Mid,
+        }
+    }
+
+    // The underlying error with any context peeled off
+    pub fn root(&self) -> &Error {
+        match self {
+            Self::Context { source, .. } => source.root(),
+            _ => self,
+        }
+    }
+
+    pub fn is_transient(&self) -> bool {
+        self.severity() == Severity::Transient
+    }
+
+    pub fn notification_type(&self) -> NotificationType {
+        match self.severity() {
+            Severity::Low => NotificationType::ErrorLow,
+            Severity::Mid | Severity::Transient => NotificationType::ErrorMid,
+            Severity::High | Severity::Fatal => NotificationType::ErrorHigh,
+        }
     }
+
+    pub fn with_context(self, context: ErrorContext) -> Self {
+        Self::Context {
+            context,
+            source: Box::new(self),
+        }
+    }
+}
+
+// Attach context to any result whose error converts into ours
+pub trait ResultExt<T> {
+    fn context(self, context: ErrorContext) -> Result<T, Error>;
 }
 
-// impl Error {
-//     fn to_string(self) -> String {
-//         self.
-//     }
-// }
+impl<T, E: Into<Error>> ResultExt<T> for Result<T, E> {
+    fn context(self, context: ErrorContext) -> Result<T, Error> {
+        self.map_err(|why| why.into().with_context(context))
+    }
+}
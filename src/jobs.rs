@@ -0,0 +1,329 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::{collections::BTreeMap, path::PathBuf, thread, time::Duration};
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::Style,
+    widgets::{Clear, Paragraph},
+    Frame,
+};
+
+use crate::{
+    config, device,
+    fs_interface::{scan_file_tree, sync_remote_to_local, ScannedTree},
+    integrity::{check, CheckSource, Problem},
+    intern_error::Error,
+    notification::NotificationWidget,
+    screens::{self, Screen, ScreenState},
+    sync_plan::{plan_sync, SyncPlan},
+    templates::{self, Template},
+    transfer::{TransferEvent, TransferId},
+    ui,
+};
+
+pub type JobId = usize;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum JobKind {
+    Sync,
+    // Dry run of a sync, listing what it would change
+    SyncPreview,
+    Index,
+    // Integrity check of the mirror or the tablet
+    Check(CheckSource),
+    // Templates listed in the tablet's templates.json
+    Templates,
+    // Whether each screen is the stock one
+    Screens,
+    // Change to the tablet's templates or screens, followed by a restart of its UI
+    Device(DeviceChange),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum DeviceChange {
+    InstallTemplate {
+        source: PathBuf,
+        name: String,
+        icon: String,
+        categories: Vec<String>,
+    },
+    RemoveTemplate {
+        filename: String,
+        name: String,
+    },
+    ReapplyTemplates,
+    InstallScreen {
+        screen: Screen,
+        source: PathBuf,
+    },
+    RestoreScreen(Screen),
+}
+
+// What a job hands back to the UI thread once it completes
+pub enum JobOutput {
+    None,
+    Index(ScannedTree),
+    SyncPlan(SyncPlan),
+    Check(Vec<Problem>),
+    Templates(Vec<Template>),
+    Screens(Vec<(Screen, ScreenState)>),
+    // What the device change did, for the notification
+    Device(String),
+}
+
+// Everything the main loop can receive besides terminal input
+pub enum AppEvent {
+    Progress(JobId, String),
+    Finished(JobId, Result<JobOutput, Error>),
+    Notify(NotificationWidget),
+    Transfer(TransferId, TransferEvent),
+    // A job started from somewhere other than the main loop, e.g. a confirmed sync preview
+    Spawn(JobKind),
+}
+
+// Times a job is attempted before a transient failure is reported as final
+pub const MAX_ATTEMPTS: u32 = 3;
+
+struct RunningJob {
+    kind: JobKind,
+    attempt: u32,
+    progress: Option<String>,
+}
+
+// Spawns long running work off the UI thread and keeps track of what is still going
+pub struct JobRunner {
+    sender: UnboundedSender<AppEvent>,
+    next_id: JobId,
+    running: BTreeMap<JobId, RunningJob>,
+}
+
+impl JobKind {
+    pub fn get_text(&self) -> &str {
+        match self {
+            Self::Sync => "Sync",
+            Self::SyncPreview => "Sync preview",
+            Self::Index => "Index",
+            Self::Check(_) => "Check",
+            Self::Templates => "Templates",
+            Self::Screens => "Screens",
+            Self::Device(change) => change.get_text(),
+        }
+    }
+
+    // Syncs and previews both work on the mirror, and device changes all rewrite files on
+    // the tablet and restart its UI, so only one of each group runs at a time
+    pub fn conflicts(&self, other: &JobKind) -> bool {
+        match (self, other) {
+            (Self::Sync | Self::SyncPreview, Self::Sync | Self::SyncPreview) => true,
+            (Self::Device(_), Self::Device(_)) => true,
+            _ => self == other,
+        }
+    }
+}
+
+impl DeviceChange {
+    pub fn get_text(&self) -> &str {
+        match self {
+            Self::InstallTemplate { .. } => "Template install",
+            Self::RemoveTemplate { .. } => "Template removal",
+            Self::ReapplyTemplates => "Template re-apply",
+            Self::InstallScreen { .. } => "Screen install",
+            Self::RestoreScreen(_) => "Screen restore",
+        }
+    }
+
+    // Make the change, then restart the tablet UI, which only reads templates and screens
+    // on start
+    fn run(&self) -> Result<String, Error> {
+        let message = match self {
+            Self::InstallTemplate {
+                source,
+                name,
+                icon,
+                categories,
+            } => {
+                let template = templates::install(source, name, icon, categories.clone())?;
+                format!("Installed template {}", template.name)
+            }
+            Self::RemoveTemplate { filename, name } => {
+                templates::remove(filename)?;
+                format!("Removed template {}", name)
+            }
+            Self::ReapplyTemplates => {
+                let missing = templates::reapply()?;
+                format!("Re-applied templates, {} were missing", missing)
+            }
+            Self::InstallScreen { screen, source } => {
+                screens::install(*screen, source)?;
+                format!(
+                    "{} screen replaced with {}",
+                    screen.get_text(),
+                    source
+                        .file_name()
+                        .unwrap_or(source.as_os_str())
+                        .to_string_lossy()
+                )
+            }
+            Self::RestoreScreen(screen) => {
+                screens::restore(*screen)?;
+                format!("{} screen restored", screen.get_text())
+            }
+        };
+
+        device::restart_ui()?;
+
+        Ok(message)
+    }
+}
+
+impl JobRunner {
+    pub fn new() -> (Self, UnboundedReceiver<AppEvent>) {
+        let (sender, receiver) = unbounded();
+
+        (
+            Self {
+                sender,
+                next_id: 0,
+                running: BTreeMap::new(),
+            },
+            receiver,
+        )
+    }
+
+    pub fn sender(&self) -> UnboundedSender<AppEvent> {
+        self.sender.clone()
+    }
+
+    pub fn is_running(&self, kind: &JobKind) -> bool {
+        self.running.values().any(|job| job.kind == *kind)
+    }
+
+    // A running job that `kind` can't run alongside
+    pub fn running_rival(&self, kind: &JobKind) -> Option<&JobKind> {
+        self.running
+            .values()
+            .map(|job| &job.kind)
+            .find(|running| running.conflicts(kind))
+    }
+
+    pub fn spawn(&mut self, kind: JobKind) -> JobId {
+        self.spawn_attempt(kind, 1)
+    }
+
+    // Run a job again after a transient failure, backing off a little more each time
+    pub fn retry(&mut self, kind: JobKind, attempt: u32) -> JobId {
+        self.spawn_attempt(kind, attempt)
+    }
+
+    fn spawn_attempt(&mut self, kind: JobKind, attempt: u32) -> JobId {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.running.insert(
+            id,
+            RunningJob {
+                kind: kind.clone(),
+                attempt,
+                progress: None,
+            },
+        );
+
+        log::debug!(
+            "Starting {} job {} (attempt {})",
+            kind.get_text(),
+            id,
+            attempt
+        );
+
+        let sender = self.sender();
+        let backoff = Duration::from_secs(2u64.pow(attempt - 1) - 1);
+
+        // The handle is dropped, the job reports back through the channel instead
+        drop(async_std::task::spawn_blocking(move || {
+            thread::sleep(backoff);
+
+            let result = match kind {
+                JobKind::Sync => sync_remote_to_local(|update| {
+                    let _ = sender.unbounded_send(AppEvent::Progress(
+                        id,
+                        format!("{}% {}", update.percent, update.rate),
+                    ));
+                })
+                .map(|_| JobOutput::None),
+                JobKind::SyncPreview => plan_sync().map(JobOutput::SyncPlan),
+                JobKind::Index => scan_file_tree().map(JobOutput::Index),
+                JobKind::Check(source) => check(source).map(JobOutput::Check),
+                JobKind::Templates => templates::installed_templates().map(JobOutput::Templates),
+                JobKind::Screens => screens::screen_states().map(JobOutput::Screens),
+                JobKind::Device(change) => change.run().map(JobOutput::Device),
+            };
+
+            let _ = sender.unbounded_send(AppEvent::Finished(id, result));
+        }));
+
+        id
+    }
+
+    pub fn update_progress(&mut self, id: JobId, progress: String) {
+        if let Some(job) = self.running.get_mut(&id) {
+            job.progress = Some(progress);
+        }
+    }
+
+    // Stop tracking a job, giving back what kind it was and which attempt just ended
+    pub fn finish(&mut self, id: JobId) -> Option<(JobKind, u32)> {
+        self.running.remove(&id).map(|job| (job.kind, job.attempt))
+    }
+
+    // One line per running job in the bottom left corner
+    pub fn render<B: Backend>(&self, f: &mut Frame<B>) {
+        let screen = ui::overlay_area(f.size());
+
+        for (row, job) in self.running.values().rev().enumerate() {
+            let mut text = match &job.progress {
+                Some(progress) => format!(" {} {} ", job.kind.get_text(), progress),
+                None => format!(" {}... ", job.kind.get_text()),
+            };
+
+            if job.attempt > 1 {
+                text.push_str(&format!("(attempt {}/{}) ", job.attempt, MAX_ATTEMPTS));
+            }
+
+            let Ok(row) = u16::try_from(row + 1) else {
+                break;
+            };
+
+            if row > screen.height {
+                break;
+            }
+
+            let width = u16::try_from(text.chars().count())
+                .unwrap_or(u16::MAX)
+                .min(screen.width);
+            let area = Rect::new(screen.x, screen.bottom() - row, width, 1);
+
+            f.render_widget(Clear, area);
+            f.render_widget(
+                Paragraph::new(text).style(
+                    Style::default()
+                        .fg(config::THEME.background)
+                        .bg(config::THEME.highlight),
+                ),
+                area,
+            );
+        }
+    }
+}
@@ -0,0 +1,184 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// File operations on the local side. Nothing is deleted outright, removed files go to
+// a trash directory in the state dir where they can be fished out again.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config,
+    intern_error::{Error, ErrorContext, ResultExt},
+    transfer::unique_path,
+};
+
+pub fn trash_dir() -> PathBuf {
+    config::SETTINGS.state_dir.join("trash")
+}
+
+// Split a file name into the stem and extension unique_path expects, directories
+// keep their whole name
+fn split_name(path: &Path) -> (String, String) {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    if path.is_dir() {
+        return (name, String::new());
+    }
+
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (String::from(stem), String::from(ext)),
+        _ => (name, String::new()),
+    }
+}
+
+// A free path for `source` inside `dir`, numbered if the name is taken
+pub fn free_path_in(dir: &Path, source: &Path) -> PathBuf {
+    let (stem, ext) = split_name(source);
+    unique_path(dir, &stem, &ext)
+}
+
+pub fn copy_recursive(source: &Path, dest: &Path) -> Result<(), Error> {
+    let context = || ErrorContext::file(source);
+
+    if source.is_dir() {
+        fs::create_dir_all(dest).context(ErrorContext::file(dest))?;
+
+        for entry in fs::read_dir(source).context(context())? {
+            let entry = entry.context(context())?;
+            copy_recursive(&entry.path(), &dest.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(source, dest).context(context())?;
+    }
+
+    Ok(())
+}
+
+// Rename where possible, copy and remove when crossing file systems
+pub fn move_path(source: &Path, dest: &Path) -> Result<(), Error> {
+    match fs::rename(source, dest) {
+        Ok(_) => Ok(()),
+        Err(why) if why.kind() == io::ErrorKind::CrossesDevices => {
+            copy_recursive(source, dest)?;
+
+            if source.is_dir() {
+                fs::remove_dir_all(source)
+            } else {
+                fs::remove_file(source)
+            }
+            .context(ErrorContext::file(source))
+        }
+        Err(why) => Err(Error::from(why).with_context(ErrorContext::file(source))),
+    }
+}
+
+// Names typed in by the user must stay inside the directory they're typed in
+fn check_name(name: &str) -> Result<(), Error> {
+    if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+        return Err(Error::IOError(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("\"{}\" isn't a valid file name", name),
+        )));
+    }
+
+    Ok(())
+}
+
+// Stem for a local file named after something on the tablet, where names can hold
+// anything. Slashes become dashes and names like ".." are refused.
+pub fn stem_for(name: &str) -> Result<String, Error> {
+    let stem = name.replace('/', "-");
+    check_name(&stem)?;
+
+    Ok(stem)
+}
+
+pub fn make_dir(dir: &Path, name: &str) -> Result<PathBuf, Error> {
+    check_name(name)?;
+
+    let path = dir.join(name);
+
+    log::info!("Creating directory {}", path.display());
+
+    fs::create_dir(&path).context(ErrorContext::file(&path))?;
+
+    Ok(path)
+}
+
+// Rename in place, refusing to overwrite what's already there
+pub fn rename(path: &Path, name: &str) -> Result<PathBuf, Error> {
+    check_name(name)?;
+
+    let dest = path.with_file_name(name);
+
+    if dest.exists() {
+        return Err(Error::IOError(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", name),
+        ))
+        .with_context(ErrorContext::file(&dest)));
+    }
+
+    log::info!("Renaming {} to {}", path.display(), dest.display());
+
+    fs::rename(path, &dest).context(ErrorContext::file(path))?;
+
+    Ok(dest)
+}
+
+// Copy or move `source` into `dir`, numbering the name if it's taken
+pub fn paste(source: &Path, dir: &Path, cut: bool) -> Result<PathBuf, Error> {
+    if dir.starts_with(source) {
+        return Err(Error::IOError(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Can't paste a directory into itself",
+        ))
+        .with_context(ErrorContext::file(source)));
+    }
+
+    let dest = free_path_in(dir, source);
+
+    log::info!(
+        "{} {} to {}",
+        if cut { "Moving" } else { "Copying" },
+        source.display(),
+        dest.display()
+    );
+
+    if cut {
+        move_path(source, &dest)?;
+    } else {
+        copy_recursive(source, &dest)?;
+    }
+
+    Ok(dest)
+}
+
+// Move a file or directory into the trash, returning where it ended up
+pub fn trash(path: &Path) -> Result<PathBuf, Error> {
+    let dir = trash_dir();
+    fs::create_dir_all(&dir).context(ErrorContext::file(&dir))?;
+
+    let dest = free_path_in(&dir, path);
+
+    log::info!("Moving {} to trash as {}", path.display(), dest.display());
+
+    move_path(path, &dest)?;
+
+    Ok(dest)
+}
@@ -0,0 +1,133 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// The terminal belongs to the TUI, so diagnostics go to a size-capped log file in
+// the state directory instead. Once the file grows past MAX_LOG_SIZE it is moved
+// aside to noteworthy.log.1 (and older ones shuffled up) before writing continues.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+use crate::intern_error::{Error, ErrorContext, ResultExt};
+
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+// Number of rotated files kept besides the live one
+const KEEP_ROTATED: usize = 3;
+
+struct LogFile {
+    file: File,
+    size: u64,
+}
+
+struct FileLogger {
+    level: LevelFilter,
+    path: PathBuf,
+    output: Mutex<LogFile>,
+}
+
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+fn open(path: &Path) -> std::io::Result<LogFile> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let size = file.metadata()?.len();
+
+    Ok(LogFile { file, size })
+}
+
+impl FileLogger {
+    fn rotate(&self, output: &mut LogFile) -> std::io::Result<()> {
+        for n in (1..KEEP_ROTATED).rev() {
+            let from = rotated_path(&self.path, n);
+
+            if from.exists() {
+                fs::rename(from, rotated_path(&self.path, n + 1))?;
+            }
+        }
+
+        fs::rename(&self.path, rotated_path(&self.path, 1))?;
+
+        *output = open(&self.path)?;
+
+        Ok(())
+    }
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!(
+            "{} {:<5} [{}] {}\n",
+            chrono::Local::now().format("%Y-%m-%dT%H:%M:%S%.3f%:z"),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+
+        let Ok(mut output) = self.output.lock() else {
+            return;
+        };
+
+        if output.size + line.len() as u64 > MAX_LOG_SIZE {
+            // Keep writing to the full file rather than losing records if rotation fails
+            let _ = self.rotate(&mut output);
+        }
+
+        if output.file.write_all(line.as_bytes()).is_ok() {
+            output.size += line.len() as u64;
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut output) = self.output.lock() {
+            let _ = output.file.flush();
+        }
+    }
+}
+
+// Install the file logger as the global logger, creating its directory if needed
+pub fn init(level: LevelFilter, path: &Path) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context(ErrorContext::file(dir))?;
+    }
+
+    let output = open(path).context(ErrorContext::file(path))?;
+
+    let logger = FileLogger {
+        level,
+        path: path.to_path_buf(),
+        output: Mutex::new(output),
+    };
+
+    // Only fails if a logger was already installed, in which case that one keeps going
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(level);
+    }
+
+    Ok(())
+}
@@ -10,36 +10,89 @@ of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Publ
 You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
 */
 
+pub mod cli;
 pub mod config;
+pub mod device;
+pub mod epub;
 pub mod fs_interface;
+pub mod highlights;
+pub mod inbox;
+pub mod integrity;
 pub mod intern_error;
+pub mod jobs;
+pub mod local;
+pub mod logging;
 pub mod notification;
+pub mod pages;
+pub mod rm;
+pub mod save_data;
+pub mod screens;
+pub mod sync_plan;
+pub mod templates;
+pub mod transfer;
+pub mod typed_text;
 pub mod ui;
 
-use fs_interface::{resolve_file_tree, sync_remote_to_local};
-use intern_error::Error;
+use clap::Parser;
+use fs_interface::{init_db, load_file_tree};
+use futures::{select, FutureExt, StreamExt};
+use futures_timer::Delay;
+use intern_error::{Error, Severity};
 use rusqlite::Connection;
 use std::{
     io::{self, Stdout},
-    sync::Arc,
+    rc::Rc,
+    time::Duration,
 };
 
 use tui::{self, backend::CrosstermBackend, Terminal};
 
 use crossterm::{
     self, cursor,
-    event::{DisableMouseCapture, EnableMouseCapture, Event, KeyCode, PopKeyboardEnhancementFlags},
+    event::{
+        DisableMouseCapture, EnableMouseCapture, Event, EventStream, KeyCode, MouseButton,
+        MouseEventKind, PopKeyboardEnhancementFlags,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 
 use crate::{
-    notification::{NotificationType, NotificationWidget},
-    ui::file_ui,
+    cli::Args,
+    config::SETTINGS,
+    integrity::CheckSource,
+    jobs::{AppEvent, JobKind, JobOutput, JobRunner, MAX_ATTEMPTS},
+    notification::{NotificationCenter, NotificationType, NotificationWidget},
+    ui::{file_ui, CursorDirection, FileUI},
 };
 
+// How often the loop wakes up without input to expire notifications
+const TICK_RATE: Duration = Duration::from_millis(250);
+
 fn main() -> Result<(), crate::intern_error::Error> {
+    let args = Args::parse();
+
     // Setup + Initialization
+    let log_file = args.log_file.unwrap_or_else(|| SETTINGS.log_file.clone());
+
+    // Not being able to log shouldn't stop the app, say so while we still own stdout
+    if let Err(why) = logging::init(args.log_level.unwrap_or(SETTINGS.log_level), &log_file) {
+        eprintln!("Logging disabled: {}", why);
+    }
+
+    // Subcommands run without the interface and report on stderr
+    if let Some(command) = args.command {
+        if let Err(why) = cli::run(command) {
+            log::error!("{}", why);
+            eprintln!("{}", why);
+            std::process::exit(1);
+        }
+
+        return Ok(());
+    }
+
+    log::info!("Starting noteworthy {}", env!("CARGO_PKG_VERSION"));
+
     let mut stdout = io::stdout();
 
     enable_raw_mode()?;
@@ -70,6 +123,11 @@ fn main() -> Result<(), crate::intern_error::Error> {
 
     terminal.show_cursor()?;
 
+    match &conclusion {
+        Ok(_) => log::info!("Exiting"),
+        Err(why) => log::error!("Exiting after fatal error: {}", why),
+    }
+
     println!("{:?}", conclusion);
 
     Ok(())
@@ -80,94 +138,287 @@ async fn render_base(
     db: Connection,
 ) -> Result<(), crate::intern_error::Error> {
     // Key event reader
-    // let mut reader = EventStream::new();
+    let mut reader = EventStream::new();
 
-    // Atomic reference counter for db, allowing db to be passed around without
+    // Reference counter for db, allowing db to be passed around without
     // adding complexity via lifetimes
-    let arc_db = Arc::new(db);
+    let rc_db = Rc::new(db);
 
-    // Resolve the current file tree into a db in memory
-    resolve_file_tree(Arc::clone(&arc_db))?;
+    // The file tree is resolved into the db in the background, start off with an empty table
+    init_db(&rc_db)?;
 
-    let conclusion: Result<(), Error> = Ok(());
+    let mut notifications = NotificationCenter::default();
 
-    let mut selected_ui = file_ui(Arc::clone(&arc_db))?;
+    let (mut jobs, mut job_events) = JobRunner::new();
 
-    let mut notification_queue: Vec<NotificationWidget> = Vec::new();
+    let mut selected_ui = file_ui(Rc::clone(&rc_db), jobs.sender())?;
 
-    let mut render_result: Result<(), Error> = Ok(());
+    jobs.spawn(JobKind::Index);
 
-    // Initial render
-    terminal.draw(|f| {
-        render_result = selected_ui.render(f);
-    })?;
-
-    render_result?;
+    let mut redraw = true;
 
     loop {
-        let event = crossterm::event::read()?;
+        if redraw {
+            let mut render_result: Result<(), Error> = Ok(());
+
+            terminal
+                .draw(|f| {
+                    render_result = selected_ui.render(f);
+                    jobs.render(f);
+                    notifications.render(f);
+                })
+                .map_err(|why| Error::CrosstermError(why.to_string()))?;
+
+            soft_error_recovery(&mut notifications, render_result)?;
+        }
+
+        redraw = true;
+
+        let mut tick = Delay::new(TICK_RATE).fuse();
+        let mut next_event = reader.next().fuse();
+        let mut next_job_event = job_events.next().fuse();
+
+        let event = select! {
+            event = next_event => match event {
+                Some(event) => event.map_err(|why| Error::CrosstermError(why.to_string()))?,
+                None => break Ok(()),
+            },
+            job_event = next_job_event => {
+                if let Some(job_event) = job_event {
+                    handle_app_event(
+                        job_event,
+                        &rc_db,
+                        &mut selected_ui,
+                        &mut jobs,
+                        &mut notifications,
+                    )?;
+                }
+                continue;
+            },
+            _ = tick => {
+                redraw = notifications.prune();
+
+                for why in selected_ui.watch_inboxes() {
+                    redraw = true;
+                    soft_error_recovery(&mut notifications, Err::<(), Error>(why))?;
+                }
+                continue;
+            }
+        };
 
         match event {
+            // While the history is open it takes all key input
+            Event::Key(event) if notifications.log_open() => match event.code {
+                KeyCode::Esc | KeyCode::Char('N') => notifications.toggle_log(),
+                KeyCode::Up => notifications.log_scroll(CursorDirection::Up),
+                KeyCode::Down => notifications.log_scroll(CursorDirection::Down),
+                KeyCode::PageUp => notifications.log_scroll(CursorDirection::PgUp),
+                KeyCode::PageDown => notifications.log_scroll(CursorDirection::PgDn),
+                _ => (),
+            },
+            // Text input gets every key, including the ones bound globally
+            Event::Key(event) if selected_ui.input_active() => {
+                soft_error_recovery(&mut notifications, selected_ui.key_handler(event.code))?;
+            }
             Event::Key(event) => {
+                log::trace!("Key {:?}", event.code);
+
                 match event.code {
                     // Global key responses
-                    // TODO: Add flag to disable keyevent handling for text input
                     KeyCode::Esc | KeyCode::Char('q') => break Ok(()),
-                    KeyCode::Char(' ') => {
-                        notification_queue.pop();
-                    }
-                    KeyCode::Char('S') => {
-                        soft_error_recovery(&mut notification_queue, sync_remote_to_local())?;
-                        resolve_file_tree(Arc::clone(&arc_db))?;
-                        selected_ui.refresh_views()?;
+                    KeyCode::Char(' ') => notifications.dismiss(),
+                    KeyCode::Char('N') => notifications.toggle_log(),
+                    // Don't handle context-specific keys if a blocking notification has yet to be dismissed
+                    _ if notifications.is_blocking() => (),
+                    KeyCode::Char('S') => request_job(JobKind::Sync, &mut jobs, &mut notifications),
+                    KeyCode::Char('D') => {
+                        request_job(JobKind::SyncPreview, &mut jobs, &mut notifications)
                     }
+                    KeyCode::Char('K') => request_job(
+                        JobKind::Check(CheckSource::Mirror),
+                        &mut jobs,
+                        &mut notifications,
+                    ),
                     _ => {
-                        // Don't handle context-specific keys if a notification has yet to be dismissed
-                        // TODO: Move this outside key loop so that all events are blocked except for acknowledgement
-                        if notification_queue.is_empty() {
-                            soft_error_recovery(
-                                &mut notification_queue,
-                                selected_ui.key_handler(event.code),
-                            )?;
-                        }
+                        soft_error_recovery(
+                            &mut notifications,
+                            selected_ui.key_handler(event.code),
+                        )?;
                     }
                 }
             }
-            // Don't want to re render on every mouse event
-            Event::Mouse(_) => continue,
+            // The history view scrolls with the wheel and ignores clicks
+            Event::Mouse(event) if notifications.log_open() => match event.kind {
+                MouseEventKind::ScrollUp => notifications.log_scroll(CursorDirection::Up),
+                MouseEventKind::ScrollDown => notifications.log_scroll(CursorDirection::Down),
+                _ => redraw = false,
+            },
+            Event::Mouse(event) => match event.kind {
+                MouseEventKind::Down(_) | MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+                    let on_notification = event.kind == MouseEventKind::Down(MouseButton::Left)
+                        && notifications.click(event.column, event.row, terminal.size()?);
+
+                    if !on_notification && !notifications.is_blocking() {
+                        soft_error_recovery(&mut notifications, selected_ui.mouse_handler(event))?;
+                    }
+                }
+                // Don't want to re render on every move and drag
+                _ => redraw = false,
+            },
             _ => (),
         };
+    }
+}
+
+// React to progress and completion messages from background jobs
+fn handle_app_event(
+    event: AppEvent,
+    db: &Rc<Connection>,
+    selected_ui: &mut FileUI,
+    jobs: &mut JobRunner,
+    notifications: &mut NotificationCenter,
+) -> Result<(), Error> {
+    match event {
+        AppEvent::Notify(notif) => notifications.push(notif),
+        AppEvent::Spawn(kind) => request_job(kind, jobs, notifications),
+        AppEvent::Progress(id, progress) => jobs.update_progress(id, progress),
+        AppEvent::Transfer(id, event) => {
+            if let Some(finished) = selected_ui.handle_transfer(id, event) {
+                notifications.push(finished.notification);
+
+                if finished.changed_remote && !jobs.is_running(&JobKind::Index) {
+                    jobs.spawn(JobKind::Index);
+                }
+            }
+        }
+        AppEvent::Finished(id, result) => {
+            let Some((kind, attempt)) = jobs.finish(id) else {
+                return Ok(());
+            };
 
-        let mut render_result: Result<(), Error> = Ok(());
+            if kind == JobKind::Sync {
+                selected_ui.record_sync(&result);
+            }
 
-        terminal.draw(|f| {
-            render_result = selected_ui.render(f);
+            let output = match result {
+                Err(why) if why.is_transient() && attempt < MAX_ATTEMPTS => {
+                    log::warn!("{} attempt {} failed: {}", kind.get_text(), attempt, why);
+
+                    notifications.push(
+                        NotificationWidget::default()
+                            .text(&format!(
+                                "{} failed, retrying ({}/{})\n{}",
+                                kind.get_text(),
+                                attempt + 1,
+                                MAX_ATTEMPTS,
+                                why
+                            ))
+                            .notif_type(NotificationType::ErrorLow),
+                    );
+                    jobs.retry(kind, attempt + 1);
+                    return Ok(());
+                }
+                result => soft_error_recovery(notifications, result)?,
+            };
+
+            match (kind, output) {
+                (JobKind::Sync, Some(_)) => {
+                    notifications.push(
+                        NotificationWidget::default()
+                            .text("Sync complete")
+                            .notif_type(NotificationType::Success),
+                    );
+                    jobs.spawn(JobKind::Index);
+                }
+                (JobKind::SyncPreview, Some(JobOutput::SyncPlan(plan))) => {
+                    log::info!("Sync would change {} object(s)", plan.changes.len());
 
-            if let Some(notif) = notification_queue.last() {
-                notif.render(f);
+                    soft_error_recovery(notifications, selected_ui.show_sync_plan(plan))?;
+                }
+                (JobKind::Check(source), Some(JobOutput::Check(problems))) => {
+                    log::info!(
+                        "Found {} problem(s) in the {}",
+                        problems.len(),
+                        source.get_text()
+                    );
+
+                    selected_ui.show_check_report(source, problems);
+                }
+                (JobKind::Templates, Some(JobOutput::Templates(templates))) => {
+                    selected_ui.show_templates(templates);
+                }
+                (JobKind::Screens, Some(JobOutput::Screens(states))) => {
+                    selected_ui.show_screens(states);
+                }
+                (JobKind::Device(_), Some(JobOutput::Device(message))) => {
+                    log::info!("{}", message);
+
+                    notifications.push(
+                        NotificationWidget::default()
+                            .text(&message)
+                            .notif_type(NotificationType::Success),
+                    );
+                    soft_error_recovery(notifications, selected_ui.reload_device_pane())?;
+                }
+                (JobKind::Index, Some(JobOutput::Index(tree))) => {
+                    log::info!(
+                        "Indexed {} objects, {} unreadable",
+                        tree.rows.len(),
+                        tree.errors.len()
+                    );
+
+                    // Unreadable files are skipped, the rest of the library still loads
+                    if let Some(first) = tree.errors.first() {
+                        notifications.push(
+                            NotificationWidget::default()
+                                .text(&format!(
+                                    "{} metadata file(s) couldn't be read\n{}",
+                                    tree.errors.len(),
+                                    first
+                                ))
+                                .notif_type(NotificationType::ErrorLow),
+                        );
+                    }
+
+                    soft_error_recovery(notifications, load_file_tree(db, &tree.rows))?;
+                    soft_error_recovery(notifications, selected_ui.refresh_views())?;
+                }
+                _ => (),
             }
-        })?;
+        }
+    };
 
-        if let Err(why) = soft_error_recovery(&mut notification_queue, render_result) {
-            break Err(why);
-        };
-    }?;
+    Ok(())
+}
 
-    conclusion
+// Start a job unless it or one it conflicts with is already going
+fn request_job(kind: JobKind, jobs: &mut JobRunner, notifications: &mut NotificationCenter) {
+    log::info!("{} requested", kind.get_text());
+
+    match jobs.running_rival(&kind) {
+        Some(running) => notifications.push(
+            NotificationWidget::default()
+                .text(&format!("{} already in progress", running.get_text())),
+        ),
+        None => {
+            jobs.spawn(kind);
+        }
+    }
 }
 
-// TODO: Move to intern_error.rs and somehow have a selection of errors we want to recover
-// from and ones we dont
+// Fatal errors are handed back to abort the main loop, anything else is raised
+// as a notification matching its severity
 fn soft_error_recovery<T>(
-    notifications: &mut Vec<NotificationWidget>,
+    notifications: &mut NotificationCenter,
     result: Result<T, Error>,
 ) -> Result<Option<T>, Error> {
     match result {
+        Err(why) if why.severity() == Severity::Fatal => Err(why),
         Err(why) => {
             notifications.push(
                 NotificationWidget::default()
                     .text(why.to_string().as_str())
-                    .notif_type(NotificationType::ErrorLow),
+                    .notif_type(why.notification_type()),
             );
             Ok(None)
         }
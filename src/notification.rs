@@ -10,18 +10,40 @@ of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Publ
 You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
 */
 
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use futures::FutureExt;
+use futures_timer::Delay;
 use tui::{
     backend::Backend,
     layout::{Constraint, Layout, Rect},
-    style::Style,
+    style::{Modifier, Style},
     text::{Span, Spans},
-    widgets::{Block, BorderType, Borders, Clear, Paragraph, Wrap},
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, Paragraph, Wrap},
     Frame,
 };
 
-use crate::config;
+use crate::{
+    config,
+    ui::{self, CursorDirection},
+};
 
-#[derive(Default)]
+// Width of the toast stack in the bottom right corner
+const TOAST_WIDTH: u16 = 40;
+// Upper bound on retained notifications so long sessions don't grow forever
+const HISTORY_LIMIT: usize = 500;
+
+// Where the toasts go on screen: the index and area of every toast that fits, plus how
+// many didn't and where to say so
+struct ToastLayout {
+    areas: Vec<(usize, Rect)>,
+    overflow: Option<(usize, Rect)>,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
 pub enum NotificationType {
     Success,
     ErrorLow,
@@ -31,10 +53,29 @@ pub enum NotificationType {
     Message,
 }
 
-#[derive(Default)]
+#[derive(Clone)]
 pub struct NotificationWidget {
     notif_text: String,
     notif_type: NotificationType,
+    created: Instant,
+}
+
+// A non-blocking notification along with the timer that expires it
+struct Toast {
+    widget: NotificationWidget,
+    timer: Delay,
+}
+
+// Owns every notification raised during the session. Blocking notifications are
+// held in `modals` until acknowledged, everything else is shown as a toast until
+// its timer runs out. All of them end up in `history`.
+#[derive(Default)]
+pub struct NotificationCenter {
+    toasts: Vec<Toast>,
+    modals: VecDeque<NotificationWidget>,
+    history: VecDeque<NotificationWidget>,
+    log_open: bool,
+    log_offset: usize,
 }
 
 impl NotificationType {
@@ -42,23 +83,46 @@ impl NotificationType {
         match self {
             Self::Success => String::from("Success"),
             Self::Message => String::from("Message"),
-            Self::ErrorHigh | Self::ErrorMid | Self::ErrorLow => String::from("Error"),
+            Self::ErrorLow => String::from("Warning"),
+            Self::ErrorMid => String::from("Error"),
+            Self::ErrorHigh => String::from("Critical error"),
         }
     }
 
     pub fn get_style(&self) -> Style {
+        let style = Style::default().bg(config::THEME.background);
+
         match self {
-            Self::Message => Style::default()
-                .fg(config::THEME.foreground)
-                .bg(config::THEME.background),
-            Self::Success => Style::default()
-                .fg(config::THEME.success)
-                .bg(config::THEME.background),
-            Self::ErrorLow | NotificationType::ErrorMid | NotificationType::ErrorHigh => {
-                Style::default()
-                    .fg(config::THEME.alert)
-                    .bg(config::THEME.background)
-            }
+            Self::Message => style.fg(config::THEME.foreground),
+            Self::Success => style.fg(config::THEME.success),
+            Self::ErrorLow => style.fg(config::THEME.highlight),
+            Self::ErrorMid => style.fg(config::THEME.alert),
+            Self::ErrorHigh => style.fg(config::THEME.alert).add_modifier(Modifier::BOLD),
+        }
+    }
+
+    // How long a toast of this type stays on screen, None if it must be acknowledged
+    pub fn lifetime(&self) -> Option<Duration> {
+        match self {
+            Self::Success => Some(Duration::from_secs(3)),
+            Self::Message => Some(Duration::from_secs(4)),
+            Self::ErrorLow => Some(Duration::from_secs(6)),
+            Self::ErrorMid => Some(Duration::from_secs(10)),
+            Self::ErrorHigh => None,
+        }
+    }
+
+    pub fn is_blocking(&self) -> bool {
+        self.lifetime().is_none()
+    }
+}
+
+impl Default for NotificationWidget {
+    fn default() -> Self {
+        Self {
+            notif_text: String::new(),
+            notif_type: NotificationType::default(),
+            created: Instant::now(),
         }
     }
 }
@@ -74,15 +138,29 @@ impl NotificationWidget {
         self
     }
 
+    pub fn get_text(&self) -> &str {
+        &self.notif_text
+    }
+
+    pub fn get_type(&self) -> NotificationType {
+        self.notif_type
+    }
+
+    fn text_lines(&self) -> Vec<Spans<'_>> {
+        self.notif_text
+            .lines()
+            .map(|line| Spans::from(vec![Span::raw(line)]))
+            .collect()
+    }
+
     pub fn generate_body(&self) -> Vec<Spans<'_>> {
-        vec![
-            Spans::from(vec![Span::raw(self.notif_text.clone())]),
-            Spans::from(vec![Span::raw("[press space to dismiss]")]),
-        ]
+        let mut body = self.text_lines();
+        body.push(Spans::from(vec![Span::raw("[press space to dismiss]")]));
+        body
     }
 
-    pub fn render<B: Backend>(&self, f: &mut Frame<B>) {
-        let message = Paragraph::new(self.generate_body())
+    fn paragraph<'a>(&self, body: Vec<Spans<'a>>) -> Paragraph<'a> {
+        Paragraph::new(body)
             .block(
                 Block::default()
                     .title(self.notif_type.get_text())
@@ -90,14 +168,258 @@ impl NotificationWidget {
                     .borders(Borders::ALL),
             )
             .style(self.notif_type.get_style())
-            .alignment(tui::layout::Alignment::Center)
-            .wrap(Wrap { trim: false });
+            .wrap(Wrap { trim: false })
+    }
+
+    // Render as a centred, blocking modal
+    pub fn render<B: Backend>(&self, f: &mut Frame<B>) {
+        let message = self
+            .paragraph(self.generate_body())
+            .alignment(tui::layout::Alignment::Center);
 
         let render_area = center_rect(20, 12, f.size());
 
         f.render_widget(Clear, render_area);
         f.render_widget(message, render_area);
     }
+
+    // Height needed to show the text wrapped in a box of the given width
+    fn toast_height(&self, width: u16) -> u16 {
+        let inner = usize::from(width.saturating_sub(2)).max(1);
+        let lines = self
+            .notif_text
+            .lines()
+            .map(|l| l.chars().count().max(1).div_ceil(inner))
+            .sum::<usize>()
+            .max(1);
+
+        u16::try_from(lines).unwrap_or(u16::MAX).saturating_add(2)
+    }
+
+    fn render_toast<B: Backend>(&self, f: &mut Frame<B>, area: Rect) {
+        let message = self.paragraph(self.text_lines());
+
+        f.render_widget(Clear, area);
+        f.render_widget(message, area);
+    }
+}
+
+impl NotificationCenter {
+    pub fn push(&mut self, notif: NotificationWidget) {
+        let level = match notif.notif_type {
+            NotificationType::ErrorHigh => log::Level::Error,
+            NotificationType::ErrorMid | NotificationType::ErrorLow => log::Level::Warn,
+            NotificationType::Success | NotificationType::Message => log::Level::Info,
+        };
+
+        log::log!(
+            level,
+            "Notification ({}): {}",
+            notif.notif_type.get_text(),
+            notif.notif_text.replace('\n', " ")
+        );
+
+        self.history.push_back(notif.clone());
+
+        if self.history.len() > HISTORY_LIMIT {
+            self.history.pop_front();
+        }
+
+        match notif.notif_type.lifetime() {
+            None => self.modals.push_back(notif),
+            Some(lifetime) => self.toasts.push(Toast {
+                widget: notif,
+                timer: Delay::new(lifetime),
+            }),
+        }
+    }
+
+    // Acknowledge the oldest blocking notification, or the newest toast if none are blocking
+    pub fn dismiss(&mut self) {
+        if self.modals.pop_front().is_none() {
+            self.toasts.pop();
+        }
+    }
+
+    // Whether input should be withheld from the UI until a notification is acknowledged
+    pub fn is_blocking(&self) -> bool {
+        !self.modals.is_empty()
+    }
+
+    // Drop toasts whose timers have fired, returns true if anything was removed
+    pub fn prune(&mut self) -> bool {
+        let before = self.toasts.len();
+
+        self.toasts
+            .retain_mut(|toast| (&mut toast.timer).now_or_never().is_none());
+
+        before != self.toasts.len()
+    }
+
+    pub fn log_open(&self) -> bool {
+        self.log_open
+    }
+
+    pub fn toggle_log(&mut self) {
+        self.log_open = !self.log_open;
+        self.log_offset = 0;
+    }
+
+    // Scroll the history view, offset counts back from the newest entry
+    pub fn log_scroll(&mut self, direction: CursorDirection) {
+        let max_offset = self.history.len().saturating_sub(1);
+
+        self.log_offset = match direction {
+            CursorDirection::Up => self.log_offset.saturating_add(1),
+            CursorDirection::Down => self.log_offset.saturating_sub(1),
+            CursorDirection::PgUp => self.log_offset.saturating_add(15),
+            CursorDirection::PgDn => self.log_offset.saturating_sub(15),
+        }
+        .min(max_offset);
+    }
+
+    pub fn render<B: Backend>(&self, f: &mut Frame<B>) {
+        self.render_toasts(f);
+
+        if self.log_open {
+            self.render_log(f);
+        }
+
+        if let Some(modal) = self.modals.front() {
+            modal.render(f);
+        }
+    }
+
+    // Stack the toasts upwards from the bottom right corner, newest at the bottom
+    fn toast_layout(&self, screen: Rect) -> ToastLayout {
+        let screen = ui::overlay_area(screen);
+        let width = TOAST_WIDTH.min(screen.width);
+        let mut bottom = screen.bottom();
+        let mut areas = Vec::new();
+
+        for (shown, (idx, toast)) in self.toasts.iter().enumerate().rev().enumerate() {
+            let height = toast.widget.toast_height(width);
+
+            // Leave a line to point out toasts that didn't fit, if there's still a line
+            // left on screen for it
+            if bottom < screen.y.saturating_add(height).saturating_add(1) {
+                let hidden = self.toasts.len() - shown;
+                let overflow = (bottom > screen.y).then(|| {
+                    (
+                        hidden,
+                        Rect::new(screen.right() - width, bottom - 1, width, 1),
+                    )
+                });
+
+                return ToastLayout { areas, overflow };
+            }
+
+            bottom -= height;
+
+            areas.push((
+                idx,
+                Rect::new(screen.right() - width, bottom, width, height),
+            ));
+        }
+
+        ToastLayout {
+            areas,
+            overflow: None,
+        }
+    }
+
+    fn render_toasts<B: Backend>(&self, f: &mut Frame<B>) {
+        let layout = self.toast_layout(f.size());
+
+        for (idx, area) in layout.areas {
+            self.toasts[idx].widget.render_toast(f, area);
+        }
+
+        if let Some((hidden, area)) = layout.overflow {
+            f.render_widget(Clear, area);
+            f.render_widget(
+                Paragraph::new(format!("+{} more", hidden))
+                    .style(NotificationType::Message.get_style()),
+                area,
+            );
+        }
+    }
+
+    // Clicking a notification dismisses it. Returns true if the click was meant for the
+    // notifications, which is any click at all while a blocking one is showing.
+    pub fn click(&mut self, column: u16, row: u16, screen: Rect) -> bool {
+        if self.modals.front().is_some() {
+            if ui::area_contains(center_rect(20, 12, screen), column, row) {
+                self.modals.pop_front();
+            }
+            return true;
+        }
+
+        match self
+            .toast_layout(screen)
+            .areas
+            .into_iter()
+            .find(|(_, area)| ui::area_contains(*area, column, row))
+        {
+            Some((idx, _)) => {
+                self.toasts.remove(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn render_log<B: Backend>(&self, f: &mut Frame<B>) {
+        let render_area = center_rect(70, 70, f.size());
+        let visible = usize::from(render_area.height.saturating_sub(2));
+
+        let items: Vec<ListItem> = self
+            .history
+            .iter()
+            .rev()
+            .skip(self.log_offset)
+            .take(visible)
+            .map(|notif| {
+                let age = notif.created.elapsed().as_secs();
+                let age = if age < 60 {
+                    format!("{}s", age)
+                } else if age < 3600 {
+                    format!("{}m", age / 60)
+                } else {
+                    format!("{}h", age / 3600)
+                };
+
+                ListItem::new(Spans::from(vec![
+                    Span::raw(format!("{:>4} ", age)),
+                    Span::styled(
+                        format!("{:<15}", notif.notif_type.get_text()),
+                        notif.notif_type.get_style(),
+                    ),
+                    Span::raw(notif.notif_text.replace('\n', " ")),
+                ]))
+            })
+            .collect();
+
+        let log = List::new(items)
+            .block(
+                Block::default()
+                    .title(format!(
+                        "Notifications ({}/{})",
+                        self.history.len().saturating_sub(self.log_offset),
+                        self.history.len()
+                    ))
+                    .border_type(BorderType::Rounded)
+                    .borders(Borders::ALL),
+            )
+            .style(
+                Style::default()
+                    .fg(config::THEME.foreground)
+                    .bg(config::THEME.background),
+            );
+
+        f.render_widget(Clear, render_area);
+        f.render_widget(log, render_area);
+    }
 }
 
 fn center_rect(pct_x: u16, pct_y: u16, area: Rect) -> Rect {
@@ -0,0 +1,807 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Page level editing of documents in the local mirror. A document lists its pages in
+// `.content`, either as `cPages` entries ordered by an index string (3.x firmware) or
+// as a plain `pages` list with `redirectionPageMap` and a `.pagedata` file of
+// templates alongside (older firmware). Each page has its own files named after its id.
+// Every edit returns the mirror files it touched so they can be pushed to the tablet.
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    config,
+    fs_interface::{document_file_type, edit_content, new_metadata, MetadataType},
+    intern_error::{Error, ErrorContext, ResultExt},
+};
+
+pub struct Page {
+    pub id: String,
+    pub template: Option<String>,
+    pub tags: Vec<String>,
+    // Pages that were never written on have no .rm file
+    pub has_lines: bool,
+    pub has_thumbnail: bool,
+}
+
+// One page as .content stores it, in whichever format the document uses
+struct PageEntry {
+    id: String,
+    // Entry in `cPages.pages`, null for documents in the older format
+    entry: Value,
+    // Older format only, PDF page shown under this one (-1 for inserted pages)
+    redirect: Option<Value>,
+    // Older format only, this page's line in `.pagedata`
+    template: Option<String>,
+}
+
+struct PageList {
+    cpages: bool,
+    pages: Vec<PageEntry>,
+    // Entries 3.x marked as deleted, written back untouched
+    deleted: Vec<Value>,
+}
+
+fn mirror() -> &'static Path {
+    Path::new(&config::SETTINGS.backup_loc)
+}
+
+// Files a page can have, relative to the mirror
+fn page_files(uuid: &str, page: &str) -> Vec<String> {
+    vec![
+        format!("{}/{}.rm", uuid, page),
+        format!("{}/{}-metadata.json", uuid, page),
+        format!("{}.thumbnails/{}.png", uuid, page),
+        format!("{}.thumbnails/{}.jpg", uuid, page),
+        format!("{}.highlights/{}.json", uuid, page),
+        format!("{}.textconversion/{}.json", uuid, page),
+    ]
+}
+
+// Pairs of (file of `page` in `from`, same file for `new_page` in `to`) that exist
+fn existing_page_files(
+    from: &str,
+    page: &str,
+    to: &str,
+    new_page: &str,
+) -> impl Iterator<Item = (String, String)> {
+    page_files(from, page)
+        .into_iter()
+        .zip(page_files(to, new_page))
+        .filter(|(file, _)| mirror().join(file).exists())
+}
+
+fn read_json(path: &Path) -> Result<Value, Error> {
+    let context = || ErrorContext::file(path);
+
+    serde_json::from_str(&fs::read_to_string(path).context(context())?).context(context())
+}
+
+fn pagedata_path(uuid: &str) -> String {
+    format!("{}.pagedata", uuid)
+}
+
+fn read_pages(uuid: &str, content: &Value) -> PageList {
+    if let Some(entries) = content["cPages"]["pages"].as_array() {
+        let (mut live, deleted): (Vec<Value>, Vec<Value>) = entries
+            .iter()
+            .cloned()
+            .partition(|entry| entry["deleted"]["value"].as_i64().unwrap_or(0) == 0);
+
+        live.sort_by(|a, b| {
+            let idx = |entry: &Value| String::from(entry["idx"]["value"].as_str().unwrap_or(""));
+            idx(a).cmp(&idx(b))
+        });
+
+        return PageList {
+            cpages: true,
+            pages: live
+                .into_iter()
+                .filter_map(|entry| {
+                    Some(PageEntry {
+                        id: String::from(entry["id"].as_str()?),
+                        entry,
+                        redirect: None,
+                        template: None,
+                    })
+                })
+                .collect(),
+            deleted,
+        };
+    }
+
+    let templates: Vec<String> = fs::read_to_string(mirror().join(pagedata_path(uuid)))
+        .map(|data| data.lines().map(String::from).collect())
+        .unwrap_or_default();
+
+    PageList {
+        cpages: false,
+        pages: content["pages"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .filter_map(|(idx, id)| {
+                Some(PageEntry {
+                    id: String::from(id.as_str()?),
+                    entry: Value::Null,
+                    redirect: content["redirectionPageMap"].get(idx).cloned(),
+                    template: templates.get(idx).cloned(),
+                })
+            })
+            .collect(),
+        deleted: Vec::new(),
+    }
+}
+
+// Highest counter used in the CRDT timestamps ("<author>:<counter>") of a value
+fn max_timestamp(value: &Value) -> u64 {
+    match value {
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| match (key.as_str(), value.as_str()) {
+                ("timestamp", Some(stamp)) => stamp
+                    .split(':')
+                    .nth(1)
+                    .and_then(|counter| counter.parse().ok())
+                    .unwrap_or(0),
+                _ => max_timestamp(value),
+            })
+            .max()
+            .unwrap_or(0),
+        Value::Array(values) => values.iter().map(max_timestamp).max().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+// Index strings that sort in page order, "ba", "bb", ... widening as needed
+fn index_key(idx: usize, count: usize) -> String {
+    let mut width = 1;
+
+    while 26usize.pow(width) < count {
+        width += 1;
+    }
+
+    let mut key = vec![b'a'; width as usize];
+    let mut rest = idx;
+
+    for place in key.iter_mut().rev() {
+        *place = b'a' + (rest % 26) as u8;
+        rest /= 26;
+    }
+
+    format!("b{}", String::from_utf8_lossy(&key))
+}
+
+// Put the page list back into .content, also writing .pagedata for older documents
+fn write_pages(uuid: &str, content: &mut Value, list: PageList) -> Result<(), Error> {
+    let ids: HashSet<&str> = list.pages.iter().map(|page| page.id.as_str()).collect();
+
+    if let Some(tags) = content["pageTags"].as_array_mut() {
+        tags.retain(|tag| ids.contains(tag["pageId"].as_str().unwrap_or_default()));
+    }
+
+    content["pageCount"] = json!(list.pages.len());
+
+    if list.cpages {
+        let stamp = format!("1:{}", max_timestamp(&content["cPages"]) + 1);
+        let count = list.pages.len();
+
+        let mut entries: Vec<Value> = list
+            .pages
+            .iter()
+            .enumerate()
+            .map(|(idx, page)| {
+                let mut entry = match &page.entry {
+                    Value::Object(_) => page.entry.clone(),
+                    _ => json!({}),
+                };
+
+                entry["id"] = json!(page.id);
+                entry["idx"] = json!({ "timestamp": stamp, "value": index_key(idx, count) });
+
+                if let (Value::Null, Some(template)) = (&entry["template"], &page.template) {
+                    entry["template"] = json!({ "timestamp": stamp, "value": template });
+                }
+
+                entry
+            })
+            .collect();
+
+        let last_opened = content["cPages"]["lastOpened"]["value"].as_str();
+
+        if !last_opened.is_some_and(|id| ids.contains(id)) {
+            if let Some(first) = list.pages.first() {
+                content["cPages"]["lastOpened"] = json!({ "timestamp": stamp, "value": first.id });
+            }
+        }
+
+        entries.extend(list.deleted);
+        content["cPages"]["pages"] = json!(entries);
+
+        return Ok(());
+    }
+
+    content["pages"] = json!(list
+        .pages
+        .iter()
+        .map(|page| page.id.as_str())
+        .collect::<Vec<_>>());
+
+    if content.get("redirectionPageMap").is_some() {
+        content["redirectionPageMap"] = json!(list
+            .pages
+            .iter()
+            .map(|page| page.redirect.clone().unwrap_or(json!(-1)))
+            .collect::<Vec<_>>());
+    }
+
+    let pagedata = mirror().join(pagedata_path(uuid));
+
+    if pagedata.exists() || list.pages.iter().any(|page| page.template.is_some()) {
+        let lines: Vec<&str> = list
+            .pages
+            .iter()
+            .map(|page| {
+                page.template
+                    .as_deref()
+                    .or(page.entry["template"]["value"].as_str())
+                    .unwrap_or("Blank")
+            })
+            .collect();
+
+        fs::write(&pagedata, lines.join("\n") + "\n").context(ErrorContext::file(&pagedata))?;
+    }
+
+    Ok(())
+}
+
+// Read the page list and let `edit` change it, nothing is written yet
+fn plan_pages(
+    uuid: &str,
+    edit: impl FnOnce(&mut Value, &mut PageList) -> Result<(), Error>,
+) -> Result<(Value, PageList), Error> {
+    let mut content = read_json(&mirror().join(format!("{}.content", uuid)))?;
+    let mut list = read_pages(uuid, &content);
+
+    edit(&mut content, &mut list)?;
+
+    if list.pages.is_empty() {
+        return Err(Error::PageEditError(String::from(
+            "A document needs at least one page",
+        )));
+    }
+
+    Ok((content, list))
+}
+
+// Read the page list, let `edit` change it and write everything back. Returns the
+// document's own files that need pushing.
+fn edit_pages(
+    uuid: &str,
+    edit: impl FnOnce(&mut Value, &mut PageList) -> Result<(), Error>,
+) -> Result<Vec<String>, Error> {
+    let (content, list) = plan_pages(uuid, edit)?;
+
+    save_pages(uuid, content, list)
+}
+
+// Files a document keeps its page list in, with their contents if they exist
+fn snapshot_pages(uuid: &str) -> Vec<(PathBuf, Option<Vec<u8>>)> {
+    [
+        format!("{}.content", uuid),
+        format!("{}.metadata", uuid),
+        pagedata_path(uuid),
+    ]
+    .into_iter()
+    .map(|file| {
+        let path = mirror().join(file);
+        let data = fs::read(&path).ok();
+        (path, data)
+    })
+    .collect()
+}
+
+fn restore_pages(snapshot: Vec<(PathBuf, Option<Vec<u8>>)>) {
+    for (path, data) in snapshot {
+        let restored = match data {
+            Some(data) => fs::write(&path, data),
+            None if path.exists() => fs::remove_file(&path),
+            None => Ok(()),
+        };
+
+        if let Err(why) = restored {
+            log::error!("Couldn't restore {}: {}", path.display(), why);
+        }
+    }
+}
+
+// Write a page list `plan_pages` gave back. Returns the document's own files that need
+// pushing.
+fn save_pages(uuid: &str, mut content: Value, list: PageList) -> Result<Vec<String>, Error> {
+    write_pages(uuid, &mut content, list)?;
+    edit_content(uuid, |value| *value = content)?;
+
+    let mut files = vec![format!("{}.content", uuid), format!("{}.metadata", uuid)];
+
+    if mirror().join(pagedata_path(uuid)).exists() {
+        files.push(pagedata_path(uuid));
+    }
+
+    Ok(files)
+}
+
+fn position(list: &PageList, page: &str) -> Result<usize, Error> {
+    list.pages
+        .iter()
+        .position(|entry| entry.id == page)
+        .ok_or_else(|| Error::PageEditError(format!("No page {}", page)))
+}
+
+pub fn document_page_list(uuid: &str) -> Result<Vec<Page>, Error> {
+    let content = read_json(&mirror().join(format!("{}.content", uuid)))?;
+    let list = read_pages(uuid, &content);
+
+    Ok(list
+        .pages
+        .into_iter()
+        .map(|page| {
+            let exists = |file: String| mirror().join(file).exists();
+
+            Page {
+                template: page
+                    .template
+                    .or_else(|| page.entry["template"]["value"].as_str().map(String::from)),
+                tags: content["pageTags"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter(|tag| tag["pageId"].as_str() == Some(&page.id))
+                    .filter_map(|tag| tag["name"].as_str().map(String::from))
+                    .collect(),
+                has_lines: exists(format!("{}/{}.rm", uuid, page.id)),
+                has_thumbnail: ["png", "jpg"]
+                    .iter()
+                    .any(|ext| exists(format!("{}.thumbnails/{}.{}", uuid, page.id, ext))),
+                id: page.id,
+            }
+        })
+        .collect())
+}
+
+// Move a page `delta` places towards the end, or the start if negative
+pub fn move_page(uuid: &str, page: &str, delta: isize) -> Result<Vec<String>, Error> {
+    edit_pages(uuid, |_, list| {
+        let from = position(list, page)?;
+        let to = from
+            .saturating_add_signed(delta)
+            .min(list.pages.len().saturating_sub(1));
+
+        let entry = list.pages.remove(from);
+        list.pages.insert(to, entry);
+
+        Ok(())
+    })
+    .context(ErrorContext::document(uuid))
+}
+
+// Returns the files to push and the ones to remove from the tablet
+pub fn delete_pages(uuid: &str, pages: &[String]) -> Result<(Vec<String>, Vec<String>), Error> {
+    let files = edit_pages(uuid, |_, list| {
+        list.pages.retain(|entry| !pages.contains(&entry.id));
+        Ok(())
+    })
+    .context(ErrorContext::document(uuid))?;
+
+    let mut removed = Vec::new();
+
+    for page in pages {
+        for (file, _) in existing_page_files(uuid, page, uuid, page).collect::<Vec<_>>() {
+            let path = mirror().join(&file);
+            fs::remove_file(&path).context(ErrorContext::file(&path))?;
+            removed.push(file);
+        }
+    }
+
+    Ok((files, removed))
+}
+
+// Copy a page and its files, the copy goes right after the original
+pub fn duplicate_page(uuid: &str, page: &str) -> Result<Vec<String>, Error> {
+    let copy = Uuid::new_v4().to_string();
+
+    let mut files = edit_pages(uuid, |content, list| {
+        let idx = position(list, page)?;
+        let original = &list.pages[idx];
+
+        let entry = PageEntry {
+            id: copy.clone(),
+            entry: original.entry.clone(),
+            redirect: original.redirect.clone(),
+            template: original.template.clone(),
+        };
+        list.pages.insert(idx + 1, entry);
+
+        copy_page_tags(content, page, &copy);
+
+        Ok(())
+    })
+    .context(ErrorContext::document(uuid))?;
+
+    for (file, new_file) in existing_page_files(uuid, page, uuid, &copy) {
+        let (from, to) = (mirror().join(&file), mirror().join(&new_file));
+        fs::copy(&from, &to).context(ErrorContext::file(&from))?;
+        files.push(new_file);
+    }
+
+    Ok(files)
+}
+
+fn copy_page_tags(content: &mut Value, page: &str, copy: &str) {
+    let copies: Vec<Value> = content["pageTags"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|tag| tag["pageId"].as_str() == Some(page))
+        .map(|tag| {
+            let mut tag = tag.clone();
+            tag["pageId"] = json!(copy);
+            tag
+        })
+        .collect();
+
+    if let Some(tags) = content["pageTags"].as_array_mut() {
+        tags.extend(copies);
+    }
+}
+
+// Move pages to the end of another notebook, keeping their ids, files and tags. Pages
+// of PDFs and EPUBs show the document under them, so only notebooks qualify. Returns
+// the files to push and the ones to remove from the tablet.
+pub fn move_pages(
+    uuid: &str,
+    pages: &[String],
+    target: &str,
+) -> Result<(Vec<String>, Vec<String>), Error> {
+    if uuid == target {
+        return Err(Error::PageEditError(String::from(
+            "Pages are already in that notebook",
+        )));
+    }
+
+    for document in [uuid, target] {
+        let file_type = document_file_type(document);
+
+        if file_type != "notebook" {
+            return Err(Error::PageEditError(format!(
+                "Pages can only be moved between notebooks, {} is a {}",
+                document, file_type
+            )));
+        }
+    }
+
+    let mut moved = Vec::new();
+    let mut tags = Vec::new();
+
+    // Both page lists are worked out before either document is written
+    let (source_content, source_list) = plan_pages(uuid, |content, list| {
+        for page in pages {
+            let idx = position(list, page)?;
+            moved.push(list.pages.remove(idx));
+        }
+
+        tags = content["pageTags"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|tag| {
+                pages
+                    .iter()
+                    .any(|page| tag["pageId"].as_str() == Some(page))
+            })
+            .cloned()
+            .collect();
+
+        Ok(())
+    })
+    .context(ErrorContext::document(uuid))?;
+
+    let (target_content, target_list) = plan_pages(target, |content, list| {
+        for mut page in moved {
+            // Each format keeps the template in its own place
+            page.template = page
+                .template
+                .or_else(|| page.entry["template"]["value"].as_str().map(String::from));
+            page.redirect = None;
+            list.pages.push(page);
+        }
+
+        match content["pageTags"].as_array_mut() {
+            Some(existing) => existing.extend(tags),
+            None => content["pageTags"] = json!(tags),
+        }
+
+        Ok(())
+    })
+    .context(ErrorContext::document(target))?;
+
+    // If either write fails both documents go back to how they were, so the pages
+    // can't end up in neither of them
+    let snapshots = (snapshot_pages(uuid), snapshot_pages(target));
+
+    let saved = save_pages(uuid, source_content, source_list)
+        .context(ErrorContext::document(uuid))
+        .and_then(|mut files| {
+            files.extend(
+                save_pages(target, target_content, target_list)
+                    .context(ErrorContext::document(target))?,
+            );
+            Ok(files)
+        });
+
+    let mut files = match saved {
+        Ok(files) => files,
+        Err(why) => {
+            restore_pages(snapshots.0);
+            restore_pages(snapshots.1);
+            return Err(why);
+        }
+    };
+
+    let mut removed = Vec::new();
+
+    for page in pages {
+        for (file, new_file) in existing_page_files(uuid, page, target, page).collect::<Vec<_>>() {
+            let (from, to) = (mirror().join(&file), mirror().join(&new_file));
+
+            if let Some(dir) = to.parent() {
+                fs::create_dir_all(dir).context(ErrorContext::file(dir))?;
+            }
+
+            fs::rename(&from, &to).context(ErrorContext::file(&from))?;
+            removed.push(file);
+            files.push(new_file);
+        }
+    }
+
+    Ok((files, removed))
+}
+
+// Order the pages of merged notebooks go in
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MergeOrder {
+    // The order the notebooks are given in
+    Selection,
+    // Oldest notebook first, by creation time where the firmware records it
+    Created,
+}
+
+fn created_time(uuid: &str) -> u64 {
+    let metadata = read_json(&mirror().join(format!("{}.metadata", uuid))).unwrap_or_default();
+
+    ["createdTime", "lastModified"]
+        .iter()
+        .find_map(|key| metadata[*key].as_str()?.parse().ok())
+        .unwrap_or(0)
+}
+
+// Combine notebooks into a new one in `parent`. Every page is copied under a new id
+// along with its files, template and tags, the originals are left as they are. The
+// new notebook takes its settings and page format from the first one. Returns the
+// new uuid and the files to push.
+pub fn merge_notebooks(
+    sources: &[String],
+    name: &str,
+    parent: &str,
+    order: MergeOrder,
+) -> Result<(String, Vec<String>), Error> {
+    for source in sources {
+        let file_type = document_file_type(source);
+
+        if file_type != "notebook" {
+            return Err(Error::PageEditError(format!(
+                "Only notebooks can be merged, {} is a {}",
+                source, file_type
+            )));
+        }
+    }
+
+    let mut sources = sources.to_vec();
+
+    if order == MergeOrder::Created {
+        sources.sort_by_key(|source| created_time(source));
+    }
+
+    let Some(first) = sources.first() else {
+        return Err(Error::PageEditError(String::from("Nothing to merge")));
+    };
+
+    let uuid = Uuid::new_v4().to_string();
+
+    match write_merged(&uuid, first, &sources, name, parent) {
+        Ok(files) => Ok((uuid, files)),
+        Err(why) => {
+            remove_document_files(&uuid);
+            Err(why)
+        }
+    }
+}
+
+// Whatever a merge managed to write before it failed
+fn remove_document_files(uuid: &str) {
+    for ext in ["metadata", "content", "pagedata"] {
+        let _ = fs::remove_file(mirror().join(format!("{}.{}", uuid, ext)));
+    }
+
+    for suffix in ["", ".thumbnails", ".highlights", ".textconversion"] {
+        let _ = fs::remove_dir_all(mirror().join(format!("{}{}", uuid, suffix)));
+    }
+}
+
+fn write_merged(
+    uuid: &str,
+    first: &str,
+    sources: &[String],
+    name: &str,
+    parent: &str,
+) -> Result<Vec<String>, Error> {
+    let mut base = read_json(&mirror().join(format!("{}.content", first)))?;
+    let cpages = read_pages(first, &base).cpages;
+
+    if let Some(map) = base.as_object_mut() {
+        for key in ["cPages", "pages", "pageTags", "redirectionPageMap", "tags"] {
+            map.remove(key);
+        }
+    }
+
+    if cpages {
+        base["cPages"] = json!({ "pages": [] });
+    }
+
+    let metadata_path = mirror().join(format!("{}.metadata", uuid));
+    let content_path = mirror().join(format!("{}.content", uuid));
+
+    fs::write(
+        &metadata_path,
+        serde_json::to_string_pretty(&new_metadata(name, parent, MetadataType::DocumentType))?,
+    )
+    .context(ErrorContext::file(&metadata_path))?;
+    fs::write(&content_path, serde_json::to_string_pretty(&base)?)
+        .context(ErrorContext::file(&content_path))?;
+
+    let mut pages = Vec::new();
+    let mut page_tags = Vec::new();
+    let mut tags: Vec<Value> = Vec::new();
+    let mut files = Vec::new();
+
+    for source in sources {
+        let content = read_json(&mirror().join(format!("{}.content", source)))?;
+
+        for mut page in read_pages(source, &content).pages {
+            let id = Uuid::new_v4().to_string();
+
+            for tag in content["pageTags"].as_array().into_iter().flatten() {
+                if tag["pageId"].as_str() == Some(&page.id) {
+                    let mut tag = tag.clone();
+                    tag["pageId"] = json!(id);
+                    page_tags.push(tag);
+                }
+            }
+
+            for (file, new_file) in existing_page_files(source, &page.id, uuid, &id) {
+                let (from, to) = (mirror().join(&file), mirror().join(&new_file));
+
+                if let Some(dir) = to.parent() {
+                    fs::create_dir_all(dir).context(ErrorContext::file(dir))?;
+                }
+
+                fs::copy(&from, &to).context(ErrorContext::file(&from))?;
+                files.push(new_file);
+            }
+
+            page.template = page
+                .template
+                .or_else(|| page.entry["template"]["value"].as_str().map(String::from));
+            page.redirect = None;
+            page.id = id;
+            pages.push(page);
+        }
+
+        // Document tags are shared, the first timestamp wins
+        for tag in content["tags"].as_array().into_iter().flatten() {
+            if !tags.iter().any(|existing| existing["name"] == tag["name"]) {
+                tags.push(tag.clone());
+            }
+        }
+    }
+
+    files.extend(
+        edit_pages(uuid, |content, list| {
+            list.pages = pages;
+            content["pageTags"] = json!(page_tags);
+            content["tags"] = json!(tags);
+            Ok(())
+        })
+        .context(ErrorContext::document(uuid))?,
+    );
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(count: usize) -> Vec<String> {
+        (0..count).map(|idx| index_key(idx, count)).collect()
+    }
+
+    #[test]
+    fn index_keys_for_a_few_pages() {
+        assert_eq!(keys(3), ["ba", "bb", "bc"]);
+    }
+
+    #[test]
+    fn index_keys_widen_past_26_pages() {
+        assert_eq!(keys(26).last().map(String::as_str), Some("bz"));
+
+        let wide = keys(27);
+
+        assert_eq!(wide[0], "baa");
+        assert_eq!(wide[25], "baz");
+        assert_eq!(wide[26], "bba");
+    }
+
+    #[test]
+    fn index_keys_sort_in_page_order() {
+        for count in [1, 26, 27, 700, 677] {
+            let keys = keys(count);
+            let mut sorted = keys.clone();
+            sorted.sort();
+
+            assert_eq!(keys, sorted, "{} pages", count);
+            assert!(keys.iter().all(|key| key.len() == keys[0].len()));
+        }
+    }
+
+    #[test]
+    fn max_timestamp_looks_everywhere() {
+        let value = json!({
+            "lastOpened": { "timestamp": "1:4", "value": "p1" },
+            "pages": [
+                { "id": "p1", "idx": { "timestamp": "1:2", "value": "ba" } },
+                { "id": "p2", "template": { "timestamp": "2:17", "value": "Blank" } },
+            ],
+        });
+
+        assert_eq!(max_timestamp(&value), 17);
+    }
+
+    #[test]
+    fn max_timestamp_skips_odd_stamps() {
+        let value = json!([
+            { "timestamp": "garbage" },
+            { "timestamp": 5 },
+            { "idx": { "timestamp": "1:3" } },
+        ]);
+
+        assert_eq!(max_timestamp(&value), 3);
+        assert_eq!(max_timestamp(&json!({})), 0);
+    }
+}
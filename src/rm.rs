@@ -0,0 +1,424 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Reader for the v6 .rm format used by firmware 3.x. A file is a header followed by
+// blocks, each block a run of tagged values. Only the parts noteworthy needs are decoded.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::intern_error::Error;
+
+const HEADER_V6: &[u8] = b"reMarkable .lines file, version=6          ";
+
+// Block types
+const BLOCK_GLYPH_ITEM: u8 = 0x03;
+const BLOCK_ROOT_TEXT: u8 = 0x07;
+
+// Tag types
+const TAG_BYTE4: u8 = 0x4;
+const TAG_LENGTH4: u8 = 0xC;
+const TAG_ID: u8 = 0xF;
+
+pub struct Block<'a> {
+    pub block_type: u8,
+    pub data: &'a [u8],
+}
+
+// Highlighted span of text on a page, as stored by the tablet
+pub struct Glyph {
+    pub start: Option<u32>,
+    pub color: u32,
+    pub text: String,
+}
+
+// Identifier of an item in a CRDT sequence, (0, 0) marks either end of the sequence
+pub type CrdtId = (u8, u64);
+
+const END_MARKER: CrdtId = (0, 0);
+
+// Inline formatting codes stored between characters of typed text
+const FORMAT_BOLD_ON: u32 = 1;
+const FORMAT_BOLD_OFF: u32 = 2;
+const FORMAT_ITALIC_ON: u32 = 3;
+const FORMAT_ITALIC_OFF: u32 = 4;
+
+// Paragraph styles of typed text, numbered as the tablet stores them
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ParagraphStyle {
+    Basic,
+    Plain,
+    Heading,
+    Bold,
+    Bullet,
+    Bullet2,
+    Checkbox,
+    CheckboxChecked,
+}
+
+// Run of typed text sharing the same inline formatting
+#[derive(Clone, PartialEq, Debug)]
+pub struct TextSpan {
+    pub text: String,
+    pub bold: bool,
+    pub italic: bool,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Paragraph {
+    pub style: ParagraphStyle,
+    pub spans: Vec<TextSpan>,
+}
+
+enum TextValue {
+    Text(String),
+    Format(u32),
+}
+
+// One insertion into the typed text. `left` and `right` are the neighbours it was
+// inserted between, deleted text keeps its item but loses its value.
+struct TextItem {
+    id: CrdtId,
+    left: CrdtId,
+    right: CrdtId,
+    value: Option<TextValue>,
+}
+
+impl ParagraphStyle {
+    fn from_code(code: u8) -> Self {
+        match code {
+            0 => Self::Basic,
+            2 => Self::Heading,
+            3 => Self::Bold,
+            4 => Self::Bullet,
+            5 => Self::Bullet2,
+            6 => Self::Checkbox,
+            7 => Self::CheckboxChecked,
+            _ => Self::Plain,
+        }
+    }
+}
+
+impl Paragraph {
+    fn push(&mut self, c: char, bold: bool, italic: bool) {
+        match self.spans.last_mut() {
+            Some(span) if span.bold == bold && span.italic == italic => span.text.push(c),
+            _ => self.spans.push(TextSpan {
+                text: c.to_string(),
+                bold,
+                italic,
+            }),
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.spans.iter().map(|span| span.text.as_str()).collect()
+    }
+}
+
+// Put the items of a CRDT sequence in document order: every item comes after its left
+// neighbour and before its right one. Ties go to the lower id so the order is stable,
+// and anything caught in a cycle is tacked on at the end rather than lost.
+fn order_items(items: Vec<TextItem>) -> Vec<TextItem> {
+    let mut by_id: HashMap<CrdtId, TextItem> =
+        items.into_iter().map(|item| (item.id, item)).collect();
+
+    let mut incoming: HashMap<CrdtId, usize> = by_id.keys().map(|id| (*id, 0)).collect();
+    let mut outgoing: HashMap<CrdtId, Vec<CrdtId>> = HashMap::new();
+
+    for item in by_id.values() {
+        for (before, after) in [(item.left, item.id), (item.id, item.right)] {
+            if before != END_MARKER
+                && after != END_MARKER
+                && by_id.contains_key(&before)
+                && by_id.contains_key(&after)
+            {
+                outgoing.entry(before).or_default().push(after);
+                *incoming.entry(after).or_default() += 1;
+            }
+        }
+    }
+
+    let mut ready: BTreeSet<CrdtId> = incoming
+        .iter()
+        .filter(|(_, count)| **count == 0)
+        .map(|(id, _)| *id)
+        .collect();
+    let mut ordered = Vec::with_capacity(by_id.len());
+
+    while let Some(id) = ready.pop_first() {
+        for next in outgoing.remove(&id).into_iter().flatten() {
+            if let Some(count) = incoming.get_mut(&next) {
+                *count -= 1;
+
+                if *count == 0 {
+                    ready.insert(next);
+                }
+            }
+        }
+
+        if let Some(item) = by_id.remove(&id) {
+            ordered.push(item);
+        }
+    }
+
+    let mut rest: Vec<TextItem> = by_id.into_values().collect();
+    rest.sort_by_key(|item| item.id);
+    ordered.extend(rest);
+
+    ordered
+}
+
+// Cursor over the bytes of a block
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|b| b[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn varuint(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= u64::from(byte & 0x7F) << shift;
+
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+
+        None
+    }
+
+    // Consume the tag if it is the expected one, optional values are simply absent
+    fn tag(&mut self, index: u64, tag_type: u8) -> Option<()> {
+        let start = self.pos;
+
+        match self.varuint() {
+            Some(tag) if tag >> 4 == index && (tag & 0xF) as u8 == tag_type => Some(()),
+            _ => {
+                self.pos = start;
+                None
+            }
+        }
+    }
+
+    fn crdt_id(&mut self) -> Option<CrdtId> {
+        Some((self.u8()?, self.varuint()?))
+    }
+
+    fn read_id(&mut self, index: u64) -> Option<CrdtId> {
+        self.tag(index, TAG_ID)?;
+        self.crdt_id()
+    }
+
+    fn read_int(&mut self, index: u64) -> Option<u32> {
+        self.tag(index, TAG_BYTE4)?;
+        self.u32()
+    }
+
+    fn read_subblock(&mut self, index: u64) -> Option<Reader<'a>> {
+        self.tag(index, TAG_LENGTH4)?;
+        let length = usize::try_from(self.u32()?).ok()?;
+        self.bytes(length).map(Reader::new)
+    }
+
+    fn read_string(&mut self, index: u64) -> Option<String> {
+        self.read_string_with_format(index).map(|(text, _)| text)
+    }
+
+    // Strings in typed text can carry a formatting code after the text
+    fn read_string_with_format(&mut self, index: u64) -> Option<(String, Option<u32>)> {
+        let mut block = self.read_subblock(index)?;
+        let length = usize::try_from(block.varuint()?).ok()?;
+        let _is_ascii = block.u8()?;
+
+        let text = block
+            .bytes(length)
+            .map(|bytes| String::from_utf8_lossy(bytes).to_string())?;
+
+        Some((text, block.read_int(2)))
+    }
+}
+
+pub fn is_v6(data: &[u8]) -> bool {
+    data.starts_with(HEADER_V6)
+}
+
+// Split a v6 file into its blocks
+pub fn blocks(data: &[u8]) -> Result<Vec<Block<'_>>, Error> {
+    if !is_v6(data) {
+        return Err(Error::RmParseError(String::from("not a v6 file")));
+    }
+
+    let mut reader = Reader::new(&data[HEADER_V6.len()..]);
+    let mut blocks = Vec::new();
+
+    while reader.pos < reader.data.len() {
+        let block = (|| {
+            let length = usize::try_from(reader.u32()?).ok()?;
+            let header = reader.bytes(4)?;
+            let data = reader.bytes(length)?;
+
+            Some(Block {
+                block_type: header[3],
+                data,
+            })
+        })()
+        .ok_or_else(|| Error::RmParseError(format!("truncated block at {}", reader.pos)))?;
+
+        blocks.push(block);
+    }
+
+    Ok(blocks)
+}
+
+impl Block<'_> {
+    // Highlights made with the text highlighter on PDF and EPUB pages. Deleted items
+    // have no value and are skipped.
+    pub fn glyph(&self) -> Option<Glyph> {
+        if self.block_type != BLOCK_GLYPH_ITEM {
+            return None;
+        }
+
+        let mut reader = Reader::new(self.data);
+
+        for index in 1..=4 {
+            reader.read_id(index)?;
+        }
+        reader.read_int(5)?;
+
+        let mut value = reader.read_subblock(6)?;
+        let _item_type = value.u8()?;
+
+        let start = value.read_int(2);
+        let _length = value.read_int(3)?;
+        let color = value.read_int(4)?;
+        let text = value.read_string(5)?;
+
+        Some(Glyph { start, color, text })
+    }
+
+    // Typed text of a page, split into paragraphs. Only the first root text block on a
+    // page carries text.
+    pub fn root_text(&self) -> Option<Vec<Paragraph>> {
+        if self.block_type != BLOCK_ROOT_TEXT {
+            return None;
+        }
+
+        let mut reader = Reader::new(self.data);
+        reader.read_id(1)?;
+
+        let mut root = reader.read_subblock(2)?;
+
+        let mut sequence = root.read_subblock(1)?.read_subblock(1)?;
+        let mut items = Vec::new();
+
+        for _ in 0..sequence.varuint()? {
+            let mut item = sequence.read_subblock(0)?;
+
+            let id = item.read_id(2)?;
+            let left = item.read_id(3)?;
+            let right = item.read_id(4)?;
+            let _deleted_length = item.read_int(5)?;
+
+            let value = item
+                .read_string_with_format(6)
+                .map(|(text, format)| match format {
+                    Some(code) => TextValue::Format(code),
+                    None => TextValue::Text(text),
+                });
+
+            items.push(TextItem {
+                id,
+                left,
+                right,
+                value,
+            });
+        }
+
+        // Paragraph styles are keyed by the newline starting the paragraph, the first
+        // paragraph has no newline and uses the end marker
+        let mut styles = HashMap::new();
+
+        if let Some(mut formats) = root
+            .read_subblock(2)
+            .and_then(|mut block| block.read_subblock(1))
+        {
+            for _ in 0..formats.varuint().unwrap_or(0) {
+                let Some(char_id) = formats.crdt_id() else {
+                    break;
+                };
+                let _timestamp = formats.read_id(1)?;
+                let mut value = formats.read_subblock(2)?;
+                let _marker = value.u8()?;
+
+                styles.insert(char_id, ParagraphStyle::from_code(value.u8()?));
+            }
+        }
+
+        let style_of = |id: &CrdtId| styles.get(id).copied().unwrap_or(ParagraphStyle::Plain);
+
+        let mut paragraphs = vec![Paragraph {
+            style: style_of(&END_MARKER),
+            spans: Vec::new(),
+        }];
+        let (mut bold, mut italic) = (false, false);
+
+        for item in order_items(items) {
+            match item.value {
+                None => (),
+                Some(TextValue::Format(code)) => match code {
+                    FORMAT_BOLD_ON => bold = true,
+                    FORMAT_BOLD_OFF => bold = false,
+                    FORMAT_ITALIC_ON => italic = true,
+                    FORMAT_ITALIC_OFF => italic = false,
+                    _ => (),
+                },
+                Some(TextValue::Text(text)) => {
+                    // Every character has its own id, counting up from the item's
+                    for (offset, c) in (0u64..).zip(text.chars()) {
+                        if c == '\n' {
+                            paragraphs.push(Paragraph {
+                                style: style_of(&(item.id.0, item.id.1 + offset)),
+                                spans: Vec::new(),
+                            });
+                        } else if let Some(paragraph) = paragraphs.last_mut() {
+                            paragraph.push(c, bold, italic);
+                        }
+                    }
+                }
+            }
+        }
+
+        Some(paragraphs)
+    }
+}
@@ -0,0 +1,61 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// UI state that outlives a session, kept as JSON at SETTINGS.save_data_loc
+
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    intern_error::{Error, ErrorContext, ResultExt},
+    ui::{file_item::SortOrder, layout::PaneLayout},
+};
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct SaveData {
+    pub local_sort: SortOrder,
+    pub remote_sort: SortOrder,
+    pub layout: PaneLayout,
+    // Whether the tablet library is shown as a tree
+    pub remote_tree: bool,
+    // Milliseconds since the epoch of the last successful sync
+    pub last_sync: Option<i64>,
+}
+
+impl SaveData {
+    // A missing or unreadable file just means starting from the defaults
+    pub fn load() -> SaveData {
+        let path = &config::SETTINGS.save_data_loc;
+
+        match fs::read_to_string(path) {
+            Err(_) => SaveData::default(),
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|why| {
+                log::warn!("Ignoring unreadable save data {}: {}", path.display(), why);
+                SaveData::default()
+            }),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let path = &config::SETTINGS.save_data_loc;
+        let context = || ErrorContext::file(path);
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context(context())?;
+        }
+
+        fs::write(path, serde_json::to_string_pretty(self)?).context(context())
+    }
+}
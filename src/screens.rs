@@ -0,0 +1,178 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// The screens the tablet shows while asleep, powered off and starting up. They are
+// plain greyscale PNGs at the panel's resolution. The first time one is replaced the
+// original is downloaded into the state directory so it can be restored later.
+
+use std::{fs, path::Path};
+
+use image::{imageops, imageops::FilterType, GrayImage, Luma};
+
+use crate::{
+    config, device,
+    intern_error::{Error, ErrorContext, ResultExt},
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Screen {
+    Suspended,
+    PowerOff,
+    Starting,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ScreenState {
+    Original,
+    Customised,
+    // Couldn't be compared, e.g. the file is missing on the tablet
+    Unknown,
+}
+
+impl Screen {
+    pub const ALL: [Screen; 3] = [Screen::Suspended, Screen::PowerOff, Screen::Starting];
+
+    pub fn file_name(&self) -> &str {
+        match self {
+            Self::Suspended => "suspended.png",
+            Self::PowerOff => "poweroff.png",
+            Self::Starting => "starting.png",
+        }
+    }
+
+    pub fn get_text(&self) -> &str {
+        match self {
+            Self::Suspended => "Sleep",
+            Self::PowerOff => "Power off",
+            Self::Starting => "Starting",
+        }
+    }
+
+    fn remote(&self) -> String {
+        format!(
+            "{}/{}",
+            config::SETTINGS.screens_path.trim_end_matches('/'),
+            self.file_name()
+        )
+    }
+
+    fn backup(&self) -> std::path::PathBuf {
+        config::SETTINGS
+            .state_dir
+            .join("screens")
+            .join("originals")
+            .join(self.file_name())
+    }
+}
+
+impl ScreenState {
+    pub fn get_text(&self) -> &str {
+        match self {
+            Self::Original => "original",
+            Self::Customised => "customised",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+// Without a backup the screen was never replaced by us. With one, the converted images
+// never come out byte for byte the size of the stock ones, so comparing sizes is enough.
+fn screen_state(screen: Screen) -> Result<ScreenState, Error> {
+    let Ok(backup) = fs::metadata(screen.backup()) else {
+        return Ok(ScreenState::Original);
+    };
+
+    match device::file_size(&screen.remote()) {
+        Ok(size) if size == backup.len() => Ok(ScreenState::Original),
+        Ok(_) => Ok(ScreenState::Customised),
+        Err(why) if why.is_transient() => Err(why),
+        Err(_) => Ok(ScreenState::Unknown),
+    }
+}
+
+pub fn screen_states() -> Result<Vec<(Screen, ScreenState)>, Error> {
+    Screen::ALL
+        .iter()
+        .map(|screen| Ok((*screen, screen_state(*screen)?)))
+        .collect()
+}
+
+// Scale an image to fit the panel, centre it on white and drop the colour
+fn convert(source: &Path, dest: &Path) -> Result<(), Error> {
+    let (width, height) = config::SETTINGS.screen_resolution;
+
+    let image = image::open(source)
+        .map_err(|why| Error::ImageError(why.to_string()))
+        .context(ErrorContext::file(source))?
+        .resize(width, height, FilterType::Lanczos3)
+        .into_luma8();
+
+    let mut canvas = GrayImage::from_pixel(width, height, Luma([255]));
+
+    imageops::overlay(
+        &mut canvas,
+        &image,
+        i64::from((width - image.width()) / 2),
+        i64::from((height - image.height()) / 2),
+    );
+
+    canvas
+        .save(dest)
+        .map_err(|why| Error::ImageError(why.to_string()))
+        .context(ErrorContext::file(dest))
+}
+
+// Replace a screen with a local image, backing the original up first
+pub fn install(screen: Screen, source: &Path) -> Result<(), Error> {
+    let staged = config::SETTINGS
+        .state_dir
+        .join("screens")
+        .join(screen.file_name());
+
+    if let Some(dir) = screen.backup().parent() {
+        fs::create_dir_all(dir).context(ErrorContext::file(dir))?;
+    }
+
+    convert(source, &staged)?;
+
+    if !screen.backup().exists() {
+        log::info!(
+            "Backing up original {} to {}",
+            screen.remote(),
+            screen.backup().display()
+        );
+        device::download(&screen.remote(), &screen.backup(), &mut |_| Ok(()))?;
+    }
+
+    log::info!(
+        "Replacing {} screen with {}",
+        screen.get_text(),
+        source.display()
+    );
+
+    device::upload(&staged, &screen.remote(), &mut |_| Ok(()))
+}
+
+pub fn restore(screen: Screen) -> Result<(), Error> {
+    let backup = screen.backup();
+
+    if !backup.exists() {
+        return Err(Error::UnsupportedFileError(format!(
+            "{} has no backup to restore",
+            screen.file_name()
+        )));
+    }
+
+    log::info!("Restoring original {} screen", screen.get_text());
+
+    device::upload(&backup, &screen.remote(), &mut |_| Ok(()))
+}
@@ -0,0 +1,362 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Preview of a sync. rsync is asked what it would transfer, and the files it lists
+// are grouped by the document they belong to so the changes can be shown by name.
+// A sync only pulls from the tablet, edits made here are pushed as they happen, so
+// the preview only has the tablet to mirror direction to show.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::{
+    config, device, fs_interface::dry_run_sync, intern_error::Error, transfer::format_size,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum ChangeKind {
+    // On the tablet but not in the mirror yet
+    New,
+    // Changed on the tablet since the last sync
+    Modified,
+    // Changed on the tablet but the mirror copy is newer, the local edits get replaced
+    Conflict,
+}
+
+#[derive(Clone, Debug)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub uuid: String,
+    pub name: String,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+#[derive(Default, Debug)]
+pub struct SyncPlan {
+    pub changes: Vec<Change>,
+}
+
+// Files of one document as listed by rsync
+#[derive(Default)]
+struct Listing {
+    new_metadata: bool,
+    newer_locally: bool,
+    files: usize,
+    bytes: u64,
+}
+
+impl ChangeKind {
+    pub fn get_text(&self) -> &str {
+        match self {
+            Self::New => "new on tablet",
+            Self::Modified => "changed on tablet",
+            Self::Conflict => "local edits replaced",
+        }
+    }
+
+    pub fn get_symbol(&self) -> &str {
+        match self {
+            Self::New => "+",
+            Self::Modified => "~",
+            Self::Conflict => "!",
+        }
+    }
+}
+
+// Document a path in the store belongs to, "<uuid>.metadata" and "<uuid>/<page>.rm"
+// both give "<uuid>"
+fn document_of(path: &str) -> &str {
+    path.split(['.', '/']).next().unwrap_or(path)
+}
+
+// rsync gives times as "2024/01/31-13:45:00" in local time
+fn parse_mtime(mtime: &str) -> Option<DateTime<Local>> {
+    let naive = NaiveDateTime::parse_from_str(mtime, "%Y/%m/%d-%H:%M:%S").ok()?;
+    Local.from_local_datetime(&naive).earliest()
+}
+
+// Whether the mirror copy of a file was changed after the one on the tablet
+fn newer_locally(path: &str, remote_mtime: &str) -> bool {
+    let local = fs::metadata(Path::new(&config::SETTINGS.backup_loc).join(path))
+        .and_then(|meta| meta.modified())
+        .map(DateTime::<Local>::from);
+
+    match (local, parse_mtime(remote_mtime)) {
+        (Ok(local), Some(remote)) => local.timestamp() > remote.timestamp(),
+        _ => false,
+    }
+}
+
+// Lines look like ">f.st......|1234|2024/01/31-13:45:00|<uuid>/<page>.rm"
+fn parse_listing(output: &str) -> BTreeMap<String, Listing> {
+    let mut documents: BTreeMap<String, Listing> = BTreeMap::new();
+
+    for line in output.lines() {
+        let mut fields = line.splitn(4, '|');
+
+        let (Some(flags), Some(size), Some(mtime), Some(path)) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+
+        let flags = flags.trim();
+
+        // Directories come and go with their files
+        if path.ends_with('/') || path.is_empty() {
+            continue;
+        }
+
+        let uuid = document_of(path);
+        let is_metadata = path == format!("{}.metadata", uuid);
+
+        // Only files rsync would actually receive, not attribute tweaks
+        if !flags.starts_with(">f") {
+            continue;
+        }
+
+        let listing = documents.entry(String::from(uuid)).or_default();
+
+        listing.files += 1;
+        listing.bytes += size.trim().parse::<u64>().unwrap_or(0);
+        listing.new_metadata |= is_metadata && flags.contains('+');
+
+        if !flags.contains('+') && newer_locally(path, mtime) {
+            listing.newer_locally = true;
+        }
+    }
+
+    documents
+}
+
+// Names of documents the mirror doesn't know about yet, read from their metadata on
+// the tablet in one go
+fn remote_names(uuids: &[&String]) -> HashMap<String, String> {
+    if uuids.is_empty() {
+        return HashMap::new();
+    }
+
+    let files: Vec<String> = uuids
+        .iter()
+        .map(|uuid| device::quote(&device::remote_path(&format!("{}.metadata", uuid))))
+        .collect();
+
+    // A marker line before each file, metadata doesn't always end in a newline
+    let cmd = format!(
+        "for f in {}; do echo \"==> $f\"; cat \"$f\"; echo; done",
+        files.join(" ")
+    );
+
+    let output = match device::run(&cmd) {
+        Ok(output) => output,
+        Err(why) => {
+            log::warn!("Couldn't read names of new documents: {}", why);
+            return HashMap::new();
+        }
+    };
+
+    let mut names = HashMap::new();
+
+    for section in output.split("==> ").skip(1) {
+        let Some((file, json)) = section.split_once('\n') else {
+            continue;
+        };
+
+        let uuid = document_of(file.rsplit('/').next().unwrap_or_default());
+        let name = serde_json::from_str::<serde_json::Value>(json.trim())
+            .ok()
+            .and_then(|value| value["visibleName"].as_str().map(String::from));
+
+        if let Some(name) = name {
+            names.insert(String::from(uuid), name);
+        }
+    }
+
+    names
+}
+
+// Ask rsync what a sync would change. Names of new documents are fetched from the
+// tablet, everything else is named later from the library with `resolve_names`.
+pub fn plan_sync() -> Result<SyncPlan, Error> {
+    let documents = parse_listing(&dry_run_sync()?);
+
+    let new: Vec<&String> = documents
+        .iter()
+        .filter(|(_, listing)| listing.new_metadata)
+        .map(|(uuid, _)| uuid)
+        .collect();
+
+    let names = remote_names(&new);
+
+    let mut changes: Vec<Change> = documents
+        .iter()
+        .map(|(uuid, listing)| {
+            let kind = if listing.new_metadata {
+                ChangeKind::New
+            } else if listing.newer_locally {
+                ChangeKind::Conflict
+            } else {
+                ChangeKind::Modified
+            };
+
+            Change {
+                kind,
+                uuid: uuid.clone(),
+                name: names.get(uuid).cloned().unwrap_or_else(|| uuid.clone()),
+                files: listing.files,
+                bytes: listing.bytes,
+            }
+        })
+        .collect();
+
+    changes.sort_by(|a, b| a.kind.cmp(&b.kind).then_with(|| a.name.cmp(&b.name)));
+
+    Ok(SyncPlan { changes })
+}
+
+impl SyncPlan {
+    // Swap uuids for the names the library knows the documents by
+    pub fn resolve_names(&mut self, db: &Connection) -> Result<(), Error> {
+        let mut stmt = db.prepare("SELECT name FROM objects WHERE uuid = ?")?;
+
+        for change in self.changes.iter_mut() {
+            if let Some(name) = stmt
+                .query_row([&change.uuid], |row| row.get::<_, String>(0))
+                .optional()?
+            {
+                change.name = name;
+            }
+        }
+
+        self.changes
+            .sort_by(|a, b| a.kind.cmp(&b.kind).then_with(|| a.name.cmp(&b.name)));
+
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.changes.iter().map(|change| change.bytes).sum()
+    }
+
+    // e.g. "3.4 MB to download, 2 new on tablet, 1 changed on tablet"
+    pub fn summary(&self) -> String {
+        let mut parts = vec![format!("{} to download", format_size(self.bytes()))];
+
+        for kind in [ChangeKind::New, ChangeKind::Modified, ChangeKind::Conflict] {
+            let count = self
+                .changes
+                .iter()
+                .filter(|change| change.kind == kind)
+                .count();
+
+            if count > 0 {
+                parts.push(format!("{} {}", count, kind.get_text()));
+            }
+        }
+
+        parts.join(", ")
+    }
+}
+
+impl Change {
+    pub fn get_text(&self) -> String {
+        format!(
+            "{} {} ({}, {} file(s), {})",
+            self.kind.get_symbol(),
+            self.name,
+            self.kind.get_text(),
+            self.files,
+            format_size(self.bytes)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NEW: &str = "0d8a3a4e-new";
+    const OLD: &str = "7f1c9b2d-old";
+
+    #[test]
+    fn document_of_paths() {
+        assert_eq!(document_of("abc.metadata"), "abc");
+        assert_eq!(document_of("abc/1.rm"), "abc");
+        assert_eq!(document_of("abc.thumbnails/1.png"), "abc");
+    }
+
+    #[test]
+    fn groups_files_by_document() {
+        let output = [
+            format!("cd+++++++++|4096|2024/01/31-13:45:00|{}/", NEW),
+            format!(">f+++++++++|   300|2024/01/31-13:45:00|{}.metadata", NEW),
+            format!(">f+++++++++|  1200|2024/01/31-13:45:00|{}/a.rm", NEW),
+            format!(">f+++++++++|    34|2024/01/31-13:45:00|{}.content", NEW),
+            format!(">f.st......|    50|2024/01/31-13:45:00|{}/b.rm", OLD),
+        ]
+        .join("\n");
+
+        let documents = parse_listing(&output);
+
+        assert_eq!(documents.len(), 2);
+
+        let new = &documents[NEW];
+        assert!(new.new_metadata);
+        assert_eq!((new.files, new.bytes), (3, 1534));
+
+        let old = &documents[OLD];
+        assert!(!old.new_metadata);
+        assert!(!old.newer_locally);
+        assert_eq!((old.files, old.bytes), (1, 50));
+    }
+
+    #[test]
+    fn new_page_of_known_document_is_not_new() {
+        let output = format!(">f+++++++++|10|2024/01/31-13:45:00|{}/c.rm", OLD);
+
+        assert!(!parse_listing(&output)[OLD].new_metadata);
+    }
+
+    #[test]
+    fn skips_everything_not_received() {
+        let output = [
+            String::from("sending incremental file list"),
+            format!(".d..t......|4096|2024/01/31-13:45:00|{}/", OLD),
+            format!(".f...p.....|10|2024/01/31-13:45:00|{}.metadata", OLD),
+            format!("*deleting  |0|2024/01/31-13:45:00|{}/gone.rm", OLD),
+            String::from(">f+++++++++|10|2024/01/31-13:45:00|"),
+            String::new(),
+        ]
+        .join("\n");
+
+        assert!(parse_listing(&output).is_empty());
+    }
+
+    #[test]
+    fn unreadable_size_counts_as_zero() {
+        let output = format!(">f.st......|?|2024/01/31-13:45:00|{}/b.rm", OLD);
+        let documents = parse_listing(&output);
+
+        assert_eq!((documents[OLD].files, documents[OLD].bytes), (1, 0));
+    }
+}
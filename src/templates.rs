@@ -0,0 +1,320 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Custom page templates. The tablet lists templates in templates.json next to the image
+// files. Templates installed through noteworthy are also kept in a registry in the state
+// directory so they can be put back after a firmware update wipes them.
+
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config, device,
+    intern_error::{Error, ErrorContext, ResultExt},
+};
+
+// Icon the tablet uses for plain lined templates
+pub const DEFAULT_ICON: &str = "e9fe";
+
+const INDEX: &str = "templates.json";
+const BACKUP: &str = "templates.json.noteworthy-backup";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Template {
+    pub name: String,
+    pub filename: String,
+    #[serde(default)]
+    pub icon_code: String,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    // Fields we don't touch ("landscape" and whatever newer firmware adds) are kept as is
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+// Registry entry, the template plus the image formats that were installed for it
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UserTemplate {
+    pub template: Template,
+    pub extensions: Vec<String>,
+}
+
+// templates.json as found on the tablet, anything besides the list is written back untouched
+struct Index {
+    value: serde_json::Value,
+    templates: Vec<Template>,
+}
+
+fn remote_file(name: &str) -> String {
+    format!(
+        "{}/{}",
+        config::SETTINGS.templates_path.trim_end_matches('/'),
+        name
+    )
+}
+
+fn registry_dir() -> std::path::PathBuf {
+    config::SETTINGS.state_dir.join("templates")
+}
+
+// Turn what the user typed ("e9fe", "\ue9fe", "0xe9fe") into the private use character
+// the tablet's icon font expects. Anything else is taken literally.
+pub fn icon_code(input: &str) -> String {
+    let hex = input
+        .trim()
+        .trim_start_matches("\\u")
+        .trim_start_matches("0x");
+
+    u32::from_str_radix(hex, 16)
+        .ok()
+        .and_then(char::from_u32)
+        .map(String::from)
+        .unwrap_or_else(|| String::from(input.trim()))
+}
+
+fn read_index() -> Result<Index, Error> {
+    let path = remote_file(INDEX);
+    let value: serde_json::Value =
+        serde_json::from_str(&device::run(&format!("cat {}", device::quote(&path)))?)
+            .context(ErrorContext::File(path))?;
+
+    let templates = serde_json::from_value(value["templates"].clone())?;
+
+    Ok(Index { value, templates })
+}
+
+// Back the current index up on the tablet and locally, then swap the new one in with a
+// rename so the tablet never sees a half written file
+fn write_index(index: Index) -> Result<(), Error> {
+    let mut value = index.value;
+    value["templates"] = serde_json::to_value(&index.templates)?;
+
+    let path = remote_file(INDEX);
+    let tmp = format!("{}.tmp", path);
+
+    device::run(&format!(
+        "cp {} {}",
+        device::quote(&path),
+        device::quote(&remote_file(BACKUP))
+    ))?;
+
+    let local_backup = registry_dir().join(BACKUP);
+    fs::create_dir_all(registry_dir()).context(ErrorContext::file(&registry_dir()))?;
+    device::download(&path, &local_backup, &mut |_| Ok(()))?;
+
+    device::upload_from(
+        &mut serde_json::to_string_pretty(&value)?.as_bytes(),
+        &tmp,
+        &mut |_| Ok(()),
+    )?;
+
+    device::run(&format!(
+        "mv {} {}",
+        device::quote(&tmp),
+        device::quote(&path)
+    ))?;
+
+    log::info!(
+        "Wrote {} with {} templates, backup in {}",
+        path,
+        index.templates.len(),
+        local_backup.display()
+    );
+
+    Ok(())
+}
+
+// Templates added through noteworthy, in the order they were installed
+pub fn user_templates() -> Vec<UserTemplate> {
+    let path = registry_dir().join("registry.json");
+
+    match fs::read_to_string(&path) {
+        Err(_) => Vec::new(),
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|why| {
+            log::warn!(
+                "Ignoring unreadable template registry {}: {}",
+                path.display(),
+                why
+            );
+            Vec::new()
+        }),
+    }
+}
+
+fn save_user_templates(templates: &[UserTemplate]) -> Result<(), Error> {
+    let path = registry_dir().join("registry.json");
+
+    fs::create_dir_all(registry_dir()).context(ErrorContext::file(&registry_dir()))?;
+    fs::write(&path, serde_json::to_string_pretty(templates)?).context(ErrorContext::file(&path))
+}
+
+pub fn installed_templates() -> Result<Vec<Template>, Error> {
+    Ok(read_index()?.templates)
+}
+
+// Copy the image files of a registered template to the tablet
+fn upload_files(user: &UserTemplate) -> Result<(), Error> {
+    for ext in &user.extensions {
+        let file = format!("{}.{}", user.template.filename, ext);
+
+        device::upload(
+            &registry_dir().join(&file),
+            &remote_file(&file),
+            &mut |_| Ok(()),
+        )?;
+    }
+
+    Ok(())
+}
+
+// Add a PNG or SVG as a template, replacing one installed earlier under the same file
+// name. A stock template with that name is refused, overwriting its image would lose it.
+pub fn install(
+    source: &Path,
+    name: &str,
+    icon: &str,
+    categories: Vec<String>,
+) -> Result<Template, Error> {
+    let ext = source
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .filter(|ext| ext == "png" || ext == "svg")
+        .ok_or_else(|| Error::UnsupportedFileError(source.to_string_lossy().to_string()))?;
+
+    let filename = source
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let mut registry = user_templates();
+    let mut index = read_index()?;
+
+    let mut user = match registry
+        .iter()
+        .position(|user| user.template.filename == filename)
+    {
+        Some(idx) => registry.remove(idx),
+        None if index.templates.iter().any(|t| t.filename == filename) => {
+            return Err(Error::TemplateError(format!(
+                "\"{}\" is the file name of a stock template, rename the image first",
+                filename
+            )));
+        }
+        None => UserTemplate {
+            template: Template {
+                name: String::new(),
+                filename: filename.clone(),
+                icon_code: String::new(),
+                categories: Vec::new(),
+                extra: serde_json::Map::new(),
+            },
+            extensions: Vec::new(),
+        },
+    };
+
+    user.template.name = String::from(name);
+    user.template.icon_code = icon_code(icon);
+    user.template.categories = categories;
+
+    if !user.extensions.contains(&ext) {
+        user.extensions.push(ext.clone());
+    }
+
+    // Keep our own copy of the image for re-applying later
+    let copy = registry_dir().join(format!("{}.{}", filename, ext));
+    fs::create_dir_all(registry_dir()).context(ErrorContext::file(&registry_dir()))?;
+    fs::copy(source, &copy).context(ErrorContext::file(source))?;
+
+    log::info!("Installing template {} from {}", name, source.display());
+
+    upload_files(&user)?;
+
+    index.templates.retain(|t| t.filename != filename);
+    index.templates.push(user.template.clone());
+    write_index(index)?;
+
+    registry.push(user.clone());
+    save_user_templates(&registry)?;
+
+    Ok(user.template)
+}
+
+// Remove a template added through noteworthy, the stock ones are left alone
+pub fn remove(filename: &str) -> Result<(), Error> {
+    let mut registry = user_templates();
+
+    let Some(idx) = registry
+        .iter()
+        .position(|user| user.template.filename == filename)
+    else {
+        return Err(Error::UnsupportedFileError(String::from(filename)));
+    };
+
+    let user = registry.remove(idx);
+
+    log::info!("Removing template {}", user.template.name);
+
+    let mut index = read_index()?;
+    index.templates.retain(|t| t.filename != filename);
+    write_index(index)?;
+
+    for ext in &user.extensions {
+        device::run(&format!(
+            "rm -f {}",
+            device::quote(&remote_file(&format!("{}.{}", filename, ext)))
+        ))?;
+    }
+
+    for ext in &user.extensions {
+        let _ = fs::remove_file(registry_dir().join(format!("{}.{}", filename, ext)));
+    }
+
+    save_user_templates(&registry)
+}
+
+// Put every registered template back, returns how many had gone missing from the index
+pub fn reapply() -> Result<usize, Error> {
+    let registry = user_templates();
+    let mut index = read_index()?;
+    let mut missing = 0;
+
+    for user in &registry {
+        upload_files(user)?;
+
+        if !index
+            .templates
+            .iter()
+            .any(|t| t.filename == user.template.filename)
+        {
+            missing += 1;
+        }
+
+        index
+            .templates
+            .retain(|t| t.filename != user.template.filename);
+        index.templates.push(user.template.clone());
+    }
+
+    log::info!(
+        "Re-applied {} templates, {} were missing",
+        registry.len(),
+        missing
+    );
+
+    if !registry.is_empty() {
+        write_index(index)?;
+    }
+
+    Ok(missing)
+}
@@ -0,0 +1,1032 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use futures::channel::mpsc::{self, UnboundedSender};
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Layout, Rect},
+    style::{Modifier, Style},
+    symbols,
+    widgets::{Block, BorderType, Borders, LineGauge, Paragraph},
+    Frame,
+};
+use uuid::Uuid;
+
+use crate::{
+    config, device, epub,
+    fs_interface::{edit_metadata, new_metadata, MetadataType, TRASH},
+    inbox,
+    intern_error::{Error, ErrorContext, ResultExt},
+    jobs::{AppEvent, MAX_ATTEMPTS},
+    local,
+    notification::{NotificationType, NotificationWidget},
+    ui::CursorDirection,
+};
+
+pub type TransferId = usize;
+
+// Values of the control flag shared between the manager and a worker
+const CONTROL_RUN: u8 = 0;
+const CONTROL_PAUSE: u8 = 1;
+const CONTROL_CANCEL: u8 = 2;
+
+// Minimum time between progress reports from a worker
+const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+// Rows of jobs shown when the panel is expanded
+const PANEL_ROWS: usize = 8;
+
+#[derive(Clone, Debug)]
+pub enum TransferKind {
+    // Local file into a collection on the tablet. With `remove_source` the local
+    // file goes to the trash afterwards, making it a move. Files picked up from a
+    // watched inbox are filed into its sent/ directory instead.
+    Upload {
+        source: PathBuf,
+        parent: String,
+        remove_source: bool,
+        from_inbox: bool,
+    },
+    // Original payload of a document into a local directory, `remove_source` moves
+    // the document to the tablet's trash afterwards
+    Download {
+        uuid: String,
+        name: String,
+        file_type: String,
+        dest: PathBuf,
+        remove_source: bool,
+    },
+    // Document rendered to PDF by the tablet into a local directory
+    Export {
+        uuid: String,
+        name: String,
+        dest: PathBuf,
+        remove_source: bool,
+    },
+    // Empty collection on the tablet, queued ahead of the uploads that go into it
+    NewCollection {
+        uuid: String,
+        name: String,
+        parent: String,
+    },
+    // Files of an existing object edited in the local mirror, written back to the tablet,
+    // and files it no longer has, removed from the tablet
+    Push {
+        uuid: String,
+        name: String,
+        files: Vec<String>,
+        removed: Vec<String>,
+    },
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum TransferState {
+    Queued,
+    Running,
+    Paused,
+    // Told to stop, waiting for the worker to notice
+    Cancelling,
+    Done,
+    Failed(String),
+    Cancelled,
+}
+
+pub enum TransferEvent {
+    Progress {
+        bytes_done: u64,
+        bytes_total: u64,
+        files_done: usize,
+        files_total: usize,
+    },
+    Finished(Result<(), Error>),
+}
+
+// What the main loop needs to know once a transfer is over
+pub struct TransferFinished {
+    pub notification: NotificationWidget,
+    pub changed_remote: bool,
+}
+
+pub struct TransferJob {
+    id: TransferId,
+    kind: TransferKind,
+    state: TransferState,
+    bytes_done: u64,
+    bytes_total: u64,
+    files_done: usize,
+    files_total: usize,
+    attempt: u32,
+    control: Arc<AtomicU8>,
+}
+
+// Queue of file transfers to and from the tablet, run one at a time
+pub struct TransferManager {
+    jobs: Vec<TransferJob>,
+    sender: UnboundedSender<AppEvent>,
+    next_id: TransferId,
+    cursor_idx: usize,
+    offset_pos: usize,
+    restart_pending: bool,
+    pub expanded: bool,
+    pub focused: bool,
+}
+
+// Handed to a worker so it can report progress and honour pause/cancel requests
+struct Progress {
+    id: TransferId,
+    sender: UnboundedSender<AppEvent>,
+    control: Arc<AtomicU8>,
+    bytes_done: u64,
+    bytes_total: u64,
+    files_done: usize,
+    files_total: usize,
+    last_report: Instant,
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+// Pick a path in `dir` that doesn't exist yet, appending " (n)" to the stem if needed
+pub fn unique_path(dir: &Path, stem: &str, extension: &str) -> PathBuf {
+    unique_path_where(dir, stem, extension, |path| path.exists())
+}
+
+// Same as unique_path, with `taken` deciding which paths are spoken for
+fn unique_path_where(
+    dir: &Path,
+    stem: &str,
+    extension: &str,
+    taken: impl Fn(&Path) -> bool,
+) -> PathBuf {
+    let file_name = |suffix: String| {
+        if extension.is_empty() {
+            format!("{}{}", stem, suffix)
+        } else {
+            format!("{}{}.{}", stem, suffix, extension)
+        }
+    };
+
+    let mut path = dir.join(file_name(String::new()));
+    let mut n = 1;
+
+    while taken(&path) {
+        path = dir.join(file_name(format!(" ({})", n)));
+        n += 1;
+    }
+
+    path
+}
+
+impl TransferKind {
+    pub fn get_name(&self) -> String {
+        match self {
+            Self::Upload { source, .. } => source
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            Self::Download { name, .. }
+            | Self::NewCollection { name, .. }
+            | Self::Push { name, .. } => name.clone(),
+            Self::Export { name, .. } => format!("{}.pdf", name),
+        }
+    }
+
+    fn get_verb(&self) -> &str {
+        match self {
+            Self::Upload { .. } => "Upload",
+            Self::Download { .. } => "Download",
+            Self::Export { .. } => "Export",
+            Self::NewCollection { .. } => "Creation",
+            Self::Push { .. } => "Update",
+        }
+    }
+
+    fn get_symbol(&self) -> &str {
+        match self {
+            Self::Upload { .. } | Self::NewCollection { .. } | Self::Push { .. } => "↑",
+            Self::Download { .. } | Self::Export { .. } => "↓",
+        }
+    }
+}
+
+impl TransferState {
+    fn get_text(&self) -> &str {
+        match self {
+            Self::Queued => "queued",
+            Self::Running => "running",
+            Self::Paused => "paused",
+            Self::Cancelling => "cancelling",
+            Self::Done => "done",
+            Self::Failed(_) => "failed",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self, Self::Done | Self::Failed(_) | Self::Cancelled)
+    }
+}
+
+impl Progress {
+    fn report(&mut self, force: bool) {
+        if !force && self.last_report.elapsed() < REPORT_INTERVAL {
+            return;
+        }
+
+        self.last_report = Instant::now();
+
+        let _ = self.sender.unbounded_send(AppEvent::Transfer(
+            self.id,
+            TransferEvent::Progress {
+                bytes_done: self.bytes_done,
+                bytes_total: self.bytes_total,
+                files_done: self.files_done,
+                files_total: self.files_total,
+            },
+        ));
+    }
+
+    // Block while paused, bail out if cancelled
+    fn checkpoint(&self) -> Result<(), Error> {
+        loop {
+            match self.control.load(Ordering::Relaxed) {
+                CONTROL_CANCEL => return Err(Error::TransferCancelled),
+                CONTROL_PAUSE => thread::sleep(REPORT_INTERVAL),
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn chunk(&mut self, bytes: usize) -> Result<(), Error> {
+        self.bytes_done += bytes as u64;
+        self.report(false);
+        self.checkpoint()
+    }
+
+    fn file_done(&mut self) {
+        self.files_done += 1;
+        self.report(true);
+    }
+}
+
+impl TransferManager {
+    pub fn new(sender: UnboundedSender<AppEvent>) -> Self {
+        Self {
+            jobs: Vec::new(),
+            sender,
+            next_id: 0,
+            cursor_idx: 0,
+            offset_pos: 0,
+            restart_pending: false,
+            expanded: false,
+            focused: false,
+        }
+    }
+
+    pub fn queue(&mut self, kind: TransferKind) {
+        log::info!("Queued {} of {}", kind.get_verb(), kind.get_name());
+
+        self.jobs.push(TransferJob {
+            id: self.next_id,
+            kind,
+            state: TransferState::Queued,
+            bytes_done: 0,
+            bytes_total: 0,
+            files_done: 0,
+            files_total: 1,
+            attempt: 1,
+            control: Arc::new(AtomicU8::new(CONTROL_RUN)),
+        });
+
+        self.next_id += 1;
+
+        self.pump();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    // Pick a destination for a download like unique_path does, also skipping the ones
+    // queued downloads will write to once they get their turn
+    pub fn unique_dest(&self, dir: &Path, stem: &str, extension: &str) -> PathBuf {
+        unique_path_where(dir, stem, extension, |path| {
+            path.exists()
+                || self.jobs.iter().any(|job| {
+                    !job.state.is_finished()
+                        && matches!(
+                            &job.kind,
+                            TransferKind::Download { dest, .. }
+                                | TransferKind::Export { dest, .. } if dest == path
+                        )
+                })
+        })
+    }
+
+    fn count(&self, state: &TransferState) -> usize {
+        self.jobs
+            .iter()
+            .filter(|job| std::mem::discriminant(&job.state) == std::mem::discriminant(state))
+            .count()
+    }
+
+    // Start the next queued job if nothing is running, restart the tablet UI once
+    // the queue has drained after an upload. Paused and cancelling jobs still hold
+    // their worker, so they count as running.
+    fn pump(&mut self) {
+        if self.jobs.iter().any(|job| {
+            matches!(
+                job.state,
+                TransferState::Running | TransferState::Paused | TransferState::Cancelling
+            )
+        }) {
+            return;
+        }
+
+        match self
+            .jobs
+            .iter_mut()
+            .find(|job| job.state == TransferState::Queued)
+        {
+            Some(job) => {
+                log::info!(
+                    "Starting {} of {} (attempt {})",
+                    job.kind.get_verb(),
+                    job.kind.get_name(),
+                    job.attempt
+                );
+
+                job.state = TransferState::Running;
+
+                let progress = Progress {
+                    id: job.id,
+                    sender: self.sender.clone(),
+                    control: Arc::clone(&job.control),
+                    bytes_done: 0,
+                    bytes_total: 0,
+                    files_done: 0,
+                    files_total: job.files_total,
+                    last_report: Instant::now(),
+                };
+
+                let kind = job.kind.clone();
+
+                thread::spawn(move || run_transfer(kind, progress));
+            }
+            None => {
+                if self.restart_pending {
+                    self.restart_pending = false;
+
+                    let sender = self.sender.clone();
+
+                    thread::spawn(move || {
+                        if let Err(why) = device::restart_ui() {
+                            let _ = sender.unbounded_send(AppEvent::Notify(
+                                NotificationWidget::default()
+                                    .text(&why.to_string())
+                                    .notif_type(NotificationType::ErrorMid),
+                            ));
+                        }
+                    });
+                }
+            }
+        }
+    }
+
+    pub fn update(&mut self, id: TransferId, event: TransferEvent) -> Option<TransferFinished> {
+        let job = self.jobs.iter_mut().find(|job| job.id == id)?;
+
+        let finished = match event {
+            TransferEvent::Progress {
+                bytes_done,
+                bytes_total,
+                files_done,
+                files_total,
+            } => {
+                job.bytes_done = bytes_done;
+                job.bytes_total = bytes_total;
+                job.files_done = files_done;
+                job.files_total = files_total;
+                None
+            }
+            TransferEvent::Finished(result) => {
+                let name = job.kind.get_name();
+                let verb = job.kind.get_verb();
+
+                let notification = match result {
+                    // Dropped connections are put back in the queue a few times before giving up
+                    Err(why)
+                        if why.is_transient()
+                            && job.attempt < MAX_ATTEMPTS
+                            && job.state != TransferState::Cancelling =>
+                    {
+                        job.attempt += 1;
+                        job.state = TransferState::Queued;
+                        job.bytes_done = 0;
+                        job.files_done = 0;
+                        job.control = Arc::new(AtomicU8::new(CONTROL_RUN));
+
+                        NotificationWidget::default()
+                            .text(&format!(
+                                "{} of {} failed, retrying ({}/{})\n{}",
+                                verb, name, job.attempt, MAX_ATTEMPTS, why
+                            ))
+                            .notif_type(NotificationType::ErrorLow)
+                    }
+                    Ok(_) => {
+                        job.state = TransferState::Done;
+                        job.bytes_done = job.bytes_total;
+
+                        NotificationWidget::default()
+                            .text(&format!("{} of {} complete", verb, name))
+                            .notif_type(NotificationType::Success)
+                    }
+                    Err(why)
+                        if matches!(why.root(), Error::TransferCancelled)
+                            || job.state == TransferState::Cancelling =>
+                    {
+                        job.state = TransferState::Cancelled;
+
+                        NotificationWidget::default()
+                            .text(&format!("{} of {} cancelled", verb, name))
+                    }
+                    Err(why) => {
+                        job.state = TransferState::Failed(why.to_string());
+
+                        NotificationWidget::default()
+                            .text(&format!("{} of {} failed\n{}", verb, name, why))
+                            .notif_type(why.notification_type())
+                    }
+                };
+
+                log::info!(
+                    "{} of {} finished as {} after {} bytes",
+                    verb,
+                    name,
+                    job.state.get_text(),
+                    job.bytes_done
+                );
+
+                let changed_remote = matches!(
+                    job.kind,
+                    TransferKind::Upload { .. }
+                        | TransferKind::NewCollection { .. }
+                        | TransferKind::Push { .. }
+                        | TransferKind::Download {
+                            remove_source: true,
+                            ..
+                        }
+                        | TransferKind::Export {
+                            remove_source: true,
+                            ..
+                        }
+                ) && job.state == TransferState::Done;
+
+                self.restart_pending |= changed_remote;
+
+                Some(TransferFinished {
+                    notification,
+                    changed_remote,
+                })
+            }
+        };
+
+        self.pump();
+
+        finished
+    }
+
+    pub fn cursor_move(&mut self, direction: CursorDirection) {
+        let delta: isize = match direction {
+            CursorDirection::Down => 1,
+            CursorDirection::Up => -1,
+            CursorDirection::PgDn => PANEL_ROWS as isize,
+            CursorDirection::PgUp => -(PANEL_ROWS as isize),
+        };
+
+        self.cursor_idx = self
+            .cursor_idx
+            .saturating_add_signed(delta)
+            .min(self.jobs.len().saturating_sub(1));
+
+        if self.cursor_idx < self.offset_pos {
+            self.offset_pos = self.cursor_idx;
+        } else if self.cursor_idx >= self.offset_pos + PANEL_ROWS {
+            self.offset_pos = self.cursor_idx + 1 - PANEL_ROWS;
+        }
+    }
+
+    pub fn cancel_selected(&mut self) {
+        if let Some(job) = self.jobs.get_mut(self.cursor_idx) {
+            match job.state {
+                TransferState::Queued => job.state = TransferState::Cancelled,
+                TransferState::Running | TransferState::Paused => {
+                    log::info!("Cancelling {}", job.kind.get_name());
+                    // The worker notices on its next chunk and reports back as cancelled
+                    job.control.store(CONTROL_CANCEL, Ordering::Relaxed);
+                    job.state = TransferState::Cancelling;
+                }
+                _ => (),
+            }
+        }
+
+        self.pump();
+    }
+
+    pub fn toggle_pause_selected(&mut self) {
+        if let Some(job) = self.jobs.get_mut(self.cursor_idx) {
+            match job.state {
+                TransferState::Running => {
+                    log::info!("Pausing {}", job.kind.get_name());
+                    job.control.store(CONTROL_PAUSE, Ordering::Relaxed);
+                    job.state = TransferState::Paused;
+                }
+                TransferState::Paused => {
+                    log::info!("Resuming {}", job.kind.get_name());
+                    job.control.store(CONTROL_RUN, Ordering::Relaxed);
+                    job.state = TransferState::Running;
+                }
+                _ => (),
+            }
+        }
+
+        self.pump();
+    }
+
+    pub fn retry_selected(&mut self) {
+        if let Some(job) = self.jobs.get_mut(self.cursor_idx) {
+            if matches!(
+                job.state,
+                TransferState::Failed(_) | TransferState::Cancelled
+            ) {
+                job.state = TransferState::Queued;
+                job.attempt = 1;
+                job.bytes_done = 0;
+                job.files_done = 0;
+                job.control = Arc::new(AtomicU8::new(CONTROL_RUN));
+            }
+        }
+
+        self.pump();
+    }
+
+    pub fn retry_failed(&mut self) {
+        for job in self.jobs.iter_mut() {
+            if let TransferState::Failed(_) = job.state {
+                job.state = TransferState::Queued;
+                job.attempt = 1;
+                job.bytes_done = 0;
+                job.files_done = 0;
+                job.control = Arc::new(AtomicU8::new(CONTROL_RUN));
+            }
+        }
+
+        self.pump();
+    }
+
+    pub fn clear_finished(&mut self) {
+        self.jobs.retain(|job| !job.state.is_finished());
+        self.cursor_idx = self.cursor_idx.min(self.jobs.len().saturating_sub(1));
+        self.offset_pos = self.offset_pos.min(self.cursor_idx);
+    }
+
+    // Rows the panel wants, zero when there's nothing to show
+    pub fn height(&self) -> u16 {
+        if self.expanded {
+            (self.jobs.len().clamp(1, PANEL_ROWS) + 2) as u16
+        } else if self.jobs.is_empty() {
+            0
+        } else {
+            3
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+
+        for state in [
+            TransferState::Running,
+            TransferState::Paused,
+            TransferState::Cancelling,
+            TransferState::Queued,
+            TransferState::Failed(String::new()),
+            TransferState::Done,
+        ] {
+            let count = self.count(&state);
+
+            if count > 0 {
+                parts.push(format!("{} {}", count, state.get_text()));
+            }
+        }
+
+        if parts.is_empty() {
+            String::from("No transfers")
+        } else {
+            parts.join(", ")
+        }
+    }
+
+    pub fn render<B: Backend>(&self, f: &mut Frame<B>, area: Rect) {
+        let block = Block::default()
+            .title(if self.expanded {
+                format!("Jobs ({})", self.summary())
+            } else {
+                String::from("Jobs")
+            })
+            .borders(Borders::ALL)
+            .border_type(if self.focused {
+                BorderType::Thick
+            } else {
+                BorderType::Double
+            })
+            .style(
+                Style::default()
+                    .fg(config::THEME.foreground)
+                    .bg(config::THEME.background),
+            );
+
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        if !self.expanded || self.jobs.is_empty() {
+            f.render_widget(Paragraph::new(self.summary()), inner);
+            return;
+        }
+
+        let rows = Layout::default()
+            .direction(tui::layout::Direction::Vertical)
+            .constraints(vec![Constraint::Length(1); usize::from(inner.height)])
+            .split(inner);
+
+        for (row, (idx, job)) in rows
+            .iter()
+            .zip(self.jobs.iter().enumerate().skip(self.offset_pos))
+        {
+            let ratio = if job.state == TransferState::Done {
+                1.0
+            } else if job.bytes_total == 0 {
+                0.0
+            } else {
+                (job.bytes_done as f64 / job.bytes_total as f64).min(1.0)
+            };
+
+            let size = if job.bytes_total == 0 {
+                format_size(job.bytes_done)
+            } else {
+                format!(
+                    "{}/{}",
+                    format_size(job.bytes_done),
+                    format_size(job.bytes_total)
+                )
+            };
+
+            let mut name = job.kind.get_name();
+            if name.chars().count() > 30 {
+                name = name.chars().take(29).collect::<String>() + "…";
+            }
+
+            let label = format!(
+                "{} {:<30} {:>21} {:>3}/{:<3} {:<9}",
+                job.kind.get_symbol(),
+                name,
+                size,
+                job.files_done,
+                job.files_total,
+                job.state.get_text()
+            );
+
+            let mut style = match job.state {
+                TransferState::Failed(_) => Style::default().fg(config::THEME.alert),
+                TransferState::Done => Style::default().fg(config::THEME.success),
+                TransferState::Paused | TransferState::Cancelling | TransferState::Cancelled => {
+                    Style::default().fg(config::THEME.highlight)
+                }
+                _ => Style::default().fg(config::THEME.foreground),
+            };
+
+            if self.focused && idx == self.cursor_idx {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+
+            f.render_widget(
+                LineGauge::default()
+                    .ratio(ratio)
+                    .label(label)
+                    .style(style)
+                    .gauge_style(style)
+                    .line_set(symbols::line::THICK),
+                *row,
+            );
+        }
+    }
+}
+
+fn run_transfer(kind: TransferKind, mut progress: Progress) {
+    let result = perform(&kind, &mut progress);
+
+    if result.is_ok() && progress.files_done < progress.files_total {
+        progress.file_done();
+    }
+
+    let _ = progress.sender.clone().unbounded_send(AppEvent::Transfer(
+        progress.id,
+        TransferEvent::Finished(result),
+    ));
+}
+
+// Run a transfer to the end on the calling thread, for use without the interface
+pub fn run_blocking(kind: &TransferKind) -> Result<(), Error> {
+    let (sender, _) = mpsc::unbounded();
+
+    let mut progress = Progress {
+        id: 0,
+        sender,
+        control: Arc::new(AtomicU8::new(CONTROL_RUN)),
+        bytes_done: 0,
+        bytes_total: 0,
+        files_done: 0,
+        files_total: 1,
+        last_report: Instant::now(),
+    };
+
+    perform(kind, &mut progress)
+}
+
+fn perform(kind: &TransferKind, progress: &mut Progress) -> Result<(), Error> {
+    match kind {
+        TransferKind::Upload {
+            source,
+            parent,
+            remove_source,
+            from_inbox,
+        } => upload(source, parent, progress)
+            .and_then(|uuid| {
+                if *from_inbox {
+                    inbox::mark_sent(source, &uuid)?;
+                } else if *remove_source {
+                    local::trash(source)?;
+                }
+                Ok(())
+            })
+            .context(ErrorContext::file(source)),
+        TransferKind::Download {
+            uuid,
+            file_type,
+            dest,
+            remove_source,
+            ..
+        } => {
+            let remote = device::remote_path(&format!("{}.{}", uuid, file_type));
+
+            device::file_size(&remote)
+                .and_then(|size| {
+                    progress.bytes_total = size;
+                    progress.report(true);
+                    device::download(&remote, dest, &mut |n| progress.chunk(n))
+                })
+                .and_then(|_| trash_remote(uuid, *remove_source))
+                .context(ErrorContext::document(uuid))
+        }
+        TransferKind::Export {
+            uuid,
+            dest,
+            remove_source,
+            ..
+        } => device::render_pdf(uuid, dest, &mut |n| progress.chunk(n))
+            .and_then(|_| trash_remote(uuid, *remove_source))
+            .context(ErrorContext::document(uuid)),
+        TransferKind::NewCollection { uuid, name, parent } => {
+            new_collection(uuid, name, parent).context(ErrorContext::document(uuid))
+        }
+        TransferKind::Push {
+            uuid,
+            files,
+            removed,
+            ..
+        } => push(files, removed, progress).context(ErrorContext::document(uuid)),
+    }
+}
+
+// Payload type a local file would be uploaded as, None if the tablet can't open it.
+// Markdown and plain text are converted to EPUB on the way.
+pub fn upload_file_type(source: &Path) -> Option<String> {
+    if epub::is_convertible(source) {
+        return Some(String::from("epub"));
+    }
+
+    source
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .filter(|ext| ext == "pdf" || ext == "epub")
+}
+
+// Push a PDF or EPUB to the tablet as a new document, mirroring its metadata locally.
+// Gives back the uuid of the new document.
+fn upload(source: &Path, parent: &str, progress: &mut Progress) -> Result<String, Error> {
+    let file_type = upload_file_type(source)
+        .ok_or_else(|| Error::UnsupportedFileError(source.to_string_lossy().to_string()))?;
+
+    let book = if epub::is_convertible(source) {
+        Some(epub::convert(source)?)
+    } else {
+        None
+    };
+
+    let name = match &book {
+        Some(book) => book.title.clone(),
+        None => source
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    };
+
+    let uuid = Uuid::new_v4().to_string();
+
+    progress.bytes_total = match &book {
+        Some(book) => book.data.len() as u64,
+        None => fs::metadata(source)?.len(),
+    };
+    progress.report(true);
+
+    let metadata =
+        serde_json::to_string_pretty(&new_metadata(&name, parent, MetadataType::DocumentType))?;
+    let content = serde_json::json!({ "fileType": file_type }).to_string();
+
+    let payload = device::remote_path(&format!("{}.{}", uuid, file_type));
+
+    let result = match &book {
+        Some(book) => device::upload_from(&mut book.data.as_slice(), &payload, &mut |n| {
+            progress.chunk(n)
+        }),
+        None => device::upload(source, &payload, &mut |n| progress.chunk(n)),
+    }
+    .and_then(|_| {
+        device::upload_from(
+            &mut content.as_bytes(),
+            &device::remote_path(&format!("{}.content", uuid)),
+            &mut |_| Ok(()),
+        )
+    })
+    .and_then(|_| {
+        // Metadata goes last so the tablet never sees a document without its payload
+        device::upload_from(
+            &mut metadata.as_bytes(),
+            &device::remote_path(&format!("{}.metadata", uuid)),
+            &mut |_| Ok(()),
+        )
+    });
+
+    if let Err(why) = result {
+        let _ = device::run(&format!(
+            "rm -f {}",
+            device::quote(&device::remote_path(&format!("{}.", uuid))) + "*"
+        ));
+        return Err(why);
+    }
+
+    // Make the document show up in the remote pane before the next sync
+    let mirror = Path::new(&config::SETTINGS.backup_loc);
+    fs::create_dir_all(mirror)?;
+    fs::write(mirror.join(format!("{}.metadata", uuid)), metadata)?;
+    fs::write(mirror.join(format!("{}.content", uuid)), content)?;
+
+    Ok(uuid)
+}
+
+// Create a collection on the tablet under a uuid picked when it was queued, so the
+// uploads queued after it already know where they're going
+fn new_collection(uuid: &str, name: &str, parent: &str) -> Result<(), Error> {
+    let metadata =
+        serde_json::to_string_pretty(&new_metadata(name, parent, MetadataType::CollectionType))?;
+    let content = String::from("{}");
+
+    device::upload_from(
+        &mut content.as_bytes(),
+        &device::remote_path(&format!("{}.content", uuid)),
+        &mut |_| Ok(()),
+    )?;
+    device::upload_from(
+        &mut metadata.as_bytes(),
+        &device::remote_path(&format!("{}.metadata", uuid)),
+        &mut |_| Ok(()),
+    )?;
+
+    let mirror = Path::new(&config::SETTINGS.backup_loc);
+    fs::create_dir_all(mirror)?;
+    fs::write(mirror.join(format!("{}.metadata", uuid)), metadata)?;
+    fs::write(mirror.join(format!("{}.content", uuid)), content)?;
+
+    Ok(())
+}
+
+// Finish a move off the tablet by putting the document in its trash
+fn trash_remote(uuid: &str, remove_source: bool) -> Result<(), Error> {
+    if !remove_source {
+        return Ok(());
+    }
+
+    edit_metadata(uuid, |value| value["parent"] = serde_json::json!(TRASH))?;
+
+    let file = format!("{}.metadata", uuid);
+
+    device::upload(
+        &Path::new(&config::SETTINGS.backup_loc).join(&file),
+        &device::remote_path(&file),
+        &mut |_| Ok(()),
+    )
+}
+
+// Copy files from the local mirror over their counterparts on the tablet, then remove
+// the ones listed in `removed`
+fn push(files: &[String], removed: &[String], progress: &mut Progress) -> Result<(), Error> {
+    let mirror = Path::new(&config::SETTINGS.backup_loc);
+
+    progress.files_total = files.len();
+    progress.bytes_total = files
+        .iter()
+        .filter_map(|file| fs::metadata(mirror.join(file)).ok())
+        .map(|m| m.len())
+        .sum();
+    progress.report(true);
+
+    // Pages can land in directories the tablet doesn't have yet
+    let mut dirs: Vec<String> = files
+        .iter()
+        .filter_map(|file| Path::new(file).parent())
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map(|dir| device::quote(&device::remote_path(&dir.to_string_lossy())))
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+
+    if !dirs.is_empty() {
+        device::run(&format!("mkdir -p {}", dirs.join(" ")))?;
+    }
+
+    for file in files {
+        device::upload(&mirror.join(file), &device::remote_path(file), &mut |n| {
+            progress.chunk(n)
+        })?;
+        progress.file_done();
+    }
+
+    if !removed.is_empty() {
+        let paths: Vec<String> = removed
+            .iter()
+            .map(|file| device::quote(&device::remote_path(file)))
+            .collect();
+
+        device::run(&format!("rm -f {}", paths.join(" ")))?;
+
+        // Directories gone from the mirror along with their files, rmdir leaves any
+        // the tablet still has something in
+        let mut emptied: Vec<String> = removed
+            .iter()
+            .filter_map(|file| Path::new(file).parent())
+            .filter(|dir| !dir.as_os_str().is_empty() && !mirror.join(dir).exists())
+            .map(|dir| device::quote(&device::remote_path(&dir.to_string_lossy())))
+            .collect();
+        emptied.sort();
+        emptied.dedup();
+
+        if !emptied.is_empty() {
+            device::run(&format!("rmdir {} 2>/dev/null; true", emptied.join(" ")))?;
+        }
+    }
+
+    Ok(())
+}
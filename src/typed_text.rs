@@ -0,0 +1,152 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Turn the typed text layer of notebooks into Markdown. Only v6 `.rm` files from
+// 3.x firmware have one, it lives in the root text block of each page.
+
+use std::{fmt::Write, fs, path::Path};
+
+use crate::{
+    config,
+    fs_interface::document_pages,
+    intern_error::{Error, ErrorContext, ResultExt},
+    rm::{self, Paragraph, ParagraphStyle},
+};
+
+pub struct PageText {
+    // 1 based, in reading order
+    pub page: usize,
+    pub paragraphs: Vec<Paragraph>,
+}
+
+// Pages without typed text are left out
+pub fn document_text(uuid: &str) -> Result<Vec<PageText>, Error> {
+    let mirror = Path::new(&config::SETTINGS.backup_loc);
+    let mut pages = Vec::new();
+
+    for (idx, page_id) in document_pages(uuid).iter().enumerate() {
+        let lines = mirror.join(uuid).join(format!("{}.rm", page_id));
+
+        let Ok(data) = fs::read(&lines) else {
+            continue;
+        };
+
+        if !rm::is_v6(&data) {
+            continue;
+        }
+
+        let paragraphs = rm::blocks(&data)
+            .context(ErrorContext::file(&lines))?
+            .iter()
+            .find_map(|block| block.root_text())
+            .unwrap_or_default();
+
+        if paragraphs.iter().any(|p| !p.text().trim().is_empty()) {
+            pages.push(PageText {
+                page: idx + 1,
+                paragraphs,
+            });
+        }
+    }
+
+    Ok(pages)
+}
+
+fn style_prefix(style: ParagraphStyle) -> &'static str {
+    match style {
+        ParagraphStyle::Heading => "# ",
+        ParagraphStyle::Bold => "## ",
+        ParagraphStyle::Bullet => "- ",
+        ParagraphStyle::Bullet2 => "  - ",
+        ParagraphStyle::Checkbox => "- [ ] ",
+        ParagraphStyle::CheckboxChecked => "- [x] ",
+        ParagraphStyle::Basic | ParagraphStyle::Plain => "",
+    }
+}
+
+fn is_list(style: ParagraphStyle) -> bool {
+    matches!(
+        style,
+        ParagraphStyle::Bullet
+            | ParagraphStyle::Bullet2
+            | ParagraphStyle::Checkbox
+            | ParagraphStyle::CheckboxChecked
+    )
+}
+
+// Markers go around the words, whitespace at the edges of a span stays outside so
+// the result is still valid emphasis
+fn inline_markdown(paragraph: &Paragraph) -> String {
+    let mut text = String::new();
+
+    for span in &paragraph.spans {
+        let marker = match (span.bold, span.italic) {
+            (true, true) => "***",
+            (true, false) => "**",
+            (false, true) => "*",
+            (false, false) => "",
+        };
+
+        let inner = span.text.trim();
+
+        if marker.is_empty() || inner.is_empty() {
+            text.push_str(&span.text);
+            continue;
+        }
+
+        let leading = &span.text[..span.text.len() - span.text.trim_start().len()];
+        let trailing = &span.text[span.text.trim_end().len()..];
+
+        let _ = write!(text, "{}{}{}{}{}", leading, marker, inner, marker, trailing);
+    }
+
+    text
+}
+
+// One section per document, pages separated by a rule. List items stay together,
+// everything else is its own block.
+pub fn to_markdown(title: &str, pages: &[PageText]) -> String {
+    let mut markdown = format!("# {}\n", title);
+
+    for (idx, page) in pages.iter().enumerate() {
+        if idx > 0 {
+            markdown.push_str("\n---\n");
+        }
+
+        let mut previous: Option<ParagraphStyle> = None;
+
+        for paragraph in &page.paragraphs {
+            let text = inline_markdown(paragraph);
+
+            if text.trim().is_empty() {
+                continue;
+            }
+
+            let in_list = is_list(paragraph.style) && previous.is_some_and(is_list);
+
+            if !in_list {
+                markdown.push('\n');
+            }
+
+            let _ = writeln!(
+                markdown,
+                "{}{}",
+                style_prefix(paragraph.style),
+                text.trim_end()
+            );
+
+            previous = Some(paragraph.style);
+        }
+    }
+
+    markdown
+}
@@ -11,27 +11,105 @@ You should have received a copy of the GNU General Public License along with Not
 */
 
 pub mod block;
+pub mod check_view;
 pub mod db_block;
 pub mod dir_block;
 pub mod file_item;
+pub mod layout;
+pub mod page_view;
+pub mod preview;
+pub mod prompt;
+pub mod screen_view;
+pub mod status_bar;
+pub mod sync_view;
+pub mod template_view;
 
-use std::sync::Arc;
+use std::{
+    fs,
+    path::PathBuf,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
-use crossterm::event::KeyCode;
+use crossterm::event::{KeyCode, MouseButton, MouseEvent, MouseEventKind};
+use futures::channel::mpsc::UnboundedSender;
 use tui::{
     backend::Backend,
-    layout::{Constraint, Layout},
+    layout::{Constraint, Layout, Rect},
     Frame,
 };
 
-use crate::intern_error;
+use uuid::Uuid;
+
+use crate::{
+    fs_interface::{
+        document_file_type, find_document, set_parent, set_pinned, set_tag, MetadataType, TRASH,
+    },
+    highlights::{document_highlights, to_markdown},
+    inbox::InboxWatcher,
+    integrity::{self, CheckSource, Problem},
+    intern_error::{self, Error, ErrorContext, ResultExt},
+    jobs::{AppEvent, DeviceChange, JobKind},
+    local,
+    notification::{NotificationType, NotificationWidget},
+    pages::{self, MergeOrder},
+    save_data::SaveData,
+    screens::{Screen, ScreenState},
+    sync_plan::SyncPlan,
+    templates::{Template, DEFAULT_ICON},
+    transfer::{
+        unique_path, upload_file_type, TransferEvent, TransferFinished, TransferId, TransferKind,
+        TransferManager,
+    },
+    typed_text::{self, document_text},
+};
+
+use self::{
+    block::FSListBlock,
+    check_view::CheckView,
+    db_block::DBBlock,
+    dir_block::DirBlock,
+    file_item::{FileItem, SortOrder},
+    layout::PaneLayout,
+    page_view::PageView,
+    preview::Preview,
+    prompt::{Prompt, PromptAction, PromptResult},
+    screen_view::ScreenView,
+    status_bar::{StatusBar, STATUS_BAR_HEIGHT},
+    sync_view::SyncView,
+    template_view::TemplateView,
+};
 
-use self::{block::FSListBlock, db_block::DBBlock, dir_block::DirBlock};
+// What a bulk transfer queued and what it had to leave out
+#[derive(Default)]
+struct Tally {
+    queued: usize,
+    skipped: Vec<String>,
+}
 
-#[derive(PartialEq)]
+// What's left of the screen for toasts and overlays once the status bar is drawn
+pub fn overlay_area(screen: Rect) -> Rect {
+    Rect {
+        height: screen.height.saturating_sub(STATUS_BAR_HEIGHT),
+        ..screen
+    }
+}
+
+// Whether a screen position falls inside an area
+pub fn area_contains(area: Rect, column: u16, row: u16) -> bool {
+    column >= area.x && column < area.right() && row >= area.y && row < area.bottom()
+}
+
+// Second click on the same item within this long counts as a double click
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+// Rows moved per notch of the scroll wheel
+const SCROLL_LINES: usize = 3;
+
+#[derive(PartialEq, Clone, Copy)]
 enum FileUIFocus {
     Local,
     Remote,
+    Jobs,
 }
 
 pub enum CursorDirection {
@@ -42,9 +120,59 @@ pub enum CursorDirection {
 }
 
 pub struct FileUI {
+    db: Rc<rusqlite::Connection>,
+    events: UnboundedSender<AppEvent>,
     local: DirBlock,
     remote: DBBlock,
+    transfers: TransferManager,
     focus: FileUIFocus,
+    save_data: SaveData,
+    prompt: Option<Prompt>,
+    // Shown in place of the remote pane while open
+    device_pane: Option<DevicePane>,
+    template_draft: Option<TemplateDraft>,
+    merge_draft: Option<MergeDraft>,
+    clipboard: Option<Clipboard>,
+    status: StatusBar,
+    // Pane, item and time of the last click, for spotting double clicks
+    last_click: Option<(FileUIFocus, usize, Instant)>,
+    // File pane focused last, which the jobs panel hands focus back to the layout for
+    last_pane: FileUIFocus,
+    preview: Preview,
+    inbox: InboxWatcher,
+    // Set once the library has been indexed, the inboxes wait for it to find their collections
+    library_loaded: bool,
+}
+
+// Local files copied or cut, waiting to be pasted into another directory
+struct Clipboard {
+    paths: Vec<PathBuf>,
+    cut: bool,
+}
+
+// Device maintenance views and the page editor, which take the place of the remote
+// pane
+enum DevicePane {
+    Templates(TemplateView),
+    Screens(ScreenView),
+    Pages(PageView),
+    Sync(SyncView),
+    Check(CheckView),
+}
+
+// Answers collected so far while prompting for a template to install
+struct TemplateDraft {
+    source: PathBuf,
+    name: String,
+    icon: String,
+}
+
+// Notebooks being merged and the answers collected so far
+struct MergeDraft {
+    sources: Vec<FileItem>,
+    parent: String,
+    name: String,
+    order: MergeOrder,
 }
 
 const WIDGET_OFFSET: u16 = 3;
@@ -52,41 +180,201 @@ const WIDGET_OFFSET: u16 = 3;
 impl FileUI {
     // Create the layout and then render generated widgets
     pub fn render<B: Backend>(&mut self, f: &mut Frame<B>) -> Result<(), intern_error::Error> {
-        let layout = Layout::default()
-            .direction(tui::layout::Direction::Horizontal)
-            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        let outer = Layout::default()
+            .direction(tui::layout::Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Min(0),
+                    Constraint::Length(self.transfers.height()),
+                    Constraint::Length(STATUS_BAR_HEIGHT),
+                ]
+                .as_ref(),
+            )
             .split(f.size());
 
-        self.local.focused = self.focus == FileUIFocus::Local;
-        self.remote.focused = self.focus == FileUIFocus::Remote;
+        self.update_focus();
+
+        let areas = self
+            .save_data
+            .layout
+            .split(outer[0], self.last_pane == FileUIFocus::Local);
+
+        // Hidden panes keep an empty area so clicks can't land on them
+        match areas.local {
+            Some(area) => f.render_widget(self.local.render(area)?, area),
+            None => self.local.set_render_area(Rect::default()),
+        }
+
+        match (areas.remote, self.device_pane.as_mut()) {
+            (Some(area), Some(DevicePane::Templates(templates))) => {
+                f.render_widget(templates.render(area), area)
+            }
+            (Some(area), Some(DevicePane::Screens(screens))) => {
+                f.render_widget(screens.render(), area)
+            }
+            (Some(area), Some(DevicePane::Pages(pages))) => {
+                f.render_widget(pages.render(area), area)
+            }
+            (Some(area), Some(DevicePane::Sync(sync))) => f.render_widget(sync.render(area), area),
+            (Some(area), Some(DevicePane::Check(check))) => {
+                f.render_widget(check.render(area), area)
+            }
+            (Some(area), None) => f.render_widget(self.remote.render(area)?, area),
+            (None, _) => self.remote.set_render_area(Rect::default()),
+        }
+
+        if let Some(area) = areas.preview {
+            match self.last_pane {
+                FileUIFocus::Remote => self
+                    .preview
+                    .update(self.remote.get_cursor_selection(), true),
+                _ => self
+                    .preview
+                    .update(self.local.get_cursor_selection(), false),
+            }
+
+            f.render_widget(self.preview.render(), area);
+        }
+
+        if outer[1].height > 0 {
+            self.transfers.render(f, outer[1]);
+        }
 
-        f.render_widget(self.local.render(layout[0])?, layout[0]);
-        f.render_widget(self.remote.render(layout[1])?, layout[1]);
+        let pane = match self.focus {
+            FileUIFocus::Local => self.local.status_text(),
+            FileUIFocus::Remote => self.remote.status_text(),
+            FileUIFocus::Jobs => self.transfers.summary(),
+        };
+        self.status.render(f, outer[2], &pane, self.key_hints());
+
+        if let Some(prompt) = &self.prompt {
+            prompt.render(f);
+        }
 
         Ok(())
     }
 
+    fn key_hints(&self) -> &str {
+        match (&self.focus, &self.device_pane) {
+            (_, _) if self.prompt.is_some() => "Enter confirm  Esc cancel",
+            (FileUIFocus::Jobs, _) => {
+                "c cancel  p pause  r retry  R retry failed  x clear finished  J close"
+            }
+            (FileUIFocus::Remote, Some(DevicePane::Templates(_))) => {
+                "i install from local  x remove  A re-apply  r reload  M close"
+            }
+            (FileUIFocus::Remote, Some(DevicePane::Screens(_))) => {
+                "i install from local  x restore  r reload  W close"
+            }
+            (FileUIFocus::Remote, Some(DevicePane::Pages(_))) => {
+                "s select  [/] move up/down  y duplicate  x delete  m move to notebook  r reload  ← close"
+            }
+            (FileUIFocus::Remote, Some(DevicePane::Sync(_))) => "Enter apply sync  ← cancel",
+            (FileUIFocus::Remote, Some(DevicePane::Check(_))) => {
+                "f fix  F fix all  r check mirror  R check tablet  ← close"
+            }
+            (FileUIFocus::Local, _) => {
+                "s select  C copy  m move  n mkdir  r rename  y/x/p copy/cut/paste  X delete  . hidden  o sort  L layout  q quit"
+            }
+            (FileUIFocus::Remote, None) => {
+                "s select  C download  e export  f pin  t/T tag  H highlights  E text  G merge  X delete  S sync  D preview sync  K check  v tree  M templates  W screens  L layout  q quit"
+            }
+        }
+    }
+
+    // Last successful sync is kept across sessions
+    pub fn record_sync<T>(&mut self, result: &Result<T, intern_error::Error>) {
+        self.status.record(result);
+
+        if result.is_ok() {
+            self.status.last_sync = Some(chrono::Utc::now().timestamp_millis());
+            self.save_data.last_sync = self.status.last_sync;
+
+            if let Err(why) = self.save_data.save() {
+                log::warn!("Couldn't save last sync time: {}", why);
+            }
+        }
+    }
+
+    fn update_focus(&mut self) {
+        // The jobs panel can't keep focus once it's collapsed
+        if self.focus == FileUIFocus::Jobs && !self.transfers.expanded {
+            self.focus = FileUIFocus::Local;
+        }
+
+        if self.focus != FileUIFocus::Jobs {
+            self.last_pane = self.focus;
+        }
+
+        self.local.focused = self.focus == FileUIFocus::Local;
+        self.remote.focused = self.focus == FileUIFocus::Remote;
+        self.transfers.focused = self.focus == FileUIFocus::Jobs;
+
+        match self.device_pane.as_mut() {
+            Some(DevicePane::Templates(templates)) => {
+                templates.focused = self.focus == FileUIFocus::Remote
+            }
+            Some(DevicePane::Screens(screens)) => {
+                screens.focused = self.focus == FileUIFocus::Remote
+            }
+            Some(DevicePane::Pages(pages)) => pages.focused = self.focus == FileUIFocus::Remote,
+            Some(DevicePane::Sync(sync)) => sync.focused = self.focus == FileUIFocus::Remote,
+            Some(DevicePane::Check(check)) => check.focused = self.focus == FileUIFocus::Remote,
+            None => (),
+        }
+    }
+
     pub fn toggle_focus(&mut self) {
         self.focus = match self.focus {
             FileUIFocus::Local => FileUIFocus::Remote,
-            FileUIFocus::Remote => FileUIFocus::Local,
+            FileUIFocus::Remote if self.transfers.expanded => FileUIFocus::Jobs,
+            FileUIFocus::Remote | FileUIFocus::Jobs => FileUIFocus::Local,
         };
+
+        self.update_focus();
     }
 
     pub fn cursor_move(&mut self, direction: CursorDirection) {
         match self.focus {
             FileUIFocus::Local => self.local.cursor_move(direction),
-            FileUIFocus::Remote => self.remote.cursor_move(direction),
+            FileUIFocus::Remote => match self.device_pane.as_mut() {
+                Some(DevicePane::Templates(templates)) => templates.cursor_move(direction),
+                Some(DevicePane::Screens(screens)) => screens.cursor_move(direction),
+                Some(DevicePane::Pages(pages)) => pages.cursor_move(direction),
+                Some(DevicePane::Sync(sync)) => sync.cursor_move(direction),
+                Some(DevicePane::Check(check)) => check.cursor_move(direction),
+                None => self.remote.cursor_move(direction),
+            },
+            FileUIFocus::Jobs => self.transfers.cursor_move(direction),
         };
 
-        self.local.focused = self.focus == FileUIFocus::Local;
-        self.remote.focused = self.focus == FileUIFocus::Remote;
+        self.update_focus();
     }
 
     pub fn expand_selection(&mut self) -> Result<(), intern_error::Error> {
         match self.focus {
             FileUIFocus::Local => self.local.expand_selection()?,
-            FileUIFocus::Remote => self.remote.expand_selection()?,
+            FileUIFocus::Remote if matches!(self.device_pane, Some(DevicePane::Sync(_))) => {
+                self.sync_key_handler(KeyCode::Enter)
+            }
+            FileUIFocus::Remote if self.device_pane.is_some() => (),
+            FileUIFocus::Remote => {
+                if self.remote.toggle_expanded(None)? {
+                    return Ok(());
+                }
+
+                match self.remote.get_cursor_selection() {
+                    Some(item) if item.file_type == MetadataType::DocumentType => {
+                        let mut view = PageView::new(&item.uuid, &item.name);
+                        view.load()?;
+
+                        self.device_pane = Some(DevicePane::Pages(view));
+                        self.update_focus();
+                    }
+                    _ => self.remote.expand_selection()?,
+                }
+            }
+            FileUIFocus::Jobs => (),
         };
 
         Ok(())
@@ -96,17 +384,1264 @@ impl FileUI {
         match self.focus {
             FileUIFocus::Local => self.local.toggle_highlight_selection(),
             FileUIFocus::Remote => self.remote.toggle_highlight_selection(),
+            FileUIFocus::Jobs => Ok(()),
         }
     }
 
+    // Called after every index of the library
     pub fn refresh_views(&mut self) -> Result<(), intern_error::Error> {
+        self.library_loaded = true;
+
         self.local.refresh_view()?;
         self.remote.refresh_view()?;
 
         Ok(())
     }
 
+    // Queue whatever has turned up in the watched inboxes since the last look, giving
+    // back problems worth telling the user about
+    pub fn watch_inboxes(&mut self) -> Vec<intern_error::Error> {
+        if !self.library_loaded || !self.inbox.due() {
+            return Vec::new();
+        }
+
+        let (uploads, errors) = self.inbox.poll(&self.db);
+
+        for upload in uploads {
+            self.transfers.queue(upload);
+        }
+
+        errors
+    }
+
+    // Bulk actions go ahead with what they can and say what they had to leave out
+    fn report_skipped(&self, tally: &Tally, action: &str) -> Result<(), intern_error::Error> {
+        match tally.skipped.first() {
+            Some(name) if tally.queued == 0 => Err(Error::UnsupportedFileError(name.clone())),
+            Some(_) => {
+                self.notify(
+                    &format!(
+                        "Skipped {} item(s) that can't be {}",
+                        tally.skipped.len(),
+                        action
+                    ),
+                    NotificationType::ErrorLow,
+                );
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    // Queue a local file for upload, or a directory as a new collection followed by
+    // everything in it. Moving a directory moves the files and leaves the directory.
+    fn queue_upload(
+        &mut self,
+        path: &std::path::Path,
+        parent: &str,
+        remove_source: bool,
+        tally: &mut Tally,
+    ) -> Result<(), intern_error::Error> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        if path.is_dir() {
+            let uuid = Uuid::new_v4().to_string();
+
+            log::info!("Upload of directory {} requested", path.display());
+
+            self.transfers.queue(TransferKind::NewCollection {
+                uuid: uuid.clone(),
+                name,
+                parent: String::from(parent),
+            });
+
+            let mut entries: Vec<PathBuf> = fs::read_dir(path)
+                .context(ErrorContext::file(path))?
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|entry| {
+                    self.local.show_hidden()
+                        || !entry
+                            .file_name()
+                            .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+                })
+                .collect();
+            entries.sort();
+
+            for entry in entries {
+                self.queue_upload(&entry, &uuid, remove_source, tally)?;
+            }
+        } else if upload_file_type(path).is_some() {
+            log::info!("Upload of {} requested", path.display());
+
+            self.transfers.queue(TransferKind::Upload {
+                source: path.to_path_buf(),
+                parent: String::from(parent),
+                remove_source,
+                from_inbox: false,
+            });
+
+            tally.queued += 1;
+        } else {
+            tally.skipped.push(name);
+        }
+
+        Ok(())
+    }
+
+    // Queue the local selection for upload into the open remote collection,
+    // `remove_source` makes it a move
+    pub fn upload_selection(&mut self, remove_source: bool) -> Result<(), intern_error::Error> {
+        let parent = self.remote.target_collection();
+
+        let mut tally = Tally::default();
+
+        for item in self.local.get_targets() {
+            self.queue_upload(&item.path, &parent, remove_source, &mut tally)?;
+        }
+
+        self.report_skipped(&tally, "uploaded")?;
+        self.local.clear_selection();
+
+        Ok(())
+    }
+
+    // Queue a document for download into `dest_dir`, or create a directory for a
+    // collection and queue everything in it. Notebooks have no payload to download
+    // so they are always exported.
+    fn queue_download(
+        &mut self,
+        item: &FileItem,
+        dest_dir: &std::path::Path,
+        export: bool,
+        remove_source: bool,
+        tally: &mut Tally,
+    ) -> Result<(), intern_error::Error> {
+        let (uuid, name) = (item.uuid.as_str(), item.name.as_str());
+
+        let Ok(stem) = local::stem_for(name) else {
+            tally.skipped.push(String::from(name));
+            return Ok(());
+        };
+
+        match item.file_type {
+            MetadataType::CollectionType => {
+                let dir = unique_path(dest_dir, &stem, "");
+
+                log::info!("Download of collection {} ({}) requested", name, uuid);
+
+                fs::create_dir_all(&dir).context(ErrorContext::file(&dir))?;
+
+                let children = self
+                    .db
+                    .prepare("SELECT uuid, name, object_type FROM objects WHERE parent=?1")?
+                    .query_map([uuid], |r| {
+                        Ok(FileItem::new()
+                            .uuid(r.get(0)?)
+                            .name(&r.get::<usize, String>(1)?)
+                            .file_type(MetadataType::from(r.get::<usize, String>(2)?)))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                for child in children {
+                    self.queue_download(&child, &dir, export, remove_source, tally)?;
+                }
+            }
+            MetadataType::DocumentType => {
+                log::info!("Download of {} ({}) requested", name, uuid);
+
+                let payload = document_file_type(uuid);
+
+                if export || payload == "notebook" {
+                    self.transfers.queue(TransferKind::Export {
+                        dest: self.transfers.unique_dest(dest_dir, &stem, "pdf"),
+                        uuid: String::from(uuid),
+                        name: String::from(name),
+                        remove_source,
+                    });
+                } else {
+                    self.transfers.queue(TransferKind::Download {
+                        dest: self.transfers.unique_dest(dest_dir, &stem, &payload),
+                        uuid: String::from(uuid),
+                        name: String::from(name),
+                        file_type: payload,
+                        remove_source,
+                    });
+                }
+
+                tally.queued += 1;
+            }
+            _ => tally.skipped.push(String::from(name)),
+        }
+
+        Ok(())
+    }
+
+    // Queue the remote selection for download into the open local directory
+    pub fn download_selection(
+        &mut self,
+        export: bool,
+        remove_source: bool,
+    ) -> Result<(), intern_error::Error> {
+        let dest_dir = self.local.get_parent().path;
+
+        let mut tally = Tally::default();
+
+        for item in self.remote.get_targets() {
+            self.queue_download(&item, &dest_dir, export, remove_source, &mut tally)?;
+        }
+
+        self.report_skipped(&tally, "downloaded")?;
+        self.remote.clear_selection();
+        self.local.refresh_view()?;
+
+        Ok(())
+    }
+
+    // Copy or move the focused pane's selection into the other pane
+    pub fn transfer_to_other_pane(
+        &mut self,
+        remove_source: bool,
+    ) -> Result<(), intern_error::Error> {
+        match self.focus {
+            FileUIFocus::Local => self.upload_selection(remove_source),
+            FileUIFocus::Remote => self.download_selection(false, remove_source),
+            FileUIFocus::Jobs => Ok(()),
+        }
+    }
+
+    // Local files go to the trash directory, documents on the tablet to its own trash
+    pub fn delete_selection(&mut self) -> Result<(), intern_error::Error> {
+        let count = match self.focus {
+            FileUIFocus::Local => {
+                let targets = self.local.get_targets();
+
+                for item in &targets {
+                    local::trash(&item.path)?;
+                }
+
+                self.local.clear_selection();
+                self.local.refresh_view()?;
+                targets.len()
+            }
+            FileUIFocus::Remote => {
+                let targets = self.remote.get_targets();
+
+                for item in &targets {
+                    log::info!("Moving {} ({}) to the tablet's trash", item.name, item.uuid);
+
+                    set_parent(&self.db, &item.uuid, TRASH)?;
+
+                    self.transfers.queue(TransferKind::Push {
+                        files: vec![format!("{}.metadata", item.uuid)],
+                        removed: Vec::new(),
+                        uuid: item.uuid.clone(),
+                        name: item.name.clone(),
+                    });
+                }
+
+                self.remote.clear_selection();
+                self.remote.refresh_view()?;
+                targets.len()
+            }
+            FileUIFocus::Jobs => 0,
+        };
+
+        if count > 0 {
+            self.notify(
+                &format!("Moved {} item(s) to the trash", count),
+                NotificationType::Success,
+            );
+        }
+
+        Ok(())
+    }
+
+    // Pin the selection, or unpin it if it's all pinned already. The edit is made to the
+    // mirror straight away and pushed to the tablet through the transfer queue.
+    pub fn toggle_pinned(&mut self) -> Result<(), intern_error::Error> {
+        let targets = self.remote.get_targets();
+        let pinned = targets.iter().any(|item| !item.pinned);
+
+        for item in targets {
+            log::info!("Setting pinned={} on {} ({})", pinned, item.name, item.uuid);
+
+            set_pinned(&self.db, &item.uuid, pinned)?;
+
+            self.transfers.queue(TransferKind::Push {
+                files: vec![format!("{}.metadata", item.uuid)],
+                removed: Vec::new(),
+                uuid: item.uuid,
+                name: item.name,
+            });
+        }
+
+        self.remote.refresh_view()
+    }
+
+    fn tag_selection(&mut self, tag: &str, present: bool) -> Result<(), intern_error::Error> {
+        let targets = self.remote.get_targets();
+
+        let documents: Vec<FileItem> = targets
+            .iter()
+            .filter(|item| item.file_type == MetadataType::DocumentType)
+            .cloned()
+            .collect();
+
+        if documents.is_empty() {
+            return Err(match targets.first() {
+                Some(item) => Error::UnsupportedFileError(item.name.clone()),
+                None => Error::VecAccessError(self.remote.get_cursor_idx()),
+            });
+        }
+
+        for item in documents {
+            log::info!(
+                "{} tag {} on {} ({})",
+                if present { "Adding" } else { "Removing" },
+                tag,
+                item.name,
+                item.uuid
+            );
+
+            set_tag(&self.db, &item.uuid, tag, present)?;
+
+            self.transfers.queue(TransferKind::Push {
+                files: vec![
+                    format!("{}.content", item.uuid),
+                    format!("{}.metadata", item.uuid),
+                ],
+                removed: Vec::new(),
+                uuid: item.uuid,
+                name: item.name,
+            });
+        }
+
+        self.remote.refresh_view()
+    }
+
+    fn notify(&self, text: &str, notif_type: NotificationType) {
+        let _ = self.events.unbounded_send(AppEvent::Notify(
+            NotificationWidget::default()
+                .text(text)
+                .notif_type(notif_type),
+        ));
+    }
+
+    // Write the highlights of the selected documents to one Markdown file in the open
+    // local directory
+    pub fn export_highlights(&mut self) -> Result<(), intern_error::Error> {
+        let documents: Vec<FileItem> = self
+            .remote
+            .get_targets()
+            .into_iter()
+            .filter(|item| item.file_type == MetadataType::DocumentType)
+            .collect();
+
+        let mut sections = Vec::new();
+        let mut count = 0;
+
+        for item in &documents {
+            let highlights =
+                document_highlights(&item.uuid).context(ErrorContext::document(&item.uuid))?;
+
+            log::info!(
+                "Found {} highlights in {} ({})",
+                highlights.len(),
+                item.name,
+                item.uuid
+            );
+
+            if !highlights.is_empty() {
+                count += highlights.len();
+                sections.push(to_markdown(&item.name, &highlights));
+            }
+        }
+
+        if sections.is_empty() {
+            self.notify("No highlights found", NotificationType::Message);
+            return Ok(());
+        }
+
+        let stem = match documents.as_slice() {
+            [item] => local::stem_for(&format!("{} highlights", item.name))?,
+            _ => String::from("highlights"),
+        };
+        let path = unique_path(&self.local.get_parent().path, &stem, "md");
+
+        fs::write(&path, sections.join("\n")).context(ErrorContext::file(&path))?;
+
+        self.notify(
+            &format!("Exported {} highlights to\n{}", count, path.display()),
+            NotificationType::Success,
+        );
+
+        self.local.refresh_view()
+    }
+
+    // Write the typed text of each selected notebook to its own Markdown file in the
+    // open local directory
+    pub fn export_typed_text(&mut self) -> Result<(), intern_error::Error> {
+        let documents: Vec<FileItem> = self
+            .remote
+            .get_targets()
+            .into_iter()
+            .filter(|item| item.file_type == MetadataType::DocumentType)
+            .collect();
+
+        let mut written = Vec::new();
+
+        for item in &documents {
+            let pages = document_text(&item.uuid).context(ErrorContext::document(&item.uuid))?;
+
+            log::info!(
+                "Found typed text on {} pages of {} ({})",
+                pages.len(),
+                item.name,
+                item.uuid
+            );
+
+            if pages.is_empty() {
+                continue;
+            }
+
+            let stem = local::stem_for(&item.name).context(ErrorContext::document(&item.uuid))?;
+            let path = unique_path(&self.local.get_parent().path, &stem, "md");
+
+            fs::write(&path, typed_text::to_markdown(&item.name, &pages))
+                .context(ErrorContext::file(&path))?;
+
+            written.push(path);
+        }
+
+        match written.as_slice() {
+            [] => {
+                self.notify("No typed text found", NotificationType::Message);
+                return Ok(());
+            }
+            [path] => self.notify(
+                &format!("Extracted typed text to\n{}", path.display()),
+                NotificationType::Success,
+            ),
+            _ => self.notify(
+                &format!(
+                    "Extracted typed text from {} notebooks to\n{}",
+                    written.len(),
+                    self.local.get_parent().path.display()
+                ),
+                NotificationType::Success,
+            ),
+        }
+
+        self.local.refresh_view()
+    }
+
+    pub fn input_active(&self) -> bool {
+        self.prompt.is_some()
+    }
+
+    fn prompt_key_handler(&mut self, keycode: KeyCode) -> Result<(), intern_error::Error> {
+        let Some(prompt) = self.prompt.as_mut() else {
+            return Ok(());
+        };
+
+        match prompt.key_handler(keycode) {
+            PromptResult::Pending => Ok(()),
+            PromptResult::Cancelled => {
+                self.prompt = None;
+                self.template_draft = None;
+                self.merge_draft = None;
+                Ok(())
+            }
+            PromptResult::Submitted(action, input) => {
+                self.prompt = None;
+
+                match action {
+                    PromptAction::AddTag => self.tag_selection(&input, true),
+                    PromptAction::RemoveTag => self.tag_selection(&input, false),
+                    PromptAction::TemplateName
+                    | PromptAction::TemplateIcon
+                    | PromptAction::TemplateCategories => self.continue_template(action, input),
+                    PromptAction::MakeDir => self.make_local_dir(&input),
+                    PromptAction::Rename => self.rename_local(&input),
+                    PromptAction::MovePages => self.move_pages(&input),
+                    PromptAction::MergeName
+                    | PromptAction::MergeOrder
+                    | PromptAction::MergeTrash => self.continue_merge(action, input),
+                }
+            }
+        }
+    }
+
+    fn make_local_dir(&mut self, name: &str) -> Result<(), intern_error::Error> {
+        local::make_dir(&self.local.get_parent().path, name)?;
+        self.local.refresh_view()
+    }
+
+    fn start_local_rename(&mut self) -> Result<(), intern_error::Error> {
+        match self.local.get_cursor_selection() {
+            Some(item) if self.local.is_selectable(item) => {
+                self.prompt =
+                    Some(Prompt::new("Rename to", PromptAction::Rename).input(&item.name));
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn rename_local(&mut self, name: &str) -> Result<(), intern_error::Error> {
+        let item = match self.local.get_cursor_selection() {
+            Some(val) => val.clone(),
+            None => return Err(Error::VecAccessError(self.local.get_cursor_idx())),
+        };
+
+        if item.name != name {
+            local::rename(&item.path, name)?;
+        }
+
+        self.local.refresh_view()
+    }
+
+    // Remember the local selection for pasting into another directory with `p`
+    fn yank_local(&mut self, cut: bool) {
+        let paths: Vec<PathBuf> = self
+            .local
+            .get_targets()
+            .into_iter()
+            .map(|item| item.path.to_path_buf())
+            .collect();
+
+        if paths.is_empty() {
+            return;
+        }
+
+        self.notify(
+            &format!(
+                "{} {} item(s), paste with p",
+                if cut { "Cut" } else { "Copied" },
+                paths.len()
+            ),
+            NotificationType::Message,
+        );
+
+        self.clipboard = Some(Clipboard { paths, cut });
+        self.local.clear_selection();
+    }
+
+    // Cut files are only pasted once, copies can be pasted again elsewhere
+    fn paste_local(&mut self) -> Result<(), intern_error::Error> {
+        let Some(clipboard) = self.clipboard.take() else {
+            return Ok(());
+        };
+
+        let dir = self.local.get_parent().path;
+
+        let result = clipboard
+            .paths
+            .iter()
+            .filter(|path| path.exists())
+            .try_for_each(|path| local::paste(path, &dir, clipboard.cut).map(|_| ()));
+
+        if !clipboard.cut {
+            self.clipboard = Some(clipboard);
+        }
+
+        self.local.refresh_view()?;
+
+        result
+    }
+
+    // Open the requested view in place of the remote pane, or go back to the library
+    // if it is already showing
+    fn toggle_device_pane(&mut self, templates: bool) -> Result<(), intern_error::Error> {
+        let showing = matches!(
+            (self.device_pane.take(), templates),
+            (Some(DevicePane::Templates(_)), true) | (Some(DevicePane::Screens(_)), false)
+        );
+
+        if showing {
+            return Ok(());
+        }
+
+        let pane = if templates {
+            DevicePane::Templates(TemplateView::default())
+        } else {
+            DevicePane::Screens(ScreenView::default())
+        };
+
+        self.device_pane = Some(pane);
+        self.update_focus();
+
+        self.reload_device_pane()
+    }
+
+    // Lists that come from the tablet are fetched in the background and shown once the
+    // job is done
+    pub fn reload_device_pane(&mut self) -> Result<(), intern_error::Error> {
+        match self.device_pane.as_mut() {
+            Some(DevicePane::Templates(_)) => {
+                let _ = self
+                    .events
+                    .unbounded_send(AppEvent::Spawn(JobKind::Templates));
+                Ok(())
+            }
+            Some(DevicePane::Screens(_)) => {
+                let _ = self
+                    .events
+                    .unbounded_send(AppEvent::Spawn(JobKind::Screens));
+                Ok(())
+            }
+            Some(DevicePane::Pages(pages)) => pages.load(),
+            Some(DevicePane::Sync(_)) | Some(DevicePane::Check(_)) | None => Ok(()),
+        }
+    }
+
+    // Ask for the name, icon and categories of the PNG or SVG under the local cursor
+    fn start_template_install(&mut self) -> Result<(), intern_error::Error> {
+        let item = match self.local.get_cursor_selection() {
+            Some(val) => val.clone(),
+            None => return Err(Error::VecAccessError(self.local.get_cursor_idx())),
+        };
+
+        if item.file_type != MetadataType::DocumentType
+            || !matches!(item.format.to_lowercase().as_str(), "png" | "svg")
+        {
+            return Err(Error::UnsupportedFileError(item.name));
+        }
+
+        let stem = item
+            .path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        self.template_draft = Some(TemplateDraft {
+            source: item.path.to_path_buf(),
+            name: String::new(),
+            icon: String::new(),
+        });
+        self.prompt = Some(Prompt::new("Template name", PromptAction::TemplateName).input(&stem));
+
+        Ok(())
+    }
+
+    fn continue_template(
+        &mut self,
+        action: PromptAction,
+        input: String,
+    ) -> Result<(), intern_error::Error> {
+        let Some(draft) = self.template_draft.as_mut() else {
+            return Ok(());
+        };
+
+        match action {
+            PromptAction::TemplateName => {
+                draft.name = input;
+                self.prompt = Some(
+                    Prompt::new("Icon code (hex)", PromptAction::TemplateIcon).input(DEFAULT_ICON),
+                );
+                Ok(())
+            }
+            PromptAction::TemplateIcon => {
+                draft.icon = input;
+                self.prompt = Some(
+                    Prompt::new(
+                        "Categories (comma separated)",
+                        PromptAction::TemplateCategories,
+                    )
+                    .input("Custom"),
+                );
+                Ok(())
+            }
+            _ => {
+                let Some(draft) = self.template_draft.take() else {
+                    return Ok(());
+                };
+
+                let categories = input
+                    .split(',')
+                    .map(|category| category.trim().to_string())
+                    .filter(|category| !category.is_empty())
+                    .collect();
+
+                let _ = self.events.unbounded_send(AppEvent::Spawn(JobKind::Device(
+                    DeviceChange::InstallTemplate {
+                        source: draft.source,
+                        name: draft.name,
+                        icon: draft.icon,
+                        categories,
+                    },
+                )));
+                Ok(())
+            }
+        }
+    }
+
+    pub fn show_templates(&mut self, templates: Vec<Template>) {
+        if let Some(DevicePane::Templates(view)) = self.device_pane.as_mut() {
+            view.set_templates(templates);
+        }
+    }
+
+    fn templates_key_handler(&mut self, keycode: KeyCode) -> Result<(), intern_error::Error> {
+        let Some(DevicePane::Templates(view)) = self.device_pane.as_ref() else {
+            return Ok(());
+        };
+
+        let Some(selected) = view.get_cursor_selection().cloned() else {
+            return match keycode {
+                KeyCode::Char('r') => self.reload_device_pane(),
+                _ => Ok(()),
+            };
+        };
+
+        let is_user = view.is_user(&selected);
+
+        match keycode {
+            KeyCode::Char('r') => self.reload_device_pane(),
+            KeyCode::Char('x') => {
+                // Stock templates come back with every update anyway, leave them be
+                if !is_user {
+                    return Err(Error::UnsupportedFileError(selected.name));
+                }
+
+                let _ = self.events.unbounded_send(AppEvent::Spawn(JobKind::Device(
+                    DeviceChange::RemoveTemplate {
+                        filename: selected.filename,
+                        name: selected.name,
+                    },
+                )));
+                Ok(())
+            }
+            KeyCode::Char('A') => {
+                let _ = self.events.unbounded_send(AppEvent::Spawn(JobKind::Device(
+                    DeviceChange::ReapplyTemplates,
+                )));
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Put the image under the local cursor on the screen selected in the screens view
+    fn install_screen(&mut self) -> Result<(), intern_error::Error> {
+        let Some(DevicePane::Screens(view)) = self.device_pane.as_ref() else {
+            return Ok(());
+        };
+
+        let Some(screen) = view.get_cursor_selection() else {
+            return Ok(());
+        };
+
+        let item = match self.local.get_cursor_selection() {
+            Some(val) => val.clone(),
+            None => return Err(Error::VecAccessError(self.local.get_cursor_idx())),
+        };
+
+        if item.file_type != MetadataType::DocumentType {
+            return Err(Error::UnsupportedFileError(item.name));
+        }
+
+        let _ = self.events.unbounded_send(AppEvent::Spawn(JobKind::Device(
+            DeviceChange::InstallScreen {
+                screen,
+                source: item.path.to_path_buf(),
+            },
+        )));
+        Ok(())
+    }
+
+    pub fn show_screens(&mut self, states: Vec<(Screen, ScreenState)>) {
+        if let Some(DevicePane::Screens(view)) = self.device_pane.as_mut() {
+            view.set_states(states);
+        }
+    }
+
+    fn screens_key_handler(&mut self, keycode: KeyCode) -> Result<(), intern_error::Error> {
+        let Some(DevicePane::Screens(view)) = self.device_pane.as_ref() else {
+            return Ok(());
+        };
+
+        let selected = view.get_cursor_selection();
+
+        match (keycode, selected) {
+            (KeyCode::Char('r'), _) => self.reload_device_pane(),
+            (KeyCode::Char('x'), Some(screen)) => {
+                let _ = self.events.unbounded_send(AppEvent::Spawn(JobKind::Device(
+                    DeviceChange::RestoreScreen(screen),
+                )));
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Push the files a page edit touched, remove the ones it dropped and show the
+    // document's pages again, with the cursor on `cursor` if given
+    fn after_page_edit(
+        &mut self,
+        files: Vec<String>,
+        removed: Vec<String>,
+        cursor: Option<&str>,
+    ) -> Result<(), intern_error::Error> {
+        let Some(DevicePane::Pages(view)) = self.device_pane.as_mut() else {
+            return Ok(());
+        };
+
+        self.transfers.queue(TransferKind::Push {
+            uuid: view.uuid.clone(),
+            name: view.name.clone(),
+            files,
+            removed,
+        });
+
+        view.load()?;
+
+        if let Some(id) = cursor {
+            view.cursor_to(id);
+        }
+
+        Ok(())
+    }
+
+    // Show what a sync would change and hold off until it's confirmed
+    pub fn show_sync_plan(&mut self, mut plan: SyncPlan) -> Result<(), intern_error::Error> {
+        plan.resolve_names(&self.db)?;
+
+        if plan.is_empty() {
+            self.notify(
+                "Nothing to sync, the mirror is up to date",
+                NotificationType::Success,
+            );
+            return Ok(());
+        }
+
+        self.device_pane = Some(DevicePane::Sync(SyncView::new(plan)));
+        self.focus = FileUIFocus::Remote;
+        self.update_focus();
+
+        Ok(())
+    }
+
+    fn sync_key_handler(&mut self, keycode: KeyCode) {
+        match keycode {
+            KeyCode::Enter | KeyCode::Char('y') => {
+                self.device_pane = None;
+                self.update_focus();
+
+                let _ = self.events.unbounded_send(AppEvent::Spawn(JobKind::Sync));
+            }
+            KeyCode::Left | KeyCode::Backspace | KeyCode::Char('n') => {
+                self.device_pane = None;
+                self.update_focus();
+                self.notify("Sync cancelled", NotificationType::Message);
+            }
+            _ => (),
+        }
+    }
+
+    // Open the problems found by an integrity check
+    pub fn show_check_report(&mut self, source: CheckSource, problems: Vec<Problem>) {
+        if problems.is_empty() {
+            self.notify(
+                &format!("No problems found in the {}", source.get_text()),
+                NotificationType::Success,
+            );
+            return;
+        }
+
+        self.device_pane = Some(DevicePane::Check(CheckView::new(source, problems)));
+        self.focus = FileUIFocus::Remote;
+        self.update_focus();
+    }
+
+    fn check_key_handler(&mut self, keycode: KeyCode) -> Result<(), intern_error::Error> {
+        let Some(DevicePane::Check(view)) = self.device_pane.as_mut() else {
+            return Ok(());
+        };
+
+        match keycode {
+            KeyCode::Left | KeyCode::Backspace => {
+                self.device_pane = None;
+                self.update_focus();
+            }
+            KeyCode::Char(key @ ('r' | 'R')) => {
+                let source = if key == 'r' {
+                    CheckSource::Mirror
+                } else {
+                    CheckSource::Device
+                };
+
+                let _ = self
+                    .events
+                    .unbounded_send(AppEvent::Spawn(JobKind::Check(source)));
+            }
+            KeyCode::Char(key @ ('f' | 'F')) => {
+                let problems = view.get_fixable(key == 'F');
+
+                if problems.is_empty() {
+                    return Err(Error::NotFoundError(String::from("Nothing here to fix")));
+                }
+
+                let transfers = integrity::apply_fixes(&problems)?;
+                view.remove_fixed(&problems);
+
+                for transfer in transfers {
+                    self.transfers.queue(transfer);
+                }
+
+                self.notify(
+                    &format!("Fixing {} problem(s)", problems.len()),
+                    NotificationType::Message,
+                );
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn pages_key_handler(&mut self, keycode: KeyCode) -> Result<(), intern_error::Error> {
+        let Some(DevicePane::Pages(view)) = self.device_pane.as_mut() else {
+            return Ok(());
+        };
+
+        let uuid = view.uuid.clone();
+        let targets = view.get_targets();
+        let cursor = view.get_cursor_selection().map(|page| page.id.clone());
+
+        match (keycode, cursor) {
+            (KeyCode::Left | KeyCode::Backspace, _) => {
+                self.device_pane = None;
+                self.update_focus();
+                Ok(())
+            }
+            (KeyCode::Char('r'), _) => self.reload_device_pane(),
+            (KeyCode::Char('s'), _) => {
+                view.toggle_selected();
+                Ok(())
+            }
+            (KeyCode::Char('a'), _) => {
+                view.toggle_select_all();
+                Ok(())
+            }
+            (KeyCode::Char(key @ ('[' | ']')), Some(page)) => {
+                let delta = if key == '[' { -1 } else { 1 };
+                let files = pages::move_page(&uuid, &page, delta)?;
+
+                self.after_page_edit(files, Vec::new(), Some(&page))
+            }
+            (KeyCode::Char('y'), Some(page)) => {
+                let files = pages::duplicate_page(&uuid, &page)?;
+
+                self.after_page_edit(files, Vec::new(), Some(&page))
+            }
+            (KeyCode::Char('x'), Some(_)) => {
+                view.clear_selection();
+                let (files, removed) = pages::delete_pages(&uuid, &targets)?;
+
+                self.notify(
+                    &format!("Deleted {} page(s)", targets.len()),
+                    NotificationType::Success,
+                );
+                self.after_page_edit(files, removed, None)
+            }
+            (KeyCode::Char('m'), Some(_)) => {
+                self.prompt = Some(Prompt::new(
+                    &format!("Move {} page(s) to notebook", targets.len()),
+                    PromptAction::MovePages,
+                ));
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Move the marked pages to the end of the notebook named, or given by uuid
+    fn move_pages(&mut self, target: &str) -> Result<(), intern_error::Error> {
+        let Some(DevicePane::Pages(view)) = self.device_pane.as_mut() else {
+            return Ok(());
+        };
+
+        let (target_uuid, target_name) = find_document(&self.db, target)?;
+        let targets = view.get_targets();
+
+        let (files, removed) = pages::move_pages(&view.uuid, &targets, &target_uuid)?;
+        view.clear_selection();
+
+        self.notify(
+            &format!("Moved {} page(s) to {}", targets.len(), target_name),
+            NotificationType::Success,
+        );
+        self.after_page_edit(files, removed, None)
+    }
+
+    // Ask for the name, page order and what to do with the originals before merging
+    // the selected notebooks
+    fn start_merge(&mut self) -> Result<(), intern_error::Error> {
+        let sources: Vec<FileItem> = self
+            .remote
+            .get_targets()
+            .into_iter()
+            .filter(|item| item.file_type == MetadataType::DocumentType)
+            .collect();
+
+        if sources.len() < 2 {
+            return Err(Error::PageEditError(String::from(
+                "Select at least two notebooks to merge",
+            )));
+        }
+
+        self.prompt = Some(
+            Prompt::new("Merged notebook name", PromptAction::MergeName)
+                .input(&format!("{} (merged)", sources[0].name)),
+        );
+        self.merge_draft = Some(MergeDraft {
+            sources,
+            parent: self.remote.target_collection(),
+            name: String::new(),
+            order: MergeOrder::Selection,
+        });
+
+        Ok(())
+    }
+
+    fn continue_merge(
+        &mut self,
+        action: PromptAction,
+        input: String,
+    ) -> Result<(), intern_error::Error> {
+        let Some(draft) = self.merge_draft.as_mut() else {
+            return Ok(());
+        };
+
+        match action {
+            PromptAction::MergeName => {
+                draft.name = input;
+                self.prompt = Some(
+                    Prompt::new(
+                        "Page order (selection or created)",
+                        PromptAction::MergeOrder,
+                    )
+                    .input("selection"),
+                );
+                Ok(())
+            }
+            PromptAction::MergeOrder => {
+                draft.order = match input.trim() {
+                    "created" | "c" => MergeOrder::Created,
+                    _ => MergeOrder::Selection,
+                };
+                self.prompt = Some(
+                    Prompt::new(
+                        "Move the originals to the trash? (y/n)",
+                        PromptAction::MergeTrash,
+                    )
+                    .input("n"),
+                );
+                Ok(())
+            }
+            _ => {
+                let Some(draft) = self.merge_draft.take() else {
+                    return Ok(());
+                };
+
+                let uuids: Vec<String> =
+                    draft.sources.iter().map(|item| item.uuid.clone()).collect();
+
+                let (uuid, files) =
+                    pages::merge_notebooks(&uuids, &draft.name, &draft.parent, draft.order)?;
+
+                log::info!(
+                    "Merged {} notebooks into {} ({})",
+                    uuids.len(),
+                    draft.name,
+                    uuid
+                );
+
+                self.transfers.queue(TransferKind::Push {
+                    uuid,
+                    name: draft.name.clone(),
+                    files,
+                    removed: Vec::new(),
+                });
+
+                if matches!(input.trim(), "y" | "yes") {
+                    for item in &draft.sources {
+                        set_parent(&self.db, &item.uuid, TRASH)?;
+
+                        self.transfers.queue(TransferKind::Push {
+                            files: vec![format!("{}.metadata", item.uuid)],
+                            removed: Vec::new(),
+                            uuid: item.uuid.clone(),
+                            name: item.name.clone(),
+                        });
+                    }
+                }
+
+                self.notify(
+                    &format!("Merged {} notebooks into {}", uuids.len(), draft.name),
+                    NotificationType::Success,
+                );
+
+                self.remote.clear_selection();
+                self.remote.refresh_view()
+            }
+        }
+    }
+
+    // Switch or resize the layout and remember it for next time
+    fn change_layout(
+        &mut self,
+        change: impl FnOnce(&mut PaneLayout),
+    ) -> Result<(), intern_error::Error> {
+        change(&mut self.save_data.layout);
+
+        log::info!("Layout changed to {:?}", self.save_data.layout);
+
+        self.save_data.save()
+    }
+
+    // Change the sort order of the focused pane and remember it for next time
+    fn change_sort_order(
+        &mut self,
+        change: impl FnOnce(&mut SortOrder),
+    ) -> Result<(), intern_error::Error> {
+        match self.focus {
+            FileUIFocus::Local => {
+                let mut order = self.local.get_sort_order();
+                change(&mut order);
+                // Pinning only means something on the tablet
+                order.pinned_first = false;
+                self.local.apply_sort_order(order);
+                self.save_data.local_sort = order;
+            }
+            FileUIFocus::Remote => {
+                let mut order = self.remote.get_sort_order();
+                change(&mut order);
+                self.remote.apply_sort_order(order);
+                self.save_data.remote_sort = order;
+            }
+            FileUIFocus::Jobs => return Ok(()),
+        }
+
+        log::info!(
+            "Sort order changed to {:?} / {:?}",
+            self.save_data.local_sort,
+            self.save_data.remote_sort
+        );
+
+        self.save_data.save()
+    }
+
+    pub fn handle_transfer(
+        &mut self,
+        id: TransferId,
+        event: TransferEvent,
+    ) -> Option<TransferFinished> {
+        if let TransferEvent::Finished(result) = &event {
+            self.status.record(result);
+        }
+
+        self.transfers.update(id, event)
+    }
+
+    // File pane under a screen position. The remote pane only counts while it shows
+    // the library.
+    fn pane_at(&self, column: u16, row: u16) -> Option<FileUIFocus> {
+        if area_contains(self.local.get_render_area(), column, row) {
+            Some(FileUIFocus::Local)
+        } else if self.device_pane.is_none()
+            && area_contains(self.remote.get_render_area(), column, row)
+        {
+            Some(FileUIFocus::Remote)
+        } else {
+            None
+        }
+    }
+
+    // Click focuses a pane and moves its cursor, a second click on the same item opens
+    // it like Enter. The wheel scrolls whichever pane is under the pointer.
+    pub fn mouse_handler(&mut self, event: MouseEvent) -> Result<(), intern_error::Error> {
+        if self.prompt.is_some() {
+            return Ok(());
+        }
+
+        let Some(pane) = self.pane_at(event.column, event.row) else {
+            return Ok(());
+        };
+
+        let block: &mut dyn FSListBlock = match pane {
+            FileUIFocus::Local => &mut self.local,
+            _ => &mut self.remote,
+        };
+
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                let idx = block.idx_at_row(event.row);
+
+                if let Some(idx) = idx {
+                    block.set_cursor_idx(idx);
+                }
+
+                self.focus = pane;
+                self.update_focus();
+
+                let Some(idx) = idx else {
+                    return Ok(());
+                };
+
+                let double = matches!(
+                    self.last_click,
+                    Some((last_pane, last_idx, at))
+                        if last_pane == pane && last_idx == idx && at.elapsed() < DOUBLE_CLICK
+                );
+
+                if double {
+                    self.last_click = None;
+                    self.expand_selection()
+                } else {
+                    self.last_click = Some((pane, idx, Instant::now()));
+                    Ok(())
+                }
+            }
+            MouseEventKind::ScrollDown | MouseEventKind::ScrollUp => {
+                let direction = || match event.kind {
+                    MouseEventKind::ScrollDown => CursorDirection::Down,
+                    _ => CursorDirection::Up,
+                };
+
+                for _ in 0..SCROLL_LINES {
+                    block.cursor_move(direction());
+                }
+
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn jobs_key_handler(&mut self, keycode: KeyCode) {
+        match keycode {
+            KeyCode::Char('c') => self.transfers.cancel_selected(),
+            KeyCode::Char('p') => self.transfers.toggle_pause_selected(),
+            KeyCode::Char('r') => self.transfers.retry_selected(),
+            KeyCode::Char('R') => self.transfers.retry_failed(),
+            KeyCode::Char('x') => self.transfers.clear_finished(),
+            _ => (),
+        }
+    }
+
     pub fn key_handler(&mut self, keycode: KeyCode) -> Result<(), intern_error::Error> {
+        log::trace!("FileUI key {:?}", keycode);
+
+        if self.prompt.is_some() {
+            return self.prompt_key_handler(keycode);
+        }
+
         match keycode {
             KeyCode::Up => {
                 self.cursor_move(CursorDirection::Up);
@@ -126,9 +1661,128 @@ impl FileUI {
             KeyCode::PageUp => {
                 self.cursor_move(CursorDirection::PgUp);
             }
+            KeyCode::Char('J') => {
+                self.transfers.expanded = !self.transfers.expanded;
+                self.update_focus();
+            }
+            _ if self.focus == FileUIFocus::Jobs => self.jobs_key_handler(keycode),
+            KeyCode::Char('M') => {
+                self.toggle_device_pane(true)?;
+            }
+            KeyCode::Char('W') => {
+                self.toggle_device_pane(false)?;
+            }
+            KeyCode::Char('i') if self.focus == FileUIFocus::Local => match self.device_pane {
+                Some(DevicePane::Templates(_)) => self.start_template_install()?,
+                Some(DevicePane::Screens(_)) => self.install_screen()?,
+                Some(DevicePane::Pages(_))
+                | Some(DevicePane::Sync(_))
+                | Some(DevicePane::Check(_))
+                | None => (),
+            },
+            _ if self.focus == FileUIFocus::Remote && self.device_pane.is_some() => {
+                match self.device_pane {
+                    Some(DevicePane::Templates(_)) => self.templates_key_handler(keycode)?,
+                    Some(DevicePane::Pages(_)) => self.pages_key_handler(keycode)?,
+                    Some(DevicePane::Sync(_)) => self.sync_key_handler(keycode),
+                    Some(DevicePane::Check(_)) => self.check_key_handler(keycode)?,
+                    _ => self.screens_key_handler(keycode)?,
+                }
+            }
             KeyCode::Char('s') => {
                 self.highlight_selection()?;
             }
+            KeyCode::Char('a') => match self.focus {
+                FileUIFocus::Local => self.local.toggle_select_all(),
+                _ => self.remote.toggle_select_all(),
+            },
+            KeyCode::Backspace => match self.focus {
+                FileUIFocus::Local => self.local.clear_selection(),
+                _ => self.remote.clear_selection(),
+            },
+            KeyCode::Char('C') | KeyCode::F(5) => {
+                self.transfer_to_other_pane(false)?;
+            }
+            KeyCode::Char('m') | KeyCode::F(6) => {
+                self.transfer_to_other_pane(true)?;
+            }
+            KeyCode::Char('X') | KeyCode::Delete => {
+                self.delete_selection()?;
+            }
+            KeyCode::Char('n') if self.focus == FileUIFocus::Local => {
+                self.prompt = Some(Prompt::new("New directory", PromptAction::MakeDir));
+            }
+            KeyCode::Char('r') if self.focus == FileUIFocus::Local => {
+                self.start_local_rename()?;
+            }
+            KeyCode::Char('y') if self.focus == FileUIFocus::Local => self.yank_local(false),
+            KeyCode::Char('x') if self.focus == FileUIFocus::Local => self.yank_local(true),
+            KeyCode::Char('p') if self.focus == FileUIFocus::Local => {
+                self.paste_local()?;
+            }
+            KeyCode::Char('.') if self.focus == FileUIFocus::Local => {
+                self.local.toggle_hidden()?;
+            }
+            KeyCode::Char('u') if self.focus == FileUIFocus::Local => {
+                self.upload_selection(false)?;
+            }
+            KeyCode::Char('d') if self.focus == FileUIFocus::Remote => {
+                self.download_selection(false, false)?;
+            }
+            KeyCode::Char('e') if self.focus == FileUIFocus::Remote => {
+                self.download_selection(true, false)?;
+            }
+            KeyCode::Char('f') if self.focus == FileUIFocus::Remote => {
+                self.toggle_pinned()?;
+            }
+            KeyCode::Char('t') if self.focus == FileUIFocus::Remote => {
+                self.prompt = Some(Prompt::new("Add tag", PromptAction::AddTag));
+            }
+            KeyCode::Char('T') if self.focus == FileUIFocus::Remote => {
+                self.prompt = Some(Prompt::new("Remove tag", PromptAction::RemoveTag));
+            }
+            KeyCode::Char('H') if self.focus == FileUIFocus::Remote => {
+                self.export_highlights()?;
+            }
+            KeyCode::Char('E') if self.focus == FileUIFocus::Remote => {
+                self.export_typed_text()?;
+            }
+            KeyCode::Char('G') if self.focus == FileUIFocus::Remote => {
+                self.start_merge()?;
+            }
+            KeyCode::Char('v') if self.focus == FileUIFocus::Remote => {
+                self.save_data.remote_tree = !self.save_data.remote_tree;
+                self.remote.set_tree_mode(self.save_data.remote_tree)?;
+                self.save_data.save()?;
+            }
+            KeyCode::Right if self.focus == FileUIFocus::Remote => {
+                self.remote.toggle_expanded(Some(true))?;
+            }
+            KeyCode::Left if self.focus == FileUIFocus::Remote => {
+                self.remote.collapse()?;
+            }
+            KeyCode::Char('L') => {
+                self.change_layout(|layout| layout.cycle_mode())?;
+                self.notify(
+                    &format!("Layout: {}", self.save_data.layout.mode.get_text()),
+                    NotificationType::Message,
+                );
+            }
+            KeyCode::Char('<') => {
+                self.change_layout(|layout| layout.resize(-5))?;
+            }
+            KeyCode::Char('>') => {
+                self.change_layout(|layout| layout.resize(5))?;
+            }
+            KeyCode::Char('o') => {
+                self.change_sort_order(|order| order.cycle_mode())?;
+            }
+            KeyCode::Char('O') => {
+                self.change_sort_order(|order| order.ascending = !order.ascending)?;
+            }
+            KeyCode::Char('P') if self.focus == FileUIFocus::Remote => {
+                self.change_sort_order(|order| order.pinned_first = !order.pinned_first)?;
+            }
             _ => (),
         };
 
@@ -137,15 +1791,40 @@ impl FileUI {
 }
 
 // Helper function to give a FileUI struct
-pub fn file_ui(db: Arc<rusqlite::Connection>) -> Result<FileUI, crate::intern_error::Error> {
+pub fn file_ui(
+    db: Rc<rusqlite::Connection>,
+    events: UnboundedSender<AppEvent>,
+) -> Result<FileUI, crate::intern_error::Error> {
+    let save_data = SaveData::load();
+
     let mut ui = FileUI {
+        db: Rc::clone(&db),
+        events: events.clone(),
         local: DirBlock::new("dir", None),
-        remote: DBBlock::new("db", Some(db)),
+        remote: DBBlock::new("My files", Some(db)),
+        transfers: TransferManager::new(events),
         focus: FileUIFocus::Local,
+        save_data,
+        prompt: None,
+        device_pane: None,
+        template_draft: None,
+        merge_draft: None,
+        clipboard: None,
+        status: StatusBar::default(),
+        last_click: None,
+        last_pane: FileUIFocus::Local,
+        preview: Preview::default(),
+        inbox: InboxWatcher::default(),
+        library_loaded: false,
     };
 
+    ui.status.last_sync = ui.save_data.last_sync;
+
+    ui.local.set_sort_order(ui.save_data.local_sort);
+    ui.remote.set_sort_order(ui.save_data.remote_sort);
+
     ui.local.resolve()?;
-    ui.remote.resolve()?;
+    ui.remote.set_tree_mode(ui.save_data.remote_tree)?;
 
     Ok(ui)
 }
@@ -11,7 +11,7 @@ You should have received a copy of the GNU General Public License along with Not
 */
 
 use rusqlite::Connection;
-use std::{cmp::Ordering, sync::Arc};
+use std::{cmp::Ordering, rc::Rc};
 use tui::{
     layout::Rect,
     style::{Modifier, Style},
@@ -21,12 +21,17 @@ use tui::{
 use crate::{
     fs_interface::MetadataType,
     intern_error::{self, Error},
+    transfer::format_size,
 };
 
-use super::{super::config, file_item::FileItem, CursorDirection};
+use super::{
+    super::config,
+    file_item::{FileItem, SortOrder},
+    CursorDirection,
+};
 
 pub trait FSListBlock {
-    fn new(title: &'static str, db_conn: Option<Arc<Connection>>) -> Self
+    fn new(title: &'static str, db_conn: Option<Rc<Connection>>) -> Self
     where
         Self: Sized;
 
@@ -50,6 +55,9 @@ pub trait FSListBlock {
     fn get_cursor_idx(&self) -> usize;
     fn set_cursor_idx(&mut self, new_idx: usize);
 
+    fn get_sort_order(&self) -> SortOrder;
+    fn set_sort_order(&mut self, order: SortOrder);
+
     fn set_parent(&mut self, new_parent: FileItem) -> Result<(), intern_error::Error>;
 
     fn get_cursor_selection(&self) -> Option<&FileItem> {
@@ -60,40 +68,137 @@ pub trait FSListBlock {
     // Does it??
     fn get_cursor_selection_mut(&mut self) -> Option<&mut FileItem>;
 
+    fn is_selected(&self, item: &FileItem) -> bool {
+        self.get_selected_content().contains(item)
+    }
+
+    // "../" and anything else that isn't a real file can't be acted on
+    fn is_selectable(&self, item: &FileItem) -> bool {
+        item.file_type != MetadataType::ReturnType
+    }
+
     fn add_selected_content(&mut self, new_item: FileItem) {
-        self.get_selected_content_mut().push(new_item);
+        if !self.is_selected(&new_item) {
+            self.get_selected_content_mut().push(new_item);
+        }
     }
 
     fn remove_selected_content(
         &mut self,
-        target_item: FileItem,
+        target_item: &FileItem,
     ) -> Result<(), intern_error::Error> {
-        match self.get_selected_content_mut().binary_search(&target_item) {
-            Err(_) => Err(Error::VecRemoveError(self.get_cursor_idx())),
-            Ok(idx) => {
+        match self
+            .get_selected_content()
+            .iter()
+            .position(|item| item == target_item)
+        {
+            None => Err(Error::VecRemoveError(self.get_cursor_idx())),
+            Some(idx) => {
                 self.get_selected_content_mut().remove(idx);
                 Ok(())
             }
         }
     }
 
+    // The selection outlives the listing it was made in. Mark whatever is selected in
+    // freshly resolved content and refresh the selected copies with what was just read.
+    fn sync_selection(&mut self) {
+        let mut selected = self.get_selected_content().clone();
+
+        for item in self.get_resolved_content_mut() {
+            item.highlighted = false;
+
+            if let Some(copy) = selected.iter_mut().find(|copy| **copy == *item) {
+                item.highlighted = true;
+                *copy = item.clone();
+            }
+        }
+
+        *self.get_selected_content_mut() = selected;
+    }
+
+    fn clear_selection(&mut self) {
+        self.clear_selected_content();
+        self.sync_selection();
+    }
+
+    // What an action applies to, the selection or the item under the cursor
+    fn get_targets(&self) -> Vec<FileItem> {
+        if self.get_selected_content().is_empty() {
+            self.get_cursor_selection()
+                .filter(|item| self.is_selectable(item))
+                .cloned()
+                .into_iter()
+                .collect()
+        } else {
+            self.get_selected_content().clone()
+        }
+    }
+
     fn resolve(&mut self) -> Result<(), intern_error::Error>;
 
-    fn generate_list(&self, render_area: Rect) -> Result<Vec<ListItem>, intern_error::Error> {
+    fn sort_content(&mut self) {
+        let order = self.get_sort_order();
+
+        self.get_resolved_content_mut()
+            .sort_by(|a, b| order.compare(a, b));
+    }
+
+    // Re-sort in place, keeping the cursor on the item it was on
+    fn apply_sort_order(&mut self, order: SortOrder) {
+        let current = self
+            .get_cursor_selection()
+            .map(|item| (item.path.clone(), item.uuid.clone()));
+
+        self.set_sort_order(order);
+        self.sort_content();
+
+        if let Some((path, uuid)) = current {
+            if let Some(idx) = self
+                .get_resolved_content()
+                .iter()
+                .position(|item| item.path == path && item.uuid == uuid)
+            {
+                self.set_cursor_idx(idx);
+            }
+        }
+
+        self.keep_cursor_visible();
+    }
+
+    // Scroll just enough for the cursor to be on screen
+    fn keep_cursor_visible(&mut self) {
+        let adj_height = usize::from(
+            self.get_render_area()
+                .height
+                .saturating_sub(super::WIDGET_OFFSET),
+        );
+        let cursor = self.get_cursor_idx();
+
+        if cursor < self.get_offset_pos() {
+            self.set_offset_pos(cursor);
+        } else if cursor > self.get_offset_pos() + adj_height {
+            self.set_offset_pos(cursor - adj_height);
+        }
+    }
+
+    fn generate_list(&self, render_area: Rect) -> Result<Vec<ListItem<'_>>, intern_error::Error> {
         let mut result: Vec<ListItem> = Vec::new();
 
-        let adj_height = usize::from(render_area.height - super::WIDGET_OFFSET);
+        let adj_height = usize::from(render_area.height.saturating_sub(super::WIDGET_OFFSET));
 
         for (idx, item) in self.get_resolved_content().iter().enumerate() {
             if idx < self.get_offset_pos() || idx > self.get_offset_pos() + adj_height {
                 continue;
             }
 
-            let mut file_name = match item.file_type {
-                MetadataType::CollectionType => String::from("/"),
-                MetadataType::ReturnType => String::from("../"),
-                _ => String::from(" "),
-            };
+            let mut file_name = item.tree_prefix.clone();
+
+            file_name.push_str(match item.file_type {
+                MetadataType::CollectionType => "/",
+                MetadataType::ReturnType => "../",
+                _ => " ",
+            });
 
             let mut style = match item.file_type {
                 MetadataType::CollectionType | MetadataType::ReturnType => {
@@ -113,6 +218,18 @@ pub trait FSListBlock {
                 file_name.push_str(&item.name);
             };
 
+            if let Some(count) = item.doc_count {
+                file_name.push_str(&format!(" ({})", count));
+            }
+
+            if item.pinned {
+                file_name.push_str(" ★");
+            }
+
+            for tag in &item.tags {
+                file_name.push_str(&format!(" #{}", tag));
+            }
+
             if self.get_focus() && idx.cmp(&self.get_cursor_idx()) == Ordering::Equal {
                 style = style.add_modifier(Modifier::REVERSED)
             };
@@ -123,13 +240,33 @@ pub trait FSListBlock {
         Ok(result)
     }
 
-    fn render(&mut self, render_area: Rect) -> Result<List, Error> {
+    fn render(&mut self, render_area: Rect) -> Result<List<'_>, Error> {
         self.set_render_area(render_area);
 
+        let mut suffix = format!(" [{}]", self.get_sort_order().indicator());
+
+        if !self.get_selected_content().is_empty() {
+            suffix.push_str(&format!(" {} selected", self.get_selected_content().len()));
+        }
+
+        // Long paths lose their start rather than the end, which is where you are
+        let room = (render_area.width as usize)
+            .saturating_sub(2)
+            .saturating_sub(suffix.chars().count());
+        let name = self.get_name();
+        let length = name.chars().count();
+
+        let title = if length > room {
+            let tail: String = name.chars().skip(length + 1 - room.max(1)).collect();
+            format!("…{}{}", tail, suffix)
+        } else {
+            format!("{}{}", name, suffix)
+        };
+
         Ok(List::new(self.generate_list(render_area).unwrap())
             .block(
                 Block::default()
-                    .title(self.get_name())
+                    .title(title)
                     .borders(Borders::ALL)
                     .border_type(BorderType::Double),
             )
@@ -140,6 +277,46 @@ pub trait FSListBlock {
             ))
     }
 
+    // Item shown on a screen row, if the row is inside the list
+    fn idx_at_row(&self, row: u16) -> Option<usize> {
+        let area = self.get_render_area();
+
+        if row <= area.y || row + 1 >= area.bottom() {
+            return None;
+        }
+
+        let idx = self.get_offset_pos() + usize::from(row - area.y - 1);
+
+        (idx < self.get_resolved_content().len()).then_some(idx)
+    }
+
+    // Item count and selection size for the status bar
+    fn status_text(&self) -> String {
+        let items = self
+            .get_resolved_content()
+            .iter()
+            .filter(|item| self.is_selectable(item))
+            .count();
+        let selected = self.get_selected_content();
+
+        if selected.is_empty() {
+            return format!("{} items", items);
+        }
+
+        let size: u64 = selected
+            .iter()
+            .filter(|item| item.file_type == MetadataType::DocumentType)
+            .map(|item| item.size)
+            .sum();
+
+        format!(
+            "{} items, {} selected ({})",
+            items,
+            selected.len(),
+            format_size(size)
+        )
+    }
+
     fn cursor_move(&mut self, direction: CursorDirection) {
         let delta: isize = match direction {
             CursorDirection::Down => 1,
@@ -148,11 +325,15 @@ pub trait FSListBlock {
             CursorDirection::PgUp => -15,
         };
 
-        let adj_height = usize::from(self.get_render_area().height - super::WIDGET_OFFSET);
+        let adj_height = usize::from(
+            self.get_render_area()
+                .height
+                .saturating_sub(super::WIDGET_OFFSET),
+        );
 
         let new_pos = if let Some(val) = self.get_cursor_idx().checked_add_signed(delta) {
-            if val > self.get_resolved_content().len() - 1 {
-                self.get_resolved_content().len() - 1
+            if val > self.get_resolved_content().len().saturating_sub(1) {
+                self.get_resolved_content().len().saturating_sub(1)
             } else {
                 val
             }
@@ -206,21 +387,44 @@ pub trait FSListBlock {
     }
 
     fn toggle_highlight_selection(&mut self) -> Result<(), Error> {
-        let current_selection = { self.get_cursor_selection().unwrap().clone() };
-        // let idx = { self.get_cursor_idx() };
-        match self.get_cursor_selection_mut() {
-            Some(val) => {
-                if !val.highlighted {
-                    val.highlighted = true;
-                    self.add_selected_content(current_selection);
-                } else {
-                    val.highlighted = false;
-                    // Unsure if a result is needed for removal
-                    let _ = self.remove_selected_content(current_selection);
-                }
-                Ok(())
+        let current_selection = match self.get_cursor_selection() {
+            Some(val) => val.clone(),
+            None => return Err(intern_error::Error::VecAccessError(self.get_cursor_idx())),
+        };
+
+        if !self.is_selectable(&current_selection) {
+            return Ok(());
+        }
+
+        if self.is_selected(&current_selection) {
+            self.remove_selected_content(&current_selection)?;
+        } else {
+            self.add_selected_content(current_selection);
+        }
+
+        self.sync_selection();
+
+        Ok(())
+    }
+
+    // Select everything in the current listing, or deselect it if it already all is
+    fn toggle_select_all(&mut self) {
+        let items: Vec<FileItem> = self
+            .get_resolved_content()
+            .iter()
+            .filter(|item| self.is_selectable(item))
+            .cloned()
+            .collect();
+
+        if items.iter().all(|item| self.is_selected(item)) {
+            self.get_selected_content_mut()
+                .retain(|selected| !items.contains(selected));
+        } else {
+            for item in items {
+                self.add_selected_content(item);
             }
-            None => Err(intern_error::Error::VecAccessError(self.get_cursor_idx())),
         }
+
+        self.sync_selection();
     }
 }
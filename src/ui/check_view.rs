@@ -0,0 +1,147 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use tui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    widgets::{Block, BorderType, Borders, List, ListItem},
+};
+
+use crate::{
+    integrity::{CheckSource, Problem},
+    intern_error::Severity,
+};
+
+use super::{super::config, CursorDirection};
+
+// Problems found by an integrity check, shown in place of the remote pane
+pub struct CheckView {
+    pub source: CheckSource,
+    problems: Vec<Problem>,
+    cursor_idx: usize,
+    offset_pos: usize,
+    render_area: Rect,
+    pub focused: bool,
+}
+
+impl CheckView {
+    pub fn new(source: CheckSource, problems: Vec<Problem>) -> Self {
+        Self {
+            source,
+            problems,
+            cursor_idx: 0,
+            offset_pos: 0,
+            render_area: Rect::default(),
+            focused: false,
+        }
+    }
+
+    // The problem under the cursor, or every one of them, that has a fix
+    pub fn get_fixable(&self, all: bool) -> Vec<Problem> {
+        self.problems
+            .iter()
+            .enumerate()
+            .filter(|(idx, problem)| problem.fix.is_some() && (all || *idx == self.cursor_idx))
+            .map(|(_, problem)| problem.clone())
+            .collect()
+    }
+
+    // Drop problems once their fixes are on the way
+    pub fn remove_fixed(&mut self, fixed: &[Problem]) {
+        self.problems.retain(|problem| {
+            !fixed
+                .iter()
+                .any(|other| other.uuid == problem.uuid && other.fix == problem.fix)
+        });
+
+        self.cursor_idx = self.cursor_idx.min(self.problems.len().saturating_sub(1));
+    }
+
+    pub fn cursor_move(&mut self, direction: CursorDirection) {
+        let delta: isize = match direction {
+            CursorDirection::Down => 1,
+            CursorDirection::Up => -1,
+            CursorDirection::PgDn => 15,
+            CursorDirection::PgUp => -15,
+        };
+
+        self.cursor_idx = self
+            .cursor_idx
+            .saturating_add_signed(delta)
+            .min(self.problems.len().saturating_sub(1));
+
+        let adj_height = usize::from(self.render_area.height.saturating_sub(super::WIDGET_OFFSET));
+
+        if self.cursor_idx < self.offset_pos {
+            self.offset_pos = self.cursor_idx;
+        } else if self.cursor_idx > self.offset_pos + adj_height {
+            self.offset_pos = self.cursor_idx - adj_height;
+        }
+    }
+
+    pub fn render(&mut self, render_area: Rect) -> List<'_> {
+        self.render_area = render_area;
+
+        let adj_height = usize::from(render_area.height.saturating_sub(super::WIDGET_OFFSET));
+
+        let items: Vec<ListItem> = self
+            .problems
+            .iter()
+            .enumerate()
+            .skip(self.offset_pos)
+            .take(adj_height + 1)
+            .map(|(idx, problem)| {
+                let mut style = match problem.severity {
+                    Severity::High | Severity::Fatal => Style::default().fg(config::THEME.alert),
+                    Severity::Mid | Severity::Transient => {
+                        Style::default().fg(config::THEME.highlight)
+                    }
+                    Severity::Low => Style::default(),
+                };
+
+                if self.focused && idx == self.cursor_idx {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+
+                let mut text = format!(
+                    "{:<6} {}: {}",
+                    problem.severity_text(),
+                    problem.path,
+                    problem.description
+                );
+
+                if let Some(fix) = &problem.fix {
+                    text.push_str(&format!(" [fix: {}]", fix.get_text()));
+                }
+
+                ListItem::new(text).style(style)
+            })
+            .collect();
+
+        List::new(items)
+            .block(
+                Block::default()
+                    .title(format!(
+                        "Check of the {} [{} problems]",
+                        self.source.get_text(),
+                        self.problems.len()
+                    ))
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Double),
+            )
+            .style(
+                Style::default()
+                    .fg(config::THEME.foreground)
+                    .bg(config::THEME.background),
+            )
+    }
+}
@@ -10,9 +10,13 @@ of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Publ
 You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
 */
 
-use std::{path::Path, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    rc::Rc,
+};
 
-use rusqlite::{named_params, Connection};
+use rusqlite::{named_params, params_from_iter, Connection, Row};
 use tui::layout::Rect;
 
 use crate::{
@@ -20,7 +24,100 @@ use crate::{
     intern_error::{self},
 };
 
-use super::{block::FSListBlock, file_item::FileItem};
+use super::{
+    block::FSListBlock,
+    file_item::{FileItem, SortOrder},
+};
+
+// Parent ids of the virtual collections. Favourites lists every pinned object like the
+// tablet's sidebar, Tags has one collection per tag holding the documents tagged with
+// it. Both leave out whatever is in the trash.
+pub const FAVOURITES: &str = "favourites";
+pub const TAGS: &str = "tags";
+const TAG_PREFIX: &str = "tag:";
+
+// Deeper than any real library, stops a parent loop in broken metadata from recursing forever
+const MAX_TREE_DEPTH: usize = 64;
+
+fn select_items(filter: &str) -> String {
+    format!(
+        "SELECT uuid, name, object_type, last_modified, last_opened, size, file_type, pinned,
+        (SELECT group_concat(tag, char(31)) FROM tags WHERE tags.uuid=objects.uuid AND page=''),
+        parent
+        FROM objects WHERE {}",
+        filter
+    )
+}
+
+fn item_from_row(r: &Row) -> rusqlite::Result<FileItem> {
+    Ok(FileItem {
+        uuid: r.get(0)?,
+        path: Path::new(".").into(), // Maybe make the path field an option in the future
+        name: r.get(1)?,
+        file_type: MetadataType::from(r.get::<usize, String>(2)?),
+        last_modified: r.get::<usize, String>(3)?.parse().unwrap_or(0),
+        last_opened: r.get::<usize, String>(4)?.parse().unwrap_or(0),
+        size: r.get(5)?,
+        format: r.get(6)?,
+        pinned: r.get(7)?,
+        tags: r
+            .get::<usize, Option<String>>(8)?
+            .map(|tags| tags.split('\u{1f}').map(String::from).collect())
+            .unwrap_or_default(),
+        ..Default::default()
+    })
+}
+
+// A collection and the ones above it up to the top of the library, walked through `parent`
+fn ancestors(db: &Connection, uuid: &str) -> Vec<(String, String)> {
+    let mut found = Vec::new();
+    let mut uuid = String::from(uuid);
+
+    // Bounded so a parent loop in broken metadata can't hang the UI
+    while uuid != "root" && found.len() < MAX_TREE_DEPTH {
+        match db.query_row(
+            "SELECT name, parent FROM objects WHERE uuid=?1",
+            [&uuid],
+            |r| Ok((r.get::<usize, String>(0)?, r.get::<usize, String>(1)?)),
+        ) {
+            Ok((name, parent)) => {
+                found.push((uuid, name));
+                uuid = parent;
+            }
+            Err(_) => break,
+        }
+    }
+
+    found
+}
+
+// Documents in a collection and everything below it, filled in for every collection on the way
+fn document_count(
+    uuid: &str,
+    children: &HashMap<String, Vec<FileItem>>,
+    counts: &mut HashMap<String, usize>,
+    depth: usize,
+) -> usize {
+    if let Some(count) = counts.get(uuid) {
+        return *count;
+    }
+
+    let mut count = 0;
+
+    if depth <= MAX_TREE_DEPTH {
+        for child in children.get(uuid).into_iter().flatten() {
+            count += match child.file_type {
+                MetadataType::CollectionType => {
+                    document_count(&child.uuid, children, counts, depth + 1)
+                }
+                _ => 1,
+            };
+        }
+    }
+
+    counts.insert(String::from(uuid), count);
+    count
+}
 
 pub struct DBBlock {
     name: String,
@@ -28,15 +125,313 @@ pub struct DBBlock {
     cursor_idx: usize,
     pub focused: bool,
     content: Vec<FileItem>,
-    db_connection: Option<Arc<Connection>>,
+    db_connection: Option<Rc<Connection>>,
     selected_content: Vec<FileItem>,
     offset_pos: usize,
     render_area: Rect,
+    sort_order: SortOrder,
+    breadcrumb: String,
+    tree_mode: bool,
+    expanded: HashSet<String>,
+    // Parent of every object, filled in while building the tree
+    tree_parents: HashMap<String, String>,
+}
+
+impl DBBlock {
+    // Virtual collections gather objects from all over the library, so nothing can be
+    // created in them
+    pub fn in_virtual_view(&self) -> bool {
+        Self::is_virtual_uuid(&self.parent)
+    }
+
+    pub fn is_virtual(item: &FileItem) -> bool {
+        Self::is_virtual_uuid(&item.uuid)
+    }
+
+    fn is_virtual_uuid(uuid: &str) -> bool {
+        uuid == FAVOURITES || uuid == TAGS || uuid.starts_with(TAG_PREFIX)
+    }
+
+    fn virtual_parent(&self) -> String {
+        if self.parent.starts_with(TAG_PREFIX) {
+            String::from(TAGS)
+        } else {
+            String::from("root")
+        }
+    }
+
+    fn virtual_collections() -> Vec<FileItem> {
+        [("Favourites", FAVOURITES), ("Tags", TAGS)]
+            .into_iter()
+            .map(|(name, uuid)| {
+                FileItem::new()
+                    .name(name)
+                    .uuid(String::from(uuid))
+                    .file_type(MetadataType::CollectionType)
+                    .path(Path::new(".").into())
+            })
+            .collect()
+    }
+
+    // The tree replaces drilling in and out of the library, the virtual collections
+    // still open as flat lists
+    pub fn tree_active(&self) -> bool {
+        self.tree_mode && !self.in_virtual_view()
+    }
+
+    // Switching keeps the cursor on the same item. The tree opens up to the collection
+    // that was open, the flat list opens the collection holding the item.
+    pub fn set_tree_mode(&mut self, tree_mode: bool) -> Result<(), intern_error::Error> {
+        let focus = if tree_mode {
+            Some(self.parent.clone())
+        } else {
+            self.get_cursor_selection().map(|item| item.uuid.clone())
+        };
+
+        if tree_mode && !self.in_virtual_view() {
+            if let Some(db) = &self.db_connection {
+                for (uuid, _) in ancestors(db, &self.parent) {
+                    self.expanded.insert(uuid);
+                }
+            }
+
+            self.parent = String::from("root");
+        } else if self.tree_active() {
+            self.parent = focus
+                .as_ref()
+                .and_then(|uuid| self.tree_parents.get(uuid))
+                .cloned()
+                .unwrap_or_else(|| String::from("root"));
+        }
+
+        self.tree_mode = tree_mode;
+        self.set_cursor_idx(0);
+        self.set_offset_pos(0);
+        self.resolve()?;
+
+        if let Some(idx) =
+            focus.and_then(|uuid| self.content.iter().position(|item| item.uuid == uuid))
+        {
+            self.set_cursor_idx(idx);
+            self.keep_cursor_visible();
+        }
+
+        Ok(())
+    }
+
+    // Open or close the collection under the cursor, `expand` forces one or the other.
+    // Returns false when there's no real collection under the cursor.
+    pub fn toggle_expanded(&mut self, expand: Option<bool>) -> Result<bool, intern_error::Error> {
+        let uuid = match self.get_cursor_selection() {
+            Some(item)
+                if self.tree_active()
+                    && item.file_type == MetadataType::CollectionType
+                    && !Self::is_virtual(item) =>
+            {
+                item.uuid.clone()
+            }
+            _ => return Ok(false),
+        };
+
+        let expand = expand.unwrap_or(!self.expanded.contains(&uuid));
+
+        if expand {
+            self.expanded.insert(uuid);
+        } else {
+            self.expanded.remove(&uuid);
+        }
+
+        self.resolve()?;
+
+        Ok(true)
+    }
+
+    // Close the collection under the cursor, or step out to the one holding it
+    pub fn collapse(&mut self) -> Result<(), intern_error::Error> {
+        match self.get_cursor_selection() {
+            Some(item) if self.expanded.contains(&item.uuid) => {
+                self.toggle_expanded(Some(false))?;
+            }
+            _ => self.cursor_to_parent(),
+        }
+
+        Ok(())
+    }
+
+    // Move the cursor up to the collection holding the item under it
+    fn cursor_to_parent(&mut self) {
+        let Some(parent) = self
+            .get_cursor_selection()
+            .and_then(|item| self.tree_parents.get(&item.uuid))
+        else {
+            return;
+        };
+
+        if let Some(idx) = self.content.iter().position(|item| item.uuid == *parent) {
+            self.set_cursor_idx(idx);
+            self.keep_cursor_visible();
+        }
+    }
+
+    // Collection new documents go into: the open one, or in the tree the one under the
+    // cursor or holding the document under it
+    pub fn target_collection(&self) -> String {
+        if self.in_virtual_view() {
+            return String::from("root");
+        } else if !self.tree_active() {
+            return self.parent.clone();
+        }
+
+        match self.get_cursor_selection() {
+            Some(item) if Self::is_virtual(item) => String::from("root"),
+            Some(item) if item.file_type == MetadataType::CollectionType => item.uuid.clone(),
+            Some(item) => self
+                .tree_parents
+                .get(&item.uuid)
+                .cloned()
+                .unwrap_or_else(|| String::from("root")),
+            None => String::from("root"),
+        }
+    }
+
+    // Every object reachable from the top of the library, children sorted under their
+    // collection and shown only while it's expanded
+    fn resolve_tree(&mut self, db: &Connection) -> Result<(), intern_error::Error> {
+        let mut stmt = db.prepare(&select_items("1"))?;
+
+        let rows = stmt.query_map((), |r| Ok((item_from_row(r)?, r.get::<usize, String>(9)?)))?;
+
+        let mut children: HashMap<String, Vec<FileItem>> = HashMap::new();
+        self.tree_parents = HashMap::new();
+
+        for (item, parent) in rows.flatten() {
+            self.tree_parents.insert(item.uuid.clone(), parent.clone());
+            children.entry(parent).or_default().push(item);
+        }
+
+        let order = self.sort_order;
+
+        for list in children.values_mut() {
+            list.sort_by(|a, b| order.compare(a, b));
+        }
+
+        let mut counts = HashMap::new();
+        document_count("root", &children, &mut counts, 0);
+
+        self.push_subtree("root", "", &children, &counts, 0);
+
+        Ok(())
+    }
+
+    fn push_subtree(
+        &mut self,
+        parent: &str,
+        guides: &str,
+        children: &HashMap<String, Vec<FileItem>>,
+        counts: &HashMap<String, usize>,
+        depth: usize,
+    ) {
+        let Some(list) = children.get(parent) else {
+            return;
+        };
+
+        if depth > MAX_TREE_DEPTH {
+            return;
+        }
+
+        for (idx, child) in list.iter().enumerate() {
+            let last = idx + 1 == list.len();
+            let collection = child.file_type == MetadataType::CollectionType;
+            let expanded = collection && self.expanded.contains(&child.uuid);
+
+            let mut item = child.clone();
+            item.tree_prefix = format!(
+                "{}{}{}",
+                guides,
+                if last { "└─" } else { "├─" },
+                match (collection, expanded) {
+                    (true, true) => "▾",
+                    (true, false) => "▸",
+                    (false, _) => " ",
+                }
+            );
+
+            if collection {
+                item.doc_count = counts.get(&child.uuid).copied();
+            }
+
+            self.content.push(item);
+
+            if expanded {
+                self.push_subtree(
+                    &child.uuid,
+                    &format!("{}{}", guides, if last { "  " } else { "│ " }),
+                    children,
+                    counts,
+                    depth + 1,
+                );
+            }
+        }
+    }
+
+    // Path from the top of the library to the open collection
+    fn breadcrumb(&self, db: &Connection) -> String {
+        if self.parent == FAVOURITES {
+            return format!("{} / Favourites", self.name);
+        } else if self.parent == TAGS {
+            return format!("{} / Tags", self.name);
+        } else if let Some(tag) = self.parent.strip_prefix(TAG_PREFIX) {
+            return format!("{} / Tags / {}", self.name, tag);
+        }
+
+        let mut names: Vec<String> = ancestors(db, &self.parent)
+            .into_iter()
+            .map(|(_, name)| name)
+            .collect();
+
+        names.push(self.name.clone());
+        names.reverse();
+        names.join(" / ")
+    }
+
+    // One collection per tag, counting documents tagged as a whole or on any page
+    fn resolve_tags(&mut self, db: &Connection) -> Result<(), intern_error::Error> {
+        self.content.push(FileItem {
+            path: Path::new(".").into(),
+            file_type: MetadataType::ReturnType,
+            uuid: self.virtual_parent(),
+            ..Default::default()
+        });
+
+        let mut stmt = db.prepare(
+            "SELECT tags.tag, COUNT(DISTINCT tags.uuid) FROM tags
+            JOIN objects ON objects.uuid = tags.uuid
+            WHERE objects.parent != 'trash'
+            GROUP BY tags.tag",
+        )?;
+
+        let tags = stmt.query_map((), |r| {
+            Ok((r.get::<usize, String>(0)?, r.get::<usize, u64>(1)?))
+        })?;
+
+        for (tag, count) in tags.flatten() {
+            self.content.push(
+                FileItem::new()
+                    .name(&tag)
+                    .uuid(format!("{}{}", TAG_PREFIX, tag))
+                    .file_type(MetadataType::CollectionType)
+                    .size(count)
+                    .path(Path::new(".").into()),
+            );
+        }
+
+        Ok(())
+    }
 }
 
 impl FSListBlock for DBBlock {
     // Figure out what the fuck I'm trying to do with lifetimes
-    fn new(title: &'static str, db_conn: Option<Arc<Connection>>) -> Self {
+    fn new(title: &'static str, db_conn: Option<Rc<Connection>>) -> Self {
         DBBlock {
             name: String::from(title),
             parent: String::from("root"),
@@ -47,11 +442,32 @@ impl FSListBlock for DBBlock {
             selected_content: Vec::new(),
             offset_pos: 0,
             render_area: Rect::default(),
+            sort_order: SortOrder::default(),
+            breadcrumb: String::from(title),
+            tree_mode: false,
+            expanded: HashSet::new(),
+            tree_parents: HashMap::new(),
         }
     }
 
     fn get_name(&self) -> String {
-        self.name.clone()
+        if self.tree_active() {
+            format!("{} (tree)", self.breadcrumb)
+        } else {
+            self.breadcrumb.clone()
+        }
+    }
+
+    // The tree is sorted level by level as it's built
+    fn sort_content(&mut self) {
+        if self.tree_active() {
+            if let Err(why) = self.resolve() {
+                log::warn!("Couldn't rebuild the tree: {}", why);
+            }
+        } else {
+            let order = self.sort_order;
+            self.content.sort_by(|a, b| order.compare(a, b));
+        }
     }
 
     fn get_focus(&self) -> bool {
@@ -90,6 +506,18 @@ impl FSListBlock for DBBlock {
         self.cursor_idx = new_idx
     }
 
+    fn get_sort_order(&self) -> SortOrder {
+        self.sort_order
+    }
+
+    fn set_sort_order(&mut self, order: SortOrder) {
+        self.sort_order = order
+    }
+
+    fn is_selectable(&self, item: &FileItem) -> bool {
+        item.file_type != MetadataType::ReturnType && !Self::is_virtual(item)
+    }
+
     fn get_parent(&self) -> FileItem {
         FileItem::new().uuid(self.parent.clone())
     }
@@ -113,7 +541,7 @@ impl FSListBlock for DBBlock {
     }
 
     fn get_cursor_selection_mut(&mut self) -> Option<&mut FileItem> {
-        self.selected_content.get_mut(self.cursor_idx)
+        self.content.get_mut(self.cursor_idx)
     }
 
     fn resolve(&mut self) -> Result<(), crate::intern_error::Error> {
@@ -126,42 +554,72 @@ impl FSListBlock for DBBlock {
         };
 
         // If we pass the above check we have db, so we can safely create a local reference unwrapped
-        let db = self.db_connection.as_ref().unwrap();
+        let db = Rc::clone(self.db_connection.as_ref().unwrap());
 
-        let mut stmt = db.prepare("SELECT uuid, name, object_type FROM objects WHERE parent=?1")?;
+        self.breadcrumb = self.breadcrumb(&db);
 
-        let file_iter = stmt.query_map([&self.parent], |r| {
-            Ok(FileItem {
-                uuid: r.get(0)?,
-                path: Path::new(".").into(), // Maybe make the path field an option in the future
-                name: r.get(1)?,
-                file_type: MetadataType::from(r.get::<usize, String>(2)?),
-                highlighted: false,
-            })
-        })?;
+        if self.tree_active() {
+            self.content.extend(Self::virtual_collections());
+            self.resolve_tree(&db)?;
+            self.sync_selection();
+            return Ok(());
+        }
 
-        match db.query_row(
-            "SELECT parent FROM objects WHERE uuid=:uuid",
-            named_params! {":uuid" : self.parent},
-            |r| Ok((r.get::<usize, String>(0), r.get::<usize, String>(1))),
-        ) {
-            Err(_) => (),
-            Ok(val) => {
-                self.content.push(FileItem {
-                    name: String::new(),
-                    path: Path::new(".").into(),
-                    file_type: MetadataType::ReturnType,
-                    uuid: val.0?,
-                    highlighted: false,
-                });
-            }
+        let (filter, params) = if self.parent == FAVOURITES {
+            ("pinned=1 AND parent != 'trash'", vec![])
+        } else if self.parent == TAGS {
+            self.resolve_tags(&db)?;
+            self.sort_content();
+            self.sync_selection();
+            return Ok(());
+        } else if let Some(tag) = self.parent.strip_prefix(TAG_PREFIX) {
+            (
+                "uuid IN (SELECT uuid FROM tags WHERE tag=?1) AND parent != 'trash'",
+                vec![tag.to_string()],
+            )
+        } else {
+            ("parent=?1", vec![self.parent.clone()])
         };
 
+        let mut stmt = db.prepare(&select_items(filter))?;
+
+        let file_iter = stmt.query_map(params_from_iter(params.iter()), item_from_row)?;
+
+        // Virtual collections have no row of their own to find the way back from
+        let back = if self.in_virtual_view() {
+            Some(Ok(self.virtual_parent()))
+        } else {
+            db.query_row(
+                "SELECT parent FROM objects WHERE uuid=:uuid",
+                named_params! {":uuid" : self.parent},
+                |r| Ok(r.get::<usize, String>(0)),
+            )
+            .ok()
+        };
+
+        if let Some(Ok(uuid)) = back {
+            self.content.push(FileItem {
+                name: String::new(),
+                path: Path::new(".").into(),
+                file_type: MetadataType::ReturnType,
+                uuid,
+                ..Default::default()
+            });
+        }
+
         for row in file_iter.flatten() {
             self.content.push(row);
         }
 
-        self.content.sort();
+        self.sort_content();
+        self.sync_selection();
+
+        // Kept above the real collections regardless of the sort order
+        if self.parent == "root" {
+            for (idx, item) in Self::virtual_collections().into_iter().enumerate() {
+                self.content.insert(idx, item);
+            }
+        }
 
         Ok(())
     }
@@ -10,19 +10,27 @@ of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Publ
 You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
 */
 
-use std::{fs, path::Path, sync::Arc};
+use std::{
+    fs,
+    path::Path,
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use tui::layout::Rect;
 
 use crate::{
     fs_interface::MetadataType,
-    intern_error::{self},
+    intern_error::{self, ErrorContext, ResultExt},
 };
 
-use super::{super::config, block::FSListBlock, file_item::FileItem};
+use super::{
+    super::config,
+    block::FSListBlock,
+    file_item::{FileItem, SortOrder},
+};
 
 pub struct DirBlock {
-    name: String,
     parent: Box<Path>,
     cursor_idx: usize,
     pub focused: bool,
@@ -31,25 +39,48 @@ pub struct DirBlock {
     selected_content: Vec<FileItem>,
     offset_pos: usize,
     render_area: Rect,
+    sort_order: SortOrder,
+    show_hidden: bool,
+}
+
+impl DirBlock {
+    pub fn show_hidden(&self) -> bool {
+        self.show_hidden
+    }
+
+    pub fn toggle_hidden(&mut self) -> Result<(), intern_error::Error> {
+        self.show_hidden = !self.show_hidden;
+        self.refresh_view()
+    }
 }
 
 impl FSListBlock for DirBlock {
-    fn new(title: &'static str, _: Option<Arc<rusqlite::Connection>>) -> Self {
+    // The title is always the open directory
+    fn new(_: &'static str, _: Option<Rc<rusqlite::Connection>>) -> Self {
+        let start = config::local_start_dir();
+
         DirBlock {
-            name: String::from(title),
-            parent: Path::new("/home/").into(),
+            parent: start.clone().into(),
             focused: false,
             cursor_idx: 0,
             content: Vec::new(),
-            last_path: Path::new("/home/").into(),
+            last_path: start.into(),
             selected_content: Vec::new(),
             offset_pos: 0,
             render_area: Rect::default(),
+            sort_order: SortOrder::default(),
+            show_hidden: config::SETTINGS.show_hidden_files,
         }
     }
 
     fn get_name(&self) -> String {
-        self.name.clone()
+        let home = std::env::var_os("HOME").filter(|home| !home.is_empty());
+
+        match home.and_then(|home| self.parent.strip_prefix(home).ok().map(Path::to_path_buf)) {
+            Some(rest) if rest.as_os_str().is_empty() => String::from("~"),
+            Some(rest) => format!("~/{}", rest.to_string_lossy()),
+            None => self.parent.to_string_lossy().to_string(),
+        }
     }
 
     fn get_focus(&self) -> bool {
@@ -92,6 +123,14 @@ impl FSListBlock for DirBlock {
         self.cursor_idx = new_idx
     }
 
+    fn get_sort_order(&self) -> SortOrder {
+        self.sort_order
+    }
+
+    fn set_sort_order(&mut self, order: SortOrder) {
+        self.sort_order = order
+    }
+
     // Probably doesn't need to be a result
     fn set_parent(&mut self, new_parent_file: FileItem) -> Result<(), intern_error::Error> {
         self.parent = new_parent_file.path;
@@ -117,7 +156,7 @@ impl FSListBlock for DirBlock {
     fn resolve(&mut self) -> Result<(), intern_error::Error> {
         self.content = Vec::new();
 
-        let paths = fs::read_dir(&self.parent)?;
+        let paths = fs::read_dir(&self.parent).context(ErrorContext::file(&self.parent))?;
 
         if self.parent != Path::new("/").into() {
             self.content
@@ -141,23 +180,42 @@ impl FSListBlock for DirBlock {
                     };
 
                     // TODO: Fix error handling
-                    if res_path.file_name().to_str().unwrap().starts_with('.')
-                        && !config::SETTINGS.show_hidden_files
+                    if res_path.file_name().to_str().unwrap().starts_with('.') && !self.show_hidden
                     {
                         continue;
                     }
 
+                    let metadata = res_path.metadata().ok();
+                    let millis = |time: std::io::Result<SystemTime>| {
+                        time.ok()
+                            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                            .map(|d| d.as_millis() as u64)
+                            .unwrap_or(0)
+                    };
+
+                    let path = res_path.path();
+
                     self.content.push(
                         FileItem::new()
                             .name(res_path.file_name().to_str().unwrap())
                             .file_type(MetadataType::from(res_path.file_type().unwrap()))
-                            .path(res_path.path().into()), // Fix
+                            .last_modified(metadata.as_ref().map_or(0, |m| millis(m.modified())))
+                            .last_opened(metadata.as_ref().map_or(0, |m| millis(m.accessed())))
+                            .size(metadata.as_ref().map_or(0, |m| m.len()))
+                            .format(
+                                &path
+                                    .extension()
+                                    .map(|ext| ext.to_string_lossy().to_string())
+                                    .unwrap_or_default(),
+                            )
+                            .path(path.into()), // Fix
                     );
                 }
             }
         }
 
-        self.content.sort();
+        self.sort_content();
+        self.sync_selection();
 
         self.last_path = self.parent.clone();
 
@@ -10,7 +10,9 @@ of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Publ
 You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
 */
 
-use std::path::Path;
+use std::{cmp::Ordering, path::Path};
+
+use serde::{Deserialize, Serialize};
 
 use crate::fs_interface::MetadataType;
 
@@ -21,6 +23,130 @@ pub struct FileItem {
     pub file_type: MetadataType,
     pub uuid: String,
     pub highlighted: bool,
+    // Milliseconds since the epoch, 0 if unknown
+    pub last_modified: u64,
+    pub last_opened: u64,
+    pub size: u64,
+    // Extension for local files, payload type (pdf, epub, notebook) for documents
+    pub format: String,
+    pub pinned: bool,
+    pub tags: Vec<String>,
+    // Indentation guides and expander drawn before the name in the tree view
+    pub tree_prefix: String,
+    // Documents in a collection and below it, shown in the tree view
+    pub doc_count: Option<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum SortMode {
+    #[default]
+    Name,
+    LastModified,
+    LastOpened,
+    Size,
+    Type,
+}
+
+// How a pane orders its items. "../" always comes first and collections
+// always come before documents, the mode only decides the order within those.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SortOrder {
+    pub mode: SortMode,
+    pub ascending: bool,
+    pub pinned_first: bool,
+}
+
+impl SortMode {
+    pub fn next(&self) -> SortMode {
+        match self {
+            Self::Name => Self::LastModified,
+            Self::LastModified => Self::LastOpened,
+            Self::LastOpened => Self::Size,
+            Self::Size => Self::Type,
+            Self::Type => Self::Name,
+        }
+    }
+
+    pub fn get_text(&self) -> &str {
+        match self {
+            Self::Name => "name",
+            Self::LastModified => "modified",
+            Self::LastOpened => "opened",
+            Self::Size => "size",
+            Self::Type => "type",
+        }
+    }
+
+    // Newest and largest first are the useful defaults, everything else reads A-Z
+    fn default_ascending(&self) -> bool {
+        matches!(self, Self::Name | Self::Type)
+    }
+}
+
+impl Default for SortOrder {
+    fn default() -> Self {
+        Self {
+            mode: SortMode::Name,
+            ascending: true,
+            pinned_first: false,
+        }
+    }
+}
+
+impl SortOrder {
+    // Move on to the next mode, starting it in its natural direction
+    pub fn cycle_mode(&mut self) {
+        self.mode = self.mode.next();
+        self.ascending = self.mode.default_ascending();
+    }
+
+    pub fn indicator(&self) -> String {
+        format!(
+            "{} {}{}",
+            self.mode.get_text(),
+            if self.ascending { "↑" } else { "↓" },
+            if self.pinned_first {
+                ", pinned first"
+            } else {
+                ""
+            }
+        )
+    }
+
+    pub fn compare(&self, a: &FileItem, b: &FileItem) -> Ordering {
+        let group = |item: &FileItem| match item.file_type {
+            MetadataType::ReturnType => 0,
+            MetadataType::CollectionType => 1,
+            _ => 2,
+        };
+
+        let by_name = || a.name.to_lowercase().cmp(&b.name.to_lowercase());
+
+        let by_mode = match self.mode {
+            SortMode::Name => by_name(),
+            SortMode::LastModified => a.last_modified.cmp(&b.last_modified),
+            SortMode::LastOpened => a.last_opened.cmp(&b.last_opened),
+            SortMode::Size => a.size.cmp(&b.size),
+            SortMode::Type => a.format.to_lowercase().cmp(&b.format.to_lowercase()),
+        };
+
+        group(a)
+            .cmp(&group(b))
+            .then_with(|| {
+                if self.pinned_first {
+                    b.pinned.cmp(&a.pinned)
+                } else {
+                    Ordering::Equal
+                }
+            })
+            .then(if self.ascending {
+                by_mode
+            } else {
+                by_mode.reverse()
+            })
+            .then_with(by_name)
+    }
 }
 
 // ** For sorting vecs of file items **
@@ -42,9 +168,11 @@ impl PartialOrd for FileItem {
     }
 }
 
+// Local files are told apart by path, everything on the tablet shares the path "."
+// and is told apart by uuid
 impl PartialEq for FileItem {
     fn eq(&self, other: &Self) -> bool {
-        self.path == other.path
+        self.path == other.path && self.uuid == other.uuid
     }
 }
 
@@ -58,6 +186,14 @@ impl Default for FileItem {
             file_type: MetadataType::DefaultType,
             uuid: String::new(),
             highlighted: false,
+            last_modified: 0,
+            last_opened: 0,
+            size: 0,
+            format: String::new(),
+            pinned: false,
+            tags: Vec::new(),
+            tree_prefix: String::new(),
+            doc_count: None,
         }
     }
 }
@@ -70,6 +206,14 @@ impl FileItem {
             file_type: MetadataType::DefaultType,
             uuid: String::new(),
             highlighted: false,
+            last_modified: 0,
+            last_opened: 0,
+            size: 0,
+            format: String::new(),
+            pinned: false,
+            tags: Vec::new(),
+            tree_prefix: String::new(),
+            doc_count: None,
         }
     }
 
@@ -92,4 +236,158 @@ impl FileItem {
         self.uuid = uuid;
         self
     }
+
+    pub fn last_modified(mut self, last_modified: u64) -> Self {
+        self.last_modified = last_modified;
+        self
+    }
+
+    pub fn last_opened(mut self, last_opened: u64) -> Self {
+        self.last_opened = last_opened;
+        self
+    }
+
+    pub fn size(mut self, size: u64) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn format(mut self, format: &str) -> Self {
+        self.format = String::from(format);
+        self
+    }
+
+    pub fn pinned(mut self, pinned: bool) -> Self {
+        self.pinned = pinned;
+        self
+    }
+
+    pub fn tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, file_type: MetadataType) -> FileItem {
+        FileItem::new().name(name).file_type(file_type)
+    }
+
+    fn sorted(order: SortOrder, mut items: Vec<FileItem>) -> Vec<String> {
+        items.sort_by(|a, b| order.compare(a, b));
+        items.into_iter().map(|item| item.name).collect()
+    }
+
+    #[test]
+    fn return_then_collections_then_documents() {
+        let items = vec![
+            item("a doc", MetadataType::DocumentType),
+            item("z folder", MetadataType::CollectionType),
+            item("../", MetadataType::ReturnType),
+            item("a folder", MetadataType::CollectionType),
+        ];
+
+        let descending = SortOrder {
+            ascending: false,
+            ..SortOrder::default()
+        };
+
+        assert_eq!(
+            sorted(SortOrder::default(), items.clone()),
+            ["../", "a folder", "z folder", "a doc"]
+        );
+        assert_eq!(
+            sorted(descending, items),
+            ["../", "z folder", "a folder", "a doc"]
+        );
+    }
+
+    #[test]
+    fn names_compare_without_case() {
+        let items = vec![
+            item("beta", MetadataType::DocumentType),
+            item("Alpha", MetadataType::DocumentType),
+            item("gamma", MetadataType::DocumentType),
+        ];
+
+        assert_eq!(
+            sorted(SortOrder::default(), items),
+            ["Alpha", "beta", "gamma"]
+        );
+    }
+
+    #[test]
+    fn pinned_first_only_within_a_group() {
+        let items = vec![
+            item("a", MetadataType::DocumentType),
+            item("b", MetadataType::DocumentType).pinned(true),
+            item("c", MetadataType::CollectionType),
+            item("d", MetadataType::CollectionType).pinned(true),
+        ];
+
+        let pinned_first = SortOrder {
+            pinned_first: true,
+            ..SortOrder::default()
+        };
+
+        assert_eq!(
+            sorted(SortOrder::default(), items.clone()),
+            ["c", "d", "a", "b"]
+        );
+        assert_eq!(sorted(pinned_first, items), ["d", "c", "b", "a"]);
+    }
+
+    #[test]
+    fn pinned_first_ignores_direction() {
+        let items = vec![
+            item("old", MetadataType::DocumentType).last_modified(1),
+            item("new", MetadataType::DocumentType).last_modified(3),
+            item("pinned", MetadataType::DocumentType)
+                .last_modified(2)
+                .pinned(true),
+        ];
+
+        let order = SortOrder {
+            mode: SortMode::LastModified,
+            ascending: false,
+            pinned_first: true,
+        };
+
+        assert_eq!(sorted(order, items), ["pinned", "new", "old"]);
+    }
+
+    #[test]
+    fn ties_fall_back_to_name() {
+        let items = vec![
+            item("b", MetadataType::DocumentType).size(10),
+            item("c", MetadataType::DocumentType).size(20),
+            item("a", MetadataType::DocumentType).size(10),
+        ];
+
+        let by_size = SortOrder {
+            mode: SortMode::Size,
+            ascending: false,
+            pinned_first: false,
+        };
+
+        assert_eq!(sorted(by_size, items), ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn cycling_resets_direction() {
+        let mut order = SortOrder::default();
+
+        order.cycle_mode();
+        assert_eq!(
+            (order.mode, order.ascending),
+            (SortMode::LastModified, false)
+        );
+
+        order.mode = SortMode::Size;
+        order.cycle_mode();
+        assert_eq!((order.mode, order.ascending), (SortMode::Type, true));
+    }
 }
@@ -0,0 +1,166 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use serde::{Deserialize, Serialize};
+use tui::layout::{Constraint, Direction, Layout, Rect};
+
+// Bounds on the share of the screen the first pane gets, in percent
+const MIN_RATIO: u16 = 20;
+const MAX_RATIO: u16 = 80;
+// Share of the width the preview column takes in the three column layout
+const PREVIEW_WIDTH: u16 = 30;
+// Rows a stacked pane keeps however far the split is moved: borders and one item
+const MIN_PANE_HEIGHT: u16 = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum LayoutMode {
+    #[default]
+    SideBySide,
+    Stacked,
+    // Only the focused pane, for terminals too narrow to share
+    Zoom,
+    // Both panes plus details of the item under the cursor
+    Preview,
+}
+
+// How the panes share the screen
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PaneLayout {
+    pub mode: LayoutMode,
+    // Percentage given to the local pane when both are shown
+    pub ratio: u16,
+}
+
+// Where each part goes, None for parts the layout hides
+#[derive(Default)]
+pub struct PaneAreas {
+    pub local: Option<Rect>,
+    pub remote: Option<Rect>,
+    pub preview: Option<Rect>,
+}
+
+impl LayoutMode {
+    pub fn next(&self) -> LayoutMode {
+        match self {
+            Self::SideBySide => Self::Stacked,
+            Self::Stacked => Self::Zoom,
+            Self::Zoom => Self::Preview,
+            Self::Preview => Self::SideBySide,
+        }
+    }
+
+    pub fn get_text(&self) -> &str {
+        match self {
+            Self::SideBySide => "side by side",
+            Self::Stacked => "stacked",
+            Self::Zoom => "single pane",
+            Self::Preview => "preview",
+        }
+    }
+}
+
+impl Default for PaneLayout {
+    fn default() -> Self {
+        Self {
+            mode: LayoutMode::SideBySide,
+            ratio: 50,
+        }
+    }
+}
+
+impl PaneLayout {
+    pub fn cycle_mode(&mut self) {
+        self.mode = self.mode.next();
+    }
+
+    pub fn resize(&mut self, delta: i16) {
+        self.ratio = self
+            .ratio
+            .saturating_add_signed(delta)
+            .clamp(MIN_RATIO, MAX_RATIO);
+    }
+
+    // `local_focused` picks the pane that stays when zoomed
+    pub fn split(&self, area: Rect, local_focused: bool) -> PaneAreas {
+        let ratio = self.ratio.clamp(MIN_RATIO, MAX_RATIO);
+
+        let halves = |direction: Direction, area: Rect| {
+            let constraints = match direction {
+                Direction::Horizontal => [
+                    Constraint::Percentage(ratio),
+                    Constraint::Percentage(100 - ratio),
+                ],
+                Direction::Vertical => {
+                    let top = (u32::from(area.height) * u32::from(ratio) / 100) as u16;
+
+                    [
+                        Constraint::Length(
+                            top.max(MIN_PANE_HEIGHT)
+                                .min(area.height.saturating_sub(MIN_PANE_HEIGHT)),
+                        ),
+                        Constraint::Min(0),
+                    ]
+                }
+            };
+
+            Layout::default()
+                .direction(direction)
+                .constraints(constraints.as_ref())
+                .split(area)
+        };
+
+        match self.mode {
+            LayoutMode::SideBySide | LayoutMode::Stacked => {
+                let direction = if self.mode == LayoutMode::Stacked {
+                    Direction::Vertical
+                } else {
+                    Direction::Horizontal
+                };
+                let panes = halves(direction, area);
+
+                PaneAreas {
+                    local: Some(panes[0]),
+                    remote: Some(panes[1]),
+                    preview: None,
+                }
+            }
+            LayoutMode::Zoom if local_focused => PaneAreas {
+                local: Some(area),
+                ..Default::default()
+            },
+            LayoutMode::Zoom => PaneAreas {
+                remote: Some(area),
+                ..Default::default()
+            },
+            LayoutMode::Preview => {
+                let columns = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints(
+                        [
+                            Constraint::Percentage(100 - PREVIEW_WIDTH),
+                            Constraint::Percentage(PREVIEW_WIDTH),
+                        ]
+                        .as_ref(),
+                    )
+                    .split(area);
+                let panes = halves(Direction::Horizontal, columns[0]);
+
+                PaneAreas {
+                    local: Some(panes[0]),
+                    remote: Some(panes[1]),
+                    preview: Some(columns[1]),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,205 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use tui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    widgets::{Block, BorderType, Borders, List, ListItem},
+};
+
+use crate::{
+    intern_error::Error,
+    pages::{document_page_list, Page},
+};
+
+use super::{super::config, CursorDirection};
+
+// Lists the pages of one document in place of the remote pane
+pub struct PageView {
+    pub uuid: String,
+    pub name: String,
+    pages: Vec<Page>,
+    // Ids of the marked pages
+    selected: Vec<String>,
+    cursor_idx: usize,
+    offset_pos: usize,
+    render_area: Rect,
+    pub focused: bool,
+}
+
+impl PageView {
+    pub fn new(uuid: &str, name: &str) -> Self {
+        Self {
+            uuid: String::from(uuid),
+            name: String::from(name),
+            pages: Vec::new(),
+            selected: Vec::new(),
+            cursor_idx: 0,
+            offset_pos: 0,
+            render_area: Rect::default(),
+            focused: false,
+        }
+    }
+
+    pub fn load(&mut self) -> Result<(), Error> {
+        self.pages = document_page_list(&self.uuid)?;
+
+        self.selected
+            .retain(|id| self.pages.iter().any(|page| page.id == *id));
+        self.cursor_idx = self.cursor_idx.min(self.pages.len().saturating_sub(1));
+
+        Ok(())
+    }
+
+    pub fn get_cursor_selection(&self) -> Option<&Page> {
+        self.pages.get(self.cursor_idx)
+    }
+
+    // Marked pages in page order, or the one under the cursor
+    pub fn get_targets(&self) -> Vec<String> {
+        if self.selected.is_empty() {
+            return self
+                .get_cursor_selection()
+                .map(|page| page.id.clone())
+                .into_iter()
+                .collect();
+        }
+
+        self.pages
+            .iter()
+            .filter(|page| self.selected.contains(&page.id))
+            .map(|page| page.id.clone())
+            .collect()
+    }
+
+    pub fn toggle_selected(&mut self) {
+        let Some(id) = self.get_cursor_selection().map(|page| page.id.clone()) else {
+            return;
+        };
+
+        match self.selected.iter().position(|selected| *selected == id) {
+            Some(idx) => {
+                self.selected.remove(idx);
+            }
+            None => self.selected.push(id),
+        }
+    }
+
+    pub fn toggle_select_all(&mut self) {
+        if self.selected.len() == self.pages.len() {
+            self.selected.clear();
+        } else {
+            self.selected = self.pages.iter().map(|page| page.id.clone()).collect();
+        }
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selected.clear();
+    }
+
+    // Keep the cursor on a page after the list changed under it
+    pub fn cursor_to(&mut self, id: &str) {
+        if let Some(idx) = self.pages.iter().position(|page| page.id == id) {
+            self.cursor_idx = idx;
+            self.cursor_move_by(0);
+        }
+    }
+
+    pub fn cursor_move(&mut self, direction: CursorDirection) {
+        self.cursor_move_by(match direction {
+            CursorDirection::Down => 1,
+            CursorDirection::Up => -1,
+            CursorDirection::PgDn => 15,
+            CursorDirection::PgUp => -15,
+        });
+    }
+
+    fn cursor_move_by(&mut self, delta: isize) {
+        self.cursor_idx = self
+            .cursor_idx
+            .saturating_add_signed(delta)
+            .min(self.pages.len().saturating_sub(1));
+
+        let adj_height = usize::from(self.render_area.height.saturating_sub(super::WIDGET_OFFSET));
+
+        if self.cursor_idx < self.offset_pos {
+            self.offset_pos = self.cursor_idx;
+        } else if self.cursor_idx > self.offset_pos + adj_height {
+            self.offset_pos = self.cursor_idx - adj_height;
+        }
+    }
+
+    pub fn render(&mut self, render_area: Rect) -> List<'_> {
+        self.render_area = render_area;
+
+        let adj_height = usize::from(render_area.height.saturating_sub(super::WIDGET_OFFSET));
+
+        let items: Vec<ListItem> = self
+            .pages
+            .iter()
+            .enumerate()
+            .skip(self.offset_pos)
+            .take(adj_height + 1)
+            .map(|(idx, page)| {
+                let mut style = if page.has_lines {
+                    Style::default()
+                } else {
+                    Style::default().add_modifier(Modifier::DIM)
+                };
+
+                if self.selected.contains(&page.id) {
+                    style = style.fg(config::THEME.highlight);
+                }
+
+                if self.focused && idx == self.cursor_idx {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+
+                let mut text = format!(
+                    "{:>4} {} {}",
+                    idx + 1,
+                    if page.has_thumbnail { "▣" } else { " " },
+                    page.template.as_deref().unwrap_or("-")
+                );
+
+                if !page.has_lines {
+                    text.push_str(" (empty)");
+                }
+
+                for tag in &page.tags {
+                    text.push_str(&format!(" #{}", tag));
+                }
+
+                ListItem::new(text).style(style)
+            })
+            .collect();
+
+        let mut title = format!("{} [{} pages]", self.name, self.pages.len());
+
+        if !self.selected.is_empty() {
+            title.push_str(&format!(" {} selected", self.selected.len()));
+        }
+
+        List::new(items)
+            .block(
+                Block::default()
+                    .title(title)
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Double),
+            )
+            .style(
+                Style::default()
+                    .fg(config::THEME.foreground)
+                    .bg(config::THEME.background),
+            )
+    }
+}
@@ -0,0 +1,168 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::{fs::File, io::Read, path::Path};
+
+use tui::{
+    style::{Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Paragraph, Wrap},
+};
+
+use crate::{
+    config,
+    fs_interface::{document_pages, MetadataType},
+    transfer::format_size,
+};
+
+use super::{file_item::FileItem, status_bar::format_millis};
+
+// Bytes read from a local file to show as text
+const TEXT_PREVIEW_BYTES: u64 = 8 * 1024;
+
+// Details of the item under the cursor of the focused pane. Worked out once per item
+// rather than on every frame since documents have to be read from the mirror.
+#[derive(Default)]
+pub struct Preview {
+    key: Option<(Box<Path>, String)>,
+    lines: Vec<(String, String)>,
+    text: Option<String>,
+}
+
+// Start of a local file if it reads as text
+fn text_head(path: &Path) -> Option<String> {
+    let mut buf = Vec::new();
+
+    File::open(path)
+        .ok()?
+        .take(TEXT_PREVIEW_BYTES)
+        .read_to_end(&mut buf)
+        .ok()?;
+
+    if buf.contains(&0) {
+        return None;
+    }
+
+    // The cut-off can land inside a character, drop whatever doesn't decode
+    Some(
+        String::from_utf8_lossy(&buf)
+            .trim_end_matches('\u{fffd}')
+            .to_string(),
+    )
+}
+
+impl Preview {
+    pub fn update(&mut self, item: Option<&FileItem>, remote: bool) {
+        let key = item.map(|item| (item.path.clone(), item.uuid.clone()));
+
+        if key == self.key {
+            return;
+        }
+
+        self.key = key;
+        self.lines = Vec::new();
+        self.text = None;
+
+        let Some(item) = item else {
+            return;
+        };
+
+        let time = |millis: u64| {
+            i64::try_from(millis)
+                .ok()
+                .filter(|millis| *millis > 0)
+                .and_then(format_millis)
+                .unwrap_or_else(|| String::from("-"))
+        };
+
+        let kind = match (item.file_type, remote) {
+            (MetadataType::CollectionType, true) => String::from("collection"),
+            (MetadataType::CollectionType, false) => String::from("directory"),
+            (MetadataType::DocumentType, _) if !item.format.is_empty() => item.format.clone(),
+            (MetadataType::DocumentType, _) => String::from("file"),
+            _ => return,
+        };
+
+        self.lines.push((String::from("Type"), kind));
+
+        if item.file_type == MetadataType::DocumentType {
+            self.lines
+                .push((String::from("Size"), format_size(item.size)));
+        }
+
+        self.lines
+            .push((String::from("Modified"), time(item.last_modified)));
+        self.lines
+            .push((String::from("Opened"), time(item.last_opened)));
+
+        if remote {
+            if item.file_type == MetadataType::DocumentType {
+                self.lines.push((
+                    String::from("Pages"),
+                    document_pages(&item.uuid).len().to_string(),
+                ));
+            }
+
+            self.lines.push((
+                String::from("Pinned"),
+                String::from(if item.pinned { "yes" } else { "no" }),
+            ));
+
+            if !item.tags.is_empty() {
+                self.lines
+                    .push((String::from("Tags"), item.tags.join(", ")));
+            }
+        } else {
+            self.lines
+                .push((String::from("Path"), item.path.display().to_string()));
+
+            if item.file_type == MetadataType::DocumentType {
+                self.text = text_head(&item.path);
+            }
+        }
+    }
+
+    pub fn render(&self) -> Paragraph<'_> {
+        let mut spans: Vec<Spans> = self
+            .lines
+            .iter()
+            .map(|(label, value)| {
+                Spans::from(vec![
+                    Span::styled(
+                        format!("{}: ", label),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw(value.as_str()),
+                ])
+            })
+            .collect();
+
+        if let Some(text) = &self.text {
+            spans.push(Spans::default());
+            spans.extend(text.lines().map(Spans::from));
+        }
+
+        Paragraph::new(spans)
+            .block(
+                Block::default()
+                    .title("Preview")
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Double),
+            )
+            .style(
+                Style::default()
+                    .fg(config::THEME.foreground)
+                    .bg(config::THEME.background),
+            )
+            .wrap(Wrap { trim: false })
+    }
+}
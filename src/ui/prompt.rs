@@ -0,0 +1,121 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crossterm::event::KeyCode;
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::Style,
+    widgets::{Block, BorderType, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use super::super::config;
+
+// What to do with the text once the prompt is submitted
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PromptAction {
+    AddTag,
+    RemoveTag,
+    TemplateName,
+    TemplateIcon,
+    TemplateCategories,
+    MakeDir,
+    Rename,
+    MovePages,
+    MergeName,
+    MergeOrder,
+    MergeTrash,
+}
+
+pub enum PromptResult {
+    Pending,
+    Cancelled,
+    Submitted(PromptAction, String),
+}
+
+// Single line text input shown over the panes, takes all key input while open
+pub struct Prompt {
+    title: String,
+    input: String,
+    action: PromptAction,
+}
+
+const PROMPT_WIDTH: u16 = 50;
+
+impl Prompt {
+    pub fn new(title: &str, action: PromptAction) -> Self {
+        Self {
+            title: String::from(title),
+            input: String::new(),
+            action,
+        }
+    }
+
+    // Start off with some text, e.g. a default the user can accept with Enter
+    pub fn input(mut self, input: &str) -> Self {
+        self.input = String::from(input);
+        self
+    }
+
+    pub fn key_handler(&mut self, keycode: KeyCode) -> PromptResult {
+        match keycode {
+            KeyCode::Esc => PromptResult::Cancelled,
+            KeyCode::Enter => match self.input.trim() {
+                "" => PromptResult::Cancelled,
+                input => PromptResult::Submitted(self.action, String::from(input)),
+            },
+            KeyCode::Backspace => {
+                self.input.pop();
+                PromptResult::Pending
+            }
+            KeyCode::Char(c) => {
+                self.input.push(c);
+                PromptResult::Pending
+            }
+            _ => PromptResult::Pending,
+        }
+    }
+
+    pub fn render<B: Backend>(&self, f: &mut Frame<B>) {
+        let screen = f.size();
+        let width = PROMPT_WIDTH.min(screen.width);
+        let area = Rect::new(
+            screen.x + (screen.width - width) / 2,
+            screen.y + screen.height.saturating_sub(3) / 2,
+            width,
+            3.min(screen.height),
+        );
+
+        // Keep the end of long input in view
+        let visible = usize::from(width.saturating_sub(3));
+        let skip = self.input.chars().count().saturating_sub(visible);
+        let text: String = self.input.chars().skip(skip).collect();
+
+        f.render_widget(Clear, area);
+        f.render_widget(
+            Paragraph::new(format!("{}_", text))
+                .block(
+                    Block::default()
+                        .title(self.title.as_str())
+                        .borders(Borders::ALL)
+                        .border_type(BorderType::Double),
+                )
+                .style(
+                    Style::default()
+                        .fg(config::THEME.highlight)
+                        .bg(config::THEME.background),
+                ),
+            area,
+        );
+    }
+}
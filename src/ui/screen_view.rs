@@ -0,0 +1,89 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use tui::{
+    style::{Modifier, Style},
+    widgets::{Block, BorderType, Borders, List, ListItem},
+};
+
+use crate::screens::{Screen, ScreenState};
+
+use super::{super::config, CursorDirection};
+
+// Lists the sleep, power off and starting screens in place of the remote pane
+#[derive(Default)]
+pub struct ScreenView {
+    states: Vec<(Screen, ScreenState)>,
+    cursor_idx: usize,
+    pub focused: bool,
+}
+
+impl ScreenView {
+    // Show the screen states read from the tablet
+    pub fn set_states(&mut self, states: Vec<(Screen, ScreenState)>) {
+        self.states = states;
+        self.cursor_idx = self.cursor_idx.min(self.states.len().saturating_sub(1));
+    }
+
+    pub fn get_cursor_selection(&self) -> Option<Screen> {
+        self.states.get(self.cursor_idx).map(|(screen, _)| *screen)
+    }
+
+    pub fn cursor_move(&mut self, direction: CursorDirection) {
+        self.cursor_idx = match direction {
+            CursorDirection::Up | CursorDirection::PgUp => self.cursor_idx.saturating_sub(1),
+            CursorDirection::Down | CursorDirection::PgDn => {
+                (self.cursor_idx + 1).min(self.states.len().saturating_sub(1))
+            }
+        };
+    }
+
+    pub fn render(&self) -> List<'_> {
+        let items: Vec<ListItem> = self
+            .states
+            .iter()
+            .enumerate()
+            .map(|(idx, (screen, state))| {
+                let mut style = match state {
+                    ScreenState::Customised => Style::default().add_modifier(Modifier::BOLD),
+                    ScreenState::Unknown => Style::default().fg(config::THEME.alert),
+                    ScreenState::Original => Style::default(),
+                };
+
+                if self.focused && idx == self.cursor_idx {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+
+                ListItem::new(format!(
+                    " {:<10} {:<15} {}",
+                    screen.get_text(),
+                    screen.file_name(),
+                    state.get_text()
+                ))
+                .style(style)
+            })
+            .collect();
+
+        List::new(items)
+            .block(
+                Block::default()
+                    .title("screens")
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Double),
+            )
+            .style(
+                Style::default()
+                    .fg(config::THEME.foreground)
+                    .bg(config::THEME.background),
+            )
+    }
+}
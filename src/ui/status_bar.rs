@@ -0,0 +1,123 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use chrono::{Local, TimeZone};
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Span, Spans},
+    widgets::Paragraph,
+    Frame,
+};
+
+use crate::{config, intern_error::Error};
+
+// Rows taken by the bar, one for state and one for key hints
+pub const STATUS_BAR_HEIGHT: u16 = 2;
+
+// Local time of a timestamp in milliseconds, leaving out the date if it's today
+pub fn format_millis(millis: i64) -> Option<String> {
+    let time = Local.timestamp_millis_opt(millis).single()?;
+
+    Some(if time.date_naive() == Local::now().date_naive() {
+        time.format("%H:%M").to_string()
+    } else {
+        time.format("%Y-%m-%d %H:%M").to_string()
+    })
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum ConnectionState {
+    // Nothing has talked to the tablet yet this session
+    #[default]
+    Unknown,
+    Connected,
+    Offline,
+}
+
+impl ConnectionState {
+    pub fn get_text(&self) -> &str {
+        match self {
+            Self::Unknown => "not connected yet",
+            Self::Connected => "connected",
+            Self::Offline => "offline",
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct StatusBar {
+    pub connection: ConnectionState,
+    // Milliseconds since the epoch of the last successful sync
+    pub last_sync: Option<i64>,
+}
+
+impl StatusBar {
+    // Anything that went over ssh tells us whether the tablet can be reached. Other
+    // failures say nothing about the connection and leave it as it was.
+    pub fn record<T>(&mut self, result: &Result<T, Error>) {
+        match result {
+            Ok(_) => self.connection = ConnectionState::Connected,
+            Err(why) if matches!(why.root(), Error::ConnectionError(_)) => {
+                self.connection = ConnectionState::Offline
+            }
+            Err(_) => (),
+        }
+    }
+
+    fn last_sync_text(&self) -> String {
+        self.last_sync
+            .and_then(format_millis)
+            .unwrap_or_else(|| String::from("never"))
+    }
+
+    // `pane` describes the focused pane, `hints` are the keys that make sense in it
+    pub fn render<B: Backend>(&self, f: &mut Frame<B>, area: Rect, pane: &str, hints: &str) {
+        let rows = Layout::default()
+            .direction(tui::layout::Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Length(1)].as_ref())
+            .split(area);
+
+        let style = Style::default()
+            .fg(config::THEME.background)
+            .bg(config::THEME.foreground);
+
+        let connection_style = match self.connection {
+            ConnectionState::Unknown => style,
+            ConnectionState::Connected => style.bg(config::THEME.success),
+            ConnectionState::Offline => style.bg(config::THEME.alert),
+        };
+
+        f.render_widget(
+            Paragraph::new(Spans::from(vec![
+                Span::styled(format!(" {} │ {} ", pane, config::SETTINGS.ssh_name), style),
+                Span::styled(
+                    format!(" {} ", self.connection.get_text()),
+                    connection_style.add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(format!(" │ last sync {}", self.last_sync_text()), style),
+            ]))
+            .style(style),
+            rows[0],
+        );
+
+        f.render_widget(
+            Paragraph::new(format!(" {}", hints)).style(
+                Style::default()
+                    .fg(config::THEME.foreground)
+                    .bg(config::THEME.background),
+            ),
+            rows[1],
+        );
+    }
+}
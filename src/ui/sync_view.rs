@@ -0,0 +1,104 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use tui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    widgets::{Block, BorderType, Borders, List, ListItem},
+};
+
+use crate::sync_plan::{ChangeKind, SyncPlan};
+
+use super::{super::config, CursorDirection};
+
+// What a sync would change, shown in place of the remote pane until it's confirmed
+pub struct SyncView {
+    plan: SyncPlan,
+    cursor_idx: usize,
+    offset_pos: usize,
+    render_area: Rect,
+    pub focused: bool,
+}
+
+impl SyncView {
+    pub fn new(plan: SyncPlan) -> Self {
+        Self {
+            plan,
+            cursor_idx: 0,
+            offset_pos: 0,
+            render_area: Rect::default(),
+            focused: false,
+        }
+    }
+
+    pub fn cursor_move(&mut self, direction: CursorDirection) {
+        let delta: isize = match direction {
+            CursorDirection::Down => 1,
+            CursorDirection::Up => -1,
+            CursorDirection::PgDn => 15,
+            CursorDirection::PgUp => -15,
+        };
+
+        self.cursor_idx = self
+            .cursor_idx
+            .saturating_add_signed(delta)
+            .min(self.plan.changes.len().saturating_sub(1));
+
+        let adj_height = usize::from(self.render_area.height.saturating_sub(super::WIDGET_OFFSET));
+
+        if self.cursor_idx < self.offset_pos {
+            self.offset_pos = self.cursor_idx;
+        } else if self.cursor_idx > self.offset_pos + adj_height {
+            self.offset_pos = self.cursor_idx - adj_height;
+        }
+    }
+
+    pub fn render(&mut self, render_area: Rect) -> List<'_> {
+        self.render_area = render_area;
+
+        let adj_height = usize::from(render_area.height.saturating_sub(super::WIDGET_OFFSET));
+
+        let items: Vec<ListItem> = self
+            .plan
+            .changes
+            .iter()
+            .enumerate()
+            .skip(self.offset_pos)
+            .take(adj_height + 1)
+            .map(|(idx, change)| {
+                let mut style = match change.kind {
+                    ChangeKind::Conflict => Style::default().fg(config::THEME.alert),
+                    ChangeKind::New | ChangeKind::Modified => Style::default(),
+                };
+
+                if self.focused && idx == self.cursor_idx {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+
+                ListItem::new(change.get_text()).style(style)
+            })
+            .collect();
+
+        List::new(items)
+            .block(
+                Block::default()
+                    .title(format!("Sync tablet → mirror: {}", self.plan.summary()))
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Double),
+            )
+            .style(
+                Style::default()
+                    .fg(config::THEME.foreground)
+                    .bg(config::THEME.background),
+            )
+    }
+}
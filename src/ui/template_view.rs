@@ -0,0 +1,158 @@
+/*
+This file is part of Noteworthy.
+
+Noteworthy is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License
+as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+
+Noteworthy is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty
+of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License along with Noteworthy. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use tui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    widgets::{Block, BorderType, Borders, List, ListItem},
+};
+
+use crate::templates::{user_templates, Template};
+
+use super::{super::config, CursorDirection};
+
+// Lists the templates installed on the tablet in place of the remote pane
+#[derive(Default)]
+pub struct TemplateView {
+    templates: Vec<Template>,
+    // File names of templates added through noteworthy
+    user: Vec<String>,
+    // Registered templates the tablet no longer lists, usually after a firmware update
+    missing: Vec<Template>,
+    cursor_idx: usize,
+    offset_pos: usize,
+    render_area: Rect,
+    pub focused: bool,
+}
+
+impl TemplateView {
+    // Show the templates read from the tablet, marking the ones added through noteworthy
+    pub fn set_templates(&mut self, templates: Vec<Template>) {
+        self.templates = templates;
+
+        let registry = user_templates();
+
+        self.user = registry
+            .iter()
+            .map(|user| user.template.filename.clone())
+            .collect();
+
+        self.missing = registry
+            .into_iter()
+            .map(|user| user.template)
+            .filter(|template| {
+                !self
+                    .templates
+                    .iter()
+                    .any(|installed| installed.filename == template.filename)
+            })
+            .collect();
+
+        self.cursor_idx = self.cursor_idx.min(self.len().saturating_sub(1));
+    }
+
+    fn len(&self) -> usize {
+        self.templates.len() + self.missing.len()
+    }
+
+    fn get(&self, idx: usize) -> Option<&Template> {
+        self.templates
+            .get(idx)
+            .or_else(|| self.missing.get(idx.checked_sub(self.templates.len())?))
+    }
+
+    pub fn get_cursor_selection(&self) -> Option<&Template> {
+        self.get(self.cursor_idx)
+    }
+
+    pub fn is_user(&self, template: &Template) -> bool {
+        self.user.contains(&template.filename)
+    }
+
+    pub fn cursor_move(&mut self, direction: CursorDirection) {
+        let delta: isize = match direction {
+            CursorDirection::Down => 1,
+            CursorDirection::Up => -1,
+            CursorDirection::PgDn => 15,
+            CursorDirection::PgUp => -15,
+        };
+
+        self.cursor_idx = self
+            .cursor_idx
+            .saturating_add_signed(delta)
+            .min(self.len().saturating_sub(1));
+
+        let adj_height = usize::from(self.render_area.height.saturating_sub(super::WIDGET_OFFSET));
+
+        if self.cursor_idx < self.offset_pos {
+            self.offset_pos = self.cursor_idx;
+        } else if self.cursor_idx > self.offset_pos + adj_height {
+            self.offset_pos = self.cursor_idx - adj_height;
+        }
+    }
+
+    pub fn render(&mut self, render_area: Rect) -> List<'_> {
+        self.render_area = render_area;
+
+        let adj_height = usize::from(render_area.height.saturating_sub(super::WIDGET_OFFSET));
+
+        let items: Vec<ListItem> = (0..self.len())
+            .skip(self.offset_pos)
+            .take(adj_height + 1)
+            .filter_map(|idx| Some((idx, self.get(idx)?)))
+            .map(|(idx, template)| {
+                let missing = idx >= self.templates.len();
+
+                let (marker, mut style) = if missing {
+                    ("!", Style::default().fg(config::THEME.alert))
+                } else if self.is_user(template) {
+                    ("+", Style::default().add_modifier(Modifier::BOLD))
+                } else {
+                    (" ", Style::default())
+                };
+
+                if self.focused && idx == self.cursor_idx {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+
+                let mut text = format!("{} {}", marker, template.name);
+
+                if !template.categories.is_empty() {
+                    text.push_str(&format!(" ({})", template.categories.join(", ")));
+                }
+
+                if missing {
+                    text.push_str(" [missing]");
+                }
+
+                ListItem::new(text).style(style)
+            })
+            .collect();
+
+        List::new(items)
+            .block(
+                Block::default()
+                    .title(format!(
+                        "templates [{} installed, {} added]",
+                        self.templates.len(),
+                        self.user.len()
+                    ))
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Double),
+            )
+            .style(
+                Style::default()
+                    .fg(config::THEME.foreground)
+                    .bg(config::THEME.background),
+            )
+    }
+}